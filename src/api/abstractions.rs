@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use super::metadata::ImageMetadata;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Prob {
@@ -119,6 +121,53 @@ pub struct AIMetadataRaw {
     pub classes: Vec<String>,
     pub modality: Option<String>, // "image" or "audio", defaults to "image"
     pub audio_config: Option<AudioConfig>,
+    #[serde(flatten)]
+    pub info: ModelInfo,
+}
+
+/// The descriptive fields `.bq` version 2 adds to the metadata JSON. All
+/// optional, so version 1 files read as the default.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ModelInfo {
+    /// Format version; missing means 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bq_version: Option<u32>,
+    /// Semantic version of the model itself, e.g. `1.2.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// SPDX identifier, e.g. `CC-BY-4.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<InputSpec>,
+    /// Hex SHA-256 of the ONNX section, checked on load.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onnx_sha256: Option<String>,
+    /// Hex SHA-256 of each external data section, by file name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data_sha256: BTreeMap<String, String>,
+}
+
+impl ModelInfo {
+    pub fn version(&self) -> u32 {
+        self.bq_version.unwrap_or(1)
+    }
+}
+
+/// The model's first input, as the ONNX graph declares it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InputSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `-1` for dynamic dimensions.
+    pub shape: Vec<i64>,
+    /// `NCHW` or `NHWC`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -148,12 +197,113 @@ pub fn sidecar_predictions_path(
     Ok(input_path.with_file_name(format!("{}_predictions.json", stem)))
 }
 
-fn load_sidecar_aioutput(file_path: &std::path::Path) -> Option<AIOutputs> {
+/// Identifies the model run that produced a sidecar: model name, the `.bq`'s
+/// SHA-256 (see [`BqPayload::identity`](super::bq::BqPayload::identity))
+/// and the config it ran with (plus the crop classifier, if any). Batch
+/// runs compare it against the current run to decide whether a file can be
+/// skipped.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ModelStamp {
+    pub model: String,
+    pub bq_sha256: String,
+    pub config: ModelConfig,
+    /// How a pipeline stage cut its crops, when not the defaults.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<super::pipeline::CropOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_cls: Option<Box<ModelStamp>>,
+}
+
+/// Everything a sidecar records about how its outputs were produced.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Provenance {
+    #[serde(flatten)]
+    pub stamp: ModelStamp,
+    pub architecture: String,
+    pub classes: Vec<String>,
+    pub ep: String,
+    pub created_at: String, // RFC 3339, local time
+    pub boquilahub_version: String,
+}
+
+/// Bumped whenever the envelope layout changes.
+pub const SIDECAR_SCHEMA_VERSION: u32 = 1;
+
+/// `_predictions.json` layout: provenance fields at the top level next to
+/// `outputs`, which holds the `AIOutputs` (image/audio) or the `PredVideo`.
+/// Images also carry their EXIF `metadata`.
+#[derive(Serialize, Deserialize)]
+struct SidecarEnvelope<P, M, O> {
+    schema_version: u32,
+    #[serde(flatten)]
+    provenance: P,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<M>,
+    outputs: O,
+}
+
+/// Either layout a sidecar may have on disk. `Bare` is the pre-envelope form
+/// (bare `AIOutputs`, or a bare `PredVideo`) that older versions wrote.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SidecarFile<O> {
+    Envelope(SidecarEnvelope<Option<Provenance>, ImageMetadata, O>),
+    Bare(O),
+}
+
+/// What a sidecar holds once read, whichever layout it was in.
+struct Sidecar<O> {
+    outputs: O,
+    provenance: Option<Provenance>,
+    metadata: Option<ImageMetadata>,
+}
+
+impl<O> SidecarFile<O> {
+    fn into_sidecar(self) -> Sidecar<O> {
+        match self {
+            SidecarFile::Envelope(e) => Sidecar {
+                outputs: e.outputs,
+                provenance: e.provenance,
+                metadata: e.metadata,
+            },
+            SidecarFile::Bare(outputs) => Sidecar {
+                outputs,
+                provenance: None,
+                metadata: None,
+            },
+        }
+    }
+}
+
+fn sidecar_json<O: Serialize>(
+    provenance: Option<&Provenance>,
+    metadata: Option<&ImageMetadata>,
+    outputs: &O,
+) -> serde_json::Result<String> {
+    serde_json::to_string(&SidecarEnvelope {
+        schema_version: SIDECAR_SCHEMA_VERSION,
+        provenance,
+        metadata,
+        outputs,
+    })
+}
+
+fn read_sidecar<O: serde::de::DeserializeOwned>(
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<Sidecar<O>> {
+    let file = std::fs::File::open(path)?;
+    let sidecar: SidecarFile<O> = serde_json::from_reader(std::io::BufReader::new(file))?;
+    Ok(sidecar.into_sidecar())
+}
+
+fn load_sidecar<O: serde::de::DeserializeOwned>(
+    file_path: &std::path::Path,
+) -> Option<Sidecar<O>> {
     let path = sidecar_predictions_path(file_path).ok()?;
     if !path.exists() {
         return None;
     }
-    AIOutputs::from_file(path).ok()
+    read_sidecar(path).ok()
 }
 
 /// Shared shape for the three media prediction types. Lets the GUI talk to
@@ -163,9 +313,23 @@ pub trait Pred {
     fn file_path(&self) -> &std::path::Path;
     fn is_processed(&self) -> bool;
 
-    /// JSON written to the sidecar `_predictions.json`. Image and audio dump
-    /// the bare `AIOutputs` (one prediction per file); video dumps the whole
-    /// `PredVideo` so frames-as-array + probe metadata round-trip.
+    /// How the current predictions were produced, if known.
+    fn provenance(&self) -> Option<&Provenance>;
+
+    fn stamp(&self) -> Option<&ModelStamp> {
+        self.provenance().map(|p| &p.stamp)
+    }
+
+    /// True when the predictions were produced by exactly `stamp`'s model,
+    /// weights and config, so re-running would be wasted work.
+    fn is_up_to_date(&self, stamp: &ModelStamp) -> bool {
+        self.is_processed() && self.stamp() == Some(stamp)
+    }
+
+    /// JSON written to the sidecar `_predictions.json`: the versioned
+    /// envelope around the outputs. Image and audio put the `AIOutputs` (one
+    /// prediction per file) in `outputs`; video puts the whole `PredVideo`
+    /// so frames-as-array + probe metadata round-trip.
     fn predictions_json(&self) -> serde_json::Result<String>;
 
     fn predictions_file_path(&self) -> std::io::Result<std::path::PathBuf> {
@@ -209,20 +373,38 @@ pub struct PredImg {
     pub file_path: std::path::PathBuf,
     pub aioutput: Option<AIOutputs>,
     pub wasprocessed: bool,
+    pub provenance: Option<Provenance>,
+    pub metadata: Option<ImageMetadata>,
 }
 
 impl PredImg {
+    /// Loads the sidecar if there is one, with the EXIF metadata it saved.
+    /// The file itself isn't opened; see [`Self::load_metadata`].
     pub fn new_simple(file_path: std::path::PathBuf) -> Self {
-        let aioutput = load_sidecar_aioutput(&file_path);
+        let (aioutput, provenance, metadata) = match load_sidecar(&file_path) {
+            Some(sidecar) => (Some(sidecar.outputs), sidecar.provenance, sidecar.metadata),
+            None => (None, None, None),
+        };
         PredImg {
             wasprocessed: aioutput.is_some(),
+            metadata,
             aioutput,
             file_path,
+            provenance,
+        }
+    }
+
+    /// Reads the file's EXIF metadata unless the sidecar had it. Called
+    /// before exports and event grouping, so opening a folder stays cheap.
+    pub fn load_metadata(&mut self) {
+        if self.metadata.is_none() {
+            self.metadata = ImageMetadata::read(&self.file_path);
         }
     }
 
     pub fn reset(&mut self) {
         self.wasprocessed = false;
+        self.provenance = None;
     }
 }
 
@@ -233,8 +415,11 @@ impl Pred for PredImg {
     fn is_processed(&self) -> bool {
         self.wasprocessed
     }
+    fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
     fn predictions_json(&self) -> serde_json::Result<String> {
-        serde_json::to_string(&self.aioutput)
+        sidecar_json(self.provenance.as_ref(), self.metadata.as_ref(), &self.aioutput)
     }
 }
 
@@ -256,20 +441,26 @@ pub struct PredAudio {
     pub file_path: std::path::PathBuf,
     pub aioutput: Option<AIOutputs>,
     pub wasprocessed: bool,
+    pub provenance: Option<Provenance>,
 }
 
 impl PredAudio {
     pub fn new_simple(file_path: std::path::PathBuf) -> Self {
-        let aioutput = load_sidecar_aioutput(&file_path);
+        let (aioutput, provenance) = match load_sidecar(&file_path) {
+            Some(sidecar) => (Some(sidecar.outputs), sidecar.provenance),
+            None => (None, None),
+        };
         PredAudio {
             wasprocessed: aioutput.is_some(),
             aioutput,
             file_path,
+            provenance,
         }
     }
 
     pub fn reset(&mut self) {
         self.wasprocessed = false;
+        self.provenance = None;
     }
 
     pub fn audio_predictions(&self) -> Option<&[AudioProb]> {
@@ -295,8 +486,11 @@ impl Pred for PredAudio {
     fn is_processed(&self) -> bool {
         self.wasprocessed
     }
+    fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
     fn predictions_json(&self) -> serde_json::Result<String> {
-        serde_json::to_string(&self.aioutput)
+        sidecar_json(self.provenance.as_ref(), None, &self.aioutput)
     }
 }
 
@@ -315,6 +509,9 @@ pub struct PredVideo {
     pub step: u32,
     pub frames: Vec<Option<AIOutputs>>,
     pub wasprocessed: bool,
+    /// Kept in the sidecar's envelope, not in `outputs`; filled on load.
+    #[serde(skip)]
+    pub provenance: Option<Provenance>,
 }
 
 impl PredVideo {
@@ -324,17 +521,13 @@ impl PredVideo {
     /// [`Self::hydrate`] so picking 100 videos doesn't pay a 100x
     /// ffmpeg-init cost upfront.
     pub fn new_simple(file_path: std::path::PathBuf) -> Self {
-        if let Ok(path) = sidecar_predictions_path(&file_path) {
-            if path.exists() {
-                if let Ok(file) = std::fs::File::open(&path) {
-                    if let Ok(mut cached) = serde_json::from_reader::<_, PredVideo>(file) {
-                        // Trust the caller-supplied path in case the video
-                        // moved since the predictions were saved.
-                        cached.file_path = file_path;
-                        return cached;
-                    }
-                }
-            }
+        if let Some(sidecar) = load_sidecar::<PredVideo>(&file_path) {
+            let mut cached = sidecar.outputs;
+            // Trust the caller-supplied path in case the video
+            // moved since the predictions were saved.
+            cached.file_path = file_path;
+            cached.provenance = sidecar.provenance;
+            return cached;
         }
         Self {
             file_path,
@@ -345,6 +538,7 @@ impl PredVideo {
             step: 1,
             frames: Vec::new(),
             wasprocessed: false,
+            provenance: None,
         }
     }
 
@@ -373,6 +567,7 @@ impl PredVideo {
             *slot = None;
         }
         self.wasprocessed = false;
+        self.provenance = None;
     }
 
     pub fn set_step(&mut self, step: u32) {
@@ -439,10 +634,13 @@ impl Pred for PredVideo {
     fn is_processed(&self) -> bool {
         self.wasprocessed
     }
+    fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
     fn predictions_json(&self) -> serde_json::Result<String> {
         // Video round-trips the whole struct (frames-as-array + probe metadata),
         // not just `aioutput` — that's the difference vs. PredImg / PredAudio.
-        serde_json::to_string(self)
+        sidecar_json(self.provenance.as_ref(), None, self)
     }
 }
 
@@ -451,6 +649,9 @@ pub struct XYXYc {
     pub xyxy: XYXY,
     pub label: String,
     pub extra_cls: Option<Vec<Prob>>,
+    /// Set by an embedding stage of a [`Pipeline`](super::pipeline::Pipeline).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Embedding>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -461,7 +662,17 @@ pub struct XYc {
 
 impl XYXYc {
     pub fn new(xyxy: XYXY, label: String) -> Self {
-        XYXYc {xyxy, label, extra_cls: None,}
+        XYXYc {xyxy, label, extra_cls: None, embedding: None,}
+    }
+
+    /// `(class_id, label, prob)` for what the box holds: the crop
+    /// classifier's top label when it ran, since the detector only says
+    /// "animal", else the detector's own.
+    pub fn species(&self) -> (u32, &str, f32) {
+        match self.extra_cls.as_ref().and_then(|p| p.top()) {
+            Some(top) => (top.class_id, top.label.as_str(), top.prob),
+            None => (self.xyxy.class_id, self.label.as_str(), self.xyxy.prob),
+        }
     }
 }
 
@@ -506,9 +717,9 @@ impl AIOutputs {
         }
     }
 
+    /// Reads an image/audio sidecar, enveloped or legacy bare.
     pub fn from_file(input_path: impl AsRef<std::path::Path>) -> std::io::Result<AIOutputs> {
-        let deserialized: AIOutputs = serde_json::from_reader(std::fs::File::open(input_path)?)?;
-        Ok(deserialized)
+        Ok(read_sidecar(input_path)?.outputs)
     }
 
     /// `(class_id, label, prob)` for the single best prediction in this output.
@@ -545,6 +756,22 @@ impl AIOutputs {
             AIOutputs::Embed(_) => None,
         }
     }
+
+    /// [`Self::dominant_prob`], but boxes and segments are named by
+    /// [`XYXYc::species`].
+    pub fn dominant_species(&self) -> Option<(u32, &str, f32)> {
+        match self {
+            AIOutputs::ObjectDetection(bboxes) => best_species(bboxes.iter()),
+            AIOutputs::Segmentation(segs) => best_species(segs.iter().map(|s| &s.bbox)),
+            _ => self.dominant_prob(),
+        }
+    }
+}
+
+fn best_species<'a>(boxes: impl Iterator<Item = &'a XYXYc>) -> Option<(u32, &'a str, f32)> {
+    boxes
+        .map(XYXYc::species)
+        .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal))
 }
 
 impl Embedding {
@@ -571,11 +798,27 @@ impl Embedding {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub confidence_threshold: f32,
     pub nms_threshold: f32,
     pub geo_fence: String,
+    /// Run detectors tile by tile instead of on the downsized image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tiling: Option<TileConfig>,
+    /// Test-time augmentation: also run flipped and rescaled copies of each
+    /// image and fuse the results. Slower, better recall.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tta: Option<TtaConfig>,
+    /// Thresholds for single classes, by label, instead of `confidence_threshold`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub class_thresholds: BTreeMap<String, f32>,
+    /// Only these labels are kept, when not empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include_classes: Vec<String>,
+    /// Labels that are always dropped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_classes: Vec<String>,
 }
 
 impl Default for ModelConfig {
@@ -584,15 +827,161 @@ impl Default for ModelConfig {
             confidence_threshold: 0.25,
             nms_threshold: 0.4,
             geo_fence: "".to_owned(),
+            tiling: None,
+            tta: None,
+            class_thresholds: BTreeMap::new(),
+            include_classes: Vec::new(),
+            exclude_classes: Vec::new(),
+        }
+    }
+}
+
+impl ModelConfig {
+    /// The threshold `label` has to reach.
+    pub fn threshold(&self, label: &str) -> f32 {
+        self.class_thresholds.get(label).copied().unwrap_or(self.confidence_threshold)
+    }
+
+    /// The lowest threshold of any class: models drop candidates below it
+    /// before [`Self::filter_output`] applies each class's own.
+    pub fn min_threshold(&self) -> f32 {
+        self.class_thresholds.values().copied().fold(self.confidence_threshold, f32::min)
+    }
+
+    /// Whether the include and exclude lists let `label` through.
+    pub fn allows(&self, label: &str) -> bool {
+        (self.include_classes.is_empty() || self.include_classes.iter().any(|c| c == label))
+            && !self.exclude_classes.iter().any(|c| c == label)
+    }
+
+    pub fn accepts(&self, label: &str, prob: f32) -> bool {
+        self.allows(label) && prob >= self.threshold(label)
+    }
+
+    /// Drops predictions of excluded classes and those under their class's
+    /// threshold. Audio windows always carry the top class of the window,
+    /// so they are only cut by a threshold set for that class.
+    pub fn filter_output(&self, output: &mut AIOutputs) {
+        match output {
+            AIOutputs::ObjectDetection(dets) => dets.retain(|det| self.accepts(&det.label, det.xyxy.prob)),
+            AIOutputs::Segmentation(segs) => segs.retain(|seg| self.accepts(&seg.bbox.label, seg.bbox.xyxy.prob)),
+            AIOutputs::PointDetection(points) => points.retain(|point| self.accepts(&point.label, point.xy.prob)),
+            AIOutputs::Classification(probs) => probs.retain(|p| self.accepts(&p.label, p.prob)),
+            AIOutputs::AudioClassification(windows) => windows.retain(|w| {
+                let label = &w.prediction.label;
+                self.allows(label) && self.class_thresholds.get(label).is_none_or(|&t| w.prediction.prob >= t)
+            }),
+            AIOutputs::Embed(_) => {}
+        }
+    }
+}
+
+/// Sliced (SAHI-style) inference for images much larger than the model
+/// input: overlapping tiles are run separately and their detections merged,
+/// boxes with NMS and points by distance.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TileConfig {
+    /// Tile width and height, in image pixels.
+    pub size: u32,
+    /// Fraction of a tile shared with its neighbour, in `[0, 1)`.
+    pub overlap: f32,
+    /// Also run the whole downsized image, for animals larger than a tile.
+    pub full_image: bool,
+    /// Points of one class closer than this, in pixels, are one animal.
+    pub point_distance: f32,
+}
+
+impl Default for TileConfig {
+    fn default() -> Self {
+        Self {
+            size: 640,
+            overlap: 0.2,
+            full_image: true,
+            point_distance: 8.0,
         }
     }
 }
 
+/// Which augmented copies of an image [`ModelConfig::tta`] runs, besides
+/// the original.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TtaConfig {
+    /// Mirror left to right.
+    pub flip: bool,
+    /// Zoom factors: above 1 crops the centre, below 1 pads the borders.
+    /// `1.0` is the original framing.
+    pub scales: Vec<f32>,
+}
+
+impl Default for TtaConfig {
+    fn default() -> Self {
+        Self {
+            flip: true,
+            scales: vec![0.83, 1.0, 1.2],
+        }
+    }
+}
+
+/// Several `.bq` models run on the same input as one: classifications are
+/// combined with `fusion`, boxes with weighted box fusion, and audio
+/// predictions averaged over `window`-second windows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnsembleConfig {
+    /// Names in `models/`, or paths to `.bq` files.
+    pub models: Vec<String>,
+    pub fusion: Fusion,
+    /// Length of the audio windows, in seconds.
+    pub window: f32,
+}
+
+impl Default for EnsembleConfig {
+    fn default() -> Self {
+        Self {
+            models: Vec::new(),
+            fusion: Fusion::Mean,
+            window: 3.0,
+        }
+    }
+}
+
+/// How an ensemble combines its members' class probabilities.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fusion {
+    /// Average per class; a class a member doesn't report counts as 0.
+    #[default]
+    Mean,
+    /// Highest probability any member gives a class.
+    Max,
+    /// Each member votes for its top class; the score is the share of votes.
+    Vote,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AvailableModel {
     pub name: String,
     pub description: String,
     pub download_link: String,
+    /// Hex SHA-256 of the `.bq`, checked after downloading.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// The model's `model_version`, for indexes without hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// Where an installed model came from, saved next to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModelOrigin {
+    /// The index it was pulled from, or `bundle <file>` it was installed from.
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
 }
 
 /// A flat `[N]` embedding vector, L2-normalised.
@@ -0,0 +1,172 @@
+use super::abstractions::*;
+use super::audio::AudioData;
+use super::bq::{process_audio, process_imgbuf, process_imgbufs, run_provenance};
+use super::formats::partition_media;
+use super::metadata::ImageMetadata;
+use super::models::MAX_BATCH;
+use super::pipeline::Pipeline;
+use super::video_file::VideofileProcessor;
+use anyhow::{anyhow, Context, Result};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Media files found under a batch root, split by modality and sorted so
+/// repeated runs visit them in the same order.
+#[derive(Default)]
+pub struct MediaFiles {
+    pub images: Vec<PathBuf>,
+    pub audios: Vec<PathBuf>,
+    pub videos: Vec<PathBuf>,
+}
+
+impl MediaFiles {
+    pub fn len(&self) -> usize {
+        self.images.len() + self.audios.len() + self.videos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Collects media under `root`. A single file is accepted as-is; a directory
+/// is listed (and walked when `recursive` is set).
+pub fn collect_media(root: impl AsRef<Path>, recursive: bool) -> Result<MediaFiles> {
+    let root = root.as_ref();
+    let mut paths = Vec::new();
+    if root.is_file() {
+        paths.push(root.to_path_buf());
+    } else {
+        walk_dir(root, recursive, &mut paths)
+            .with_context(|| format!("Failed to read directory: {}", root.display()))?;
+    }
+    paths.sort();
+
+    let (images, audios, videos) = partition_media(paths);
+    Ok(MediaFiles { images, audios, videos })
+}
+
+fn walk_dir(dir: &Path, recursive: bool, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive {
+                walk_dir(&path, recursive, out)?;
+            }
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the loaded model(s) on one image. Same path as the GUI: the primary
+/// model plus the optional classification model on every crop.
+pub fn process_image_file(file_path: &Path) -> Result<PredImg> {
+    let img = image::open(file_path)
+        .with_context(|| format!("Failed to open image: {}", file_path.display()))?
+        .into_rgb8();
+    let aioutput = process_imgbuf(&img)?;
+    Ok(finished_img(file_path, aioutput))
+}
+
+/// [`process_image_file`] over many files: decoded in parallel, then run
+/// through the model in batches. One result per path, in order; a file that
+/// fails to decode doesn't stop the others.
+pub fn process_image_files(file_paths: &[PathBuf]) -> Vec<Result<PredImg>> {
+    let pipeline = match Pipeline::current().context("no model loaded") {
+        Ok(pipeline) => pipeline,
+        Err(e) => return file_paths.iter().map(|_| Err(anyhow!("{}", e))).collect(),
+    };
+    file_paths
+        .iter()
+        .zip(run_image_files(&pipeline, file_paths))
+        .map(|(path, aioutput)| Ok(finished_img(path, aioutput?)))
+        .collect()
+}
+
+/// Decodes `file_paths` in parallel and runs `pipeline` on them as one batch.
+pub fn run_image_files(pipeline: &Pipeline, file_paths: &[PathBuf]) -> Vec<Result<AIOutputs>> {
+    let decoded: Vec<Result<ImageBuffer<Rgb<u8>, Vec<u8>>>> = file_paths
+        .par_iter()
+        .map(|path| {
+            Ok(image::open(path)
+                .with_context(|| format!("Failed to open image: {}", path.display()))?
+                .into_rgb8())
+        })
+        .collect();
+    let mut imgs = Vec::new();
+    let decoded: Vec<Result<()>> = decoded.into_iter().map(|img| img.map(|img| imgs.push(img))).collect();
+    let mut outputs = match pipeline.run_images(&imgs) {
+        Ok(outputs) => outputs.into_iter(),
+        Err(e) => return file_paths.iter().map(|_| Err(anyhow!("{}", e))).collect(),
+    };
+    decoded
+        .into_iter()
+        .map(|img| {
+            img?;
+            outputs.next().context("model returned fewer outputs than images")
+        })
+        .collect()
+}
+
+fn finished_img(file_path: &Path, aioutput: AIOutputs) -> PredImg {
+    PredImg {
+        file_path: file_path.to_path_buf(),
+        aioutput: Some(aioutput),
+        wasprocessed: true,
+        provenance: run_provenance(),
+        metadata: ImageMetadata::read(file_path),
+    }
+}
+
+pub fn process_audio_file(file_path: &Path) -> Result<PredAudio> {
+    let audio = AudioData::from_file(file_path)
+        .map_err(|e| anyhow!("Failed to decode audio {}: {}", file_path.display(), e))?
+        .to_mono();
+    let aioutput = process_audio(&audio)?;
+    Ok(PredAudio {
+        file_path: file_path.to_path_buf(),
+        aioutput: Some(aioutput),
+        wasprocessed: true,
+        provenance: run_provenance(),
+    })
+}
+
+/// Analyses every `step`-th frame, like the GUI's video mode, and returns the
+/// filled-in `PredVideo`.
+pub fn process_video_file(file_path: &Path, step: u32) -> Result<PredVideo> {
+    let path_str = file_path
+        .to_str()
+        .ok_or_else(|| anyhow!("Non-UTF-8 path: {}", file_path.display()))?;
+    // `probe` surfaces open/decode errors as a Result; `new` would panic.
+    let probe = VideofileProcessor::probe(path_str)
+        .map_err(|e| anyhow!("Failed to open video {}: {}", file_path.display(), e))?;
+
+    let mut pred = PredVideo {
+        file_path: file_path.to_path_buf(),
+        width: probe.width,
+        height: probe.height,
+        fps: probe.fps,
+        n_frames: probe.n_frames,
+        step: step.max(1),
+        frames: vec![None; probe.n_frames as usize],
+        wasprocessed: false,
+        provenance: run_provenance(),
+    };
+
+    let step = pred.step as u64;
+    let mut frames = VideofileProcessor::new(path_str).filter(|(frame_idx, _)| frame_idx % step == 0);
+    loop {
+        let (idxs, imgs): (Vec<u64>, Vec<_>) = frames.by_ref().take(MAX_BATCH).unzip();
+        if imgs.is_empty() {
+            break;
+        }
+        for (frame_idx, aioutput) in idxs.into_iter().zip(process_imgbufs(&imgs)?) {
+            pred.record(frame_idx, aioutput);
+        }
+    }
+    pred.wasprocessed = true;
+    Ok(pred)
+}
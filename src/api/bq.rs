@@ -1,22 +1,30 @@
 use super::abstractions::*;
 use super::audio::*;
+use super::bqfile::{self, BqFile, Signer};
+use super::download;
 use super::models::{AIInput, Model, Task};
+use super::pipeline::Pipeline;
 use super::processing::post::PostProcessing;
-use super::processing::pre::slice_image;
+use super::registry::{ModelHandle, ModelRegistry};
 use anyhow::{bail, ensure, Context, Result};
+use ed25519_dalek::SigningKey;
 use image::{ImageBuffer, Rgb};
-use ort::session::builder::GraphOptimizationLevel;
+use memmap2::Mmap;
+use ort::session::builder::{GraphOptimizationLevel, SessionBuilder};
 #[cfg(feature = "cuda")]
 use ort::ep::CUDA;
 #[cfg(feature = "webgpu")]
 use ort::ep::WebGPU;
 use ort::session::Session;
+use ort::value::{Outlet, ValueType};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
-use std::sync::{LazyLock, RwLock};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 
 pub(crate) fn ort_err<E: std::fmt::Display>(e: E) -> anyhow::Error {
     anyhow::anyhow!("{e}")
@@ -24,68 +32,65 @@ pub(crate) fn ort_err<E: std::fmt::Display>(e: E) -> anyhow::Error {
 
 pub struct BQModel;
 
+/// The two models the GUI, TUI and CLI work with: a primary model and an
+/// optional crop classifier. Each is just a fixed name in [`ModelRegistry`].
 pub enum GlobalBQ {
     First,
     Second,
 }
 
 impl GlobalBQ {
-    fn get_lock(&self) -> &'static RwLock<Option<Model>> {
+    /// Registry name the slot's model is loaded under. Model names come
+    /// from file names, which can't hold a `/`, so these never clash with
+    /// a model loaded by name.
+    pub const fn key(&self) -> &'static str {
         match self {
-            GlobalBQ::First => &CURRENT_AI,
-            GlobalBQ::Second => &CURRENT_AI2,
+            GlobalBQ::First => "global/first",
+            GlobalBQ::Second => "global/second",
         }
     }
 
+    pub fn handle(&self) -> Option<ModelHandle> {
+        ModelRegistry::get(self.key())
+    }
+
     pub fn set_model(
         &self,
         value: impl AsRef<Path>,
         ep: Ep,
         config: Option<ModelConfig>,
     ) -> Result<()> {
-        let config = config.unwrap_or_default();
-
-        let (model_metadata, data) = BQModel::import_data(value)?;
-        
-        let session = BQModel::session_from_memory(&data, ep)?;
-        let aimodel: Model = Model::new(
-            model_metadata,
-            session,
-            config,
-        )?;
-        *self.get_lock().write().unwrap() = Some(aimodel);
-        Ok(())
+        ModelRegistry::load(self.key(), value, ep, config).map(|_| ())
     }
 
     pub fn update_config(&self, new_config: ModelConfig) {
-        if let Some(ref mut model) = self.get_lock().write().unwrap().as_mut() {
-            *model.config_mut() = new_config;
+        if let Some(model) = self.handle() {
+            model.update_config(new_config);
         }
     }
 
     pub fn clear(&self) {
-        let mut guard = self.get_lock().write().unwrap();
-        *guard = None;
+        ModelRegistry::unload(self.key());
+    }
+
+    /// Identity of the loaded model and its current config.
+    pub fn stamp(&self) -> Option<ModelStamp> {
+        Some(self.handle()?.stamp())
+    }
+
+    /// Sidecar provenance for an output of this slot's model, made now.
+    pub fn provenance(&self) -> Option<Provenance> {
+        Some(self.handle()?.provenance())
     }
 
     pub fn run(&self, input: &AIInput) -> Result<AIOutputs> {
-        let guard = self.get_lock().read().ok().context("model lock poisoned")?;
-        let model = guard.as_ref().context("no model loaded")?;
-        Ok(model.run(input))
+        self.handle().context("no model loaded")?.run(input)
     }
 }
 
 fn parse_bq_header(content: &[u8], file_stem: &str) -> Result<(AIMetadata, usize)> {
-    ensure!(content.len() >= 7, "File too short to be a valid .bq file");
-    ensure!(&content[..7] == b"BQMODEL", "Invalid file format: missing BQMODEL magic string");
-    ensure!(content[7] == 1, "Unsupported .bq version: {}", content[7]);
-    ensure!(content.len() >= 12, "File too short: missing JSON length");
-
-    let json_length = u32::from_le_bytes(content[8..12].try_into().context("Failed to read JSON length")?) as usize;
-    let json_end = 12 + json_length;
-    ensure!(content.len() >= json_end, "File truncated: JSON section extends beyond file end");
-
-    let json_str = String::from_utf8(content[12..json_end].to_vec())
+    let (json, json_end) = bqfile::json_section(content)?;
+    let json_str = String::from_utf8(json.to_vec())
         .context("Failed to parse JSON content in .bq file")?;
     let ai_model: AIMetadataRaw = serde_json::from_str(&json_str)
         .context("Failed to deserialize JSON into AI metadata")?;
@@ -93,8 +98,44 @@ fn parse_bq_header(content: &[u8], file_stem: &str) -> Result<(AIMetadata, usize
     Ok((ai_model, json_end))
 }
 
+/// A `.bq` file mapped into memory, so its sections can be checked and
+/// handed to onnxruntime without reading the file into a buffer first.
+pub struct BqPayload {
+    map: Mmap,
+}
+
+impl BqPayload {
+    pub fn open(file_path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self { map: map_file(file_path)? })
+    }
+
+    pub fn file(&self) -> Result<BqFile<'_>> {
+        BqFile::parse(&self.map)
+    }
+
+    /// Hex SHA-256 the model is known by in [`ModelStamp`]s. When the JSON
+    /// holds the sections' hashes, which [`BQModel::import_data`] checks,
+    /// hashing the JSON covers the whole model without reading the weights
+    /// again; older files are hashed whole.
+    pub fn identity(&self, info: &ModelInfo) -> Result<String> {
+        if info.onnx_sha256.is_some() {
+            Ok(bqfile::sha256_hex(self.file()?.json))
+        } else {
+            Ok(bqfile::sha256_hex(&self.map))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
 impl BQModel {
-    pub fn session_from_memory(model_data: &[u8], ep: Ep) -> Result<Session> {
+    fn session_builder(ep: Ep) -> Result<SessionBuilder> {
         let mut builder = Session::builder().map_err(ort_err)?;
         builder = builder.with_optimization_level(GraphOptimizationLevel::Level3).map_err(ort_err)?;
         match ep {
@@ -104,36 +145,64 @@ impl BQModel {
             Ep::WebGPU => builder = builder.with_execution_providers([WebGPU::default().build().error_on_failure()]).map_err(ort_err)?,
             _ => {}
         }
-        Ok(builder.commit_from_memory(model_data)?)
+        Ok(builder)
+    }
+
+    pub fn session_from_memory(model_data: &[u8], ep: Ep) -> Result<Session> {
+        Ok(Self::session_builder(ep)?.commit_from_memory(model_data)?)
     }
 
-    pub fn import_data(file_path: impl AsRef<Path>) -> Result<(AIMetadata, Vec<u8>)> {
-        let content = fs::read(&file_path)
-            .with_context(|| format!("Failed to read .bq file: {}", file_path.as_ref().display()))?;
+    /// A session for the model in `payload`, its external data included.
+    pub fn session_from_payload(payload: &BqPayload, ep: Ep) -> Result<Session> {
+        let file = payload.file()?;
+        let mut builder = Self::session_builder(ep)?;
+        for (name, data) in &file.external {
+            // onnxruntime may point into these buffers for the session's whole
+            // life, so it gets its own copy rather than the mapping.
+            builder = builder
+                .with_external_initializer_file_in_memory(*name, Cow::Owned(data.to_vec()))
+                .map_err(ort_err)?;
+        }
+        Ok(builder.commit_from_memory(file.onnx)?)
+    }
+
+    /// The metadata and mapped contents of the `.bq` at `file_path`, after
+    /// checking them against the file's hashes and signature.
+    pub fn import_data(file_path: impl AsRef<Path>) -> Result<(AIMetadata, BqPayload)> {
+        let payload = BqPayload::open(&file_path)?;
         let name = file_path
             .as_ref()
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("unknown");
-        let (ai_model, json_end) = parse_bq_header(&content, name)?;
+        let (ai_model, _) = parse_bq_header(&payload.map, name)?;
 
-        let onnx_length_start = json_end;
-        ensure!(
-            content.len() >= onnx_length_start + 4,
-            "File truncated: missing ONNX length"
-        );
-        let onnx_length = u32::from_le_bytes(
-            content[onnx_length_start..onnx_length_start + 4].try_into()?,
-        ) as usize;
-        let onnx_start = onnx_length_start + 4;
-        let onnx_end = onnx_start + onnx_length;
-        ensure!(
-            content.len() >= onnx_end,
-            "File truncated: ONNX section extends beyond file end"
-        );
-        let onnx_data = content[onnx_start..onnx_end].to_vec();
+        let file = payload.file()?;
+        file.verify(&ai_model.info)
+            .with_context(|| format!("Failed to verify {}", file_path.as_ref().display()))?;
+        check_signer(&file, file_path.as_ref())?;
 
-        Ok((ai_model, onnx_data))
+        Ok((ai_model, payload))
+    }
+
+    /// The keys in [`TRUSTED_KEYS_PATH`]; `None` when there is no such file.
+    pub fn trusted_keys() -> Result<Option<Vec<[u8; 32]>>> {
+        match fs::read_to_string(TRUSTED_KEYS_PATH) {
+            Ok(text) => bqfile::parse_trusted_keys(&text)
+                .map(Some)
+                .with_context(|| format!("Failed to read {}", TRUSTED_KEYS_PATH)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", TRUSTED_KEYS_PATH)),
+        }
+    }
+
+    /// Who signed the `.bq` at `file_path`, against [`Self::trusted_keys`].
+    /// Only the signature is checked, not the sections' hashes.
+    pub fn signer(file_path: impl AsRef<Path>) -> Result<Signer> {
+        let payload = BqPayload::open(&file_path)?;
+        let file = payload.file()?;
+        file.verify_signature()?;
+        Ok(file.signer(&Self::trusted_keys()?.unwrap_or_default()))
     }
 
     pub fn from_file_to_metadata(file_path: impl AsRef<Path>) -> Result<AIMetadata> {
@@ -150,22 +219,9 @@ impl BQModel {
     }
 
     pub fn from_file_to_jsonbuf(file_path: impl AsRef<Path>) -> Result<Vec<u8>> {
-        let path = file_path.as_ref();
-
-        let mut file = File::open(path)
-            .with_context(|| format!("Failed to open .bq file: {}", path.display()))?;
-
-        let mut header = [0u8; 12];
-        file.read_exact(&mut header)
-            .with_context(|| format!("Failed to read .bq header: {}", path.display()))?;
-
-        let json_length = u32::from_le_bytes(header[8..12].try_into().context("Failed to read JSON length")?) as usize;
-        let mut buf = vec![0u8; 12 + json_length];
-        buf[..12].copy_from_slice(&header);
-        file.read_exact(&mut buf[12..])
-            .with_context(|| format!("Failed to read JSON section from .bq file: {}", path.display()))?;
-
-        return Ok(buf)
+        let payload = BqPayload::open(file_path)?;
+        let (_, json_end) = bqfile::json_section(&payload.map)?;
+        Ok(payload.map[..json_end].to_vec())
     }
 
     pub fn get_list() -> Vec<AIMetadata> {
@@ -175,63 +231,403 @@ impl BQModel {
     pub fn from_file_print_shape(model_path: impl AsRef<Path>) -> Result<()> {
         let path = model_path.as_ref();
 
-        let model_data = match path.extension().and_then(|e| e.to_str()) {
-            Some("onnx") => fs::read(path)?,
+        let session = match path.extension().and_then(|e| e.to_str()) {
+            Some("onnx") => Session::builder().map_err(ort_err)?.commit_from_file(path).map_err(ort_err)?,
             Some("bq") => {
-                let (_metadata, data) = BQModel::import_data(path)?;
-                data
+                let (_metadata, payload) = BQModel::import_data(path)?;
+                BQModel::session_from_payload(&payload, Ep::Cpu)?
             }
             Some(ext) => bail!("Unsupported extension: .{}", ext),
             None => bail!("No file extension found"),
         };
 
-        let mut builder = Session::builder().map_err(ort_err)?;
-        let session = builder.commit_from_memory(&model_data).map_err(ort_err)?;
-
         println!("Inputs:\n{:?}", session.inputs());
         println!("Outputs:\n{:?}", session.outputs());
 
         Ok(())
     }
 
-    pub fn create_bq_file(name: String) -> Result<()> {
+    /// Packs `name.json` and `name.onnx` into a version 2 `name.bq`, signed
+    /// with the hex key in `sign_key` when given. The ONNX model's external
+    /// data files go in as well: those in `data`, and `name.onnx.data`
+    /// when it exists.
+    pub fn create_bq_file(name: String, data: &[PathBuf], sign_key: Option<&Path>) -> Result<()> {
         let json_path = format!("{}.json", name);
         let onnx_path = format!("{}.onnx", name);
         let output_path = format!("{}.bq", name);
 
         let json_content = fs::read(&json_path).with_context(|| format!("Failed to open {}", json_path))?;
-        let _ai: AIMetadataRaw = serde_json::from_slice(&json_content)
+        let ai: AIMetadataRaw = serde_json::from_slice(&json_content)
             .with_context(|| format!("Failed to deserialize {} into required AI metadata", json_path))?;
-        let onnx_content = fs::read(&onnx_path).with_context(|| format!("Failed to open {}", onnx_path))?;
+        let mut metadata: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&json_content)
+            .with_context(|| format!("{} must be a JSON object", json_path))?;
+        let onnx_content = map_file(&onnx_path)?;
 
         if !matches!(onnx_content.get(0..2), Some(&[0x08, ir_ver]) if ir_ver > 0) {
             bail!("File {} does not appear to be a valid ONNX model", onnx_path);
         }
 
-        let mut output = Vec::new();
-        output.extend_from_slice(b"BQMODEL");
-        output.push(1);
-
-        let json_length = json_content.len() as u32;
-        output.extend_from_slice(&json_length.to_le_bytes());
-        output.extend_from_slice(&json_content);
+        let mut data_paths = data.to_vec();
+        let default_data = PathBuf::from(format!("{}.data", onnx_path));
+        if default_data.exists() && !data_paths.contains(&default_data) {
+            data_paths.push(default_data);
+        }
+        let mut external = Vec::with_capacity(data_paths.len());
+        for path in &data_paths {
+            let file_name = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .with_context(|| format!("Invalid data file name: {}", path.display()))?;
+            external.push((file_name, map_file(path)?));
+        }
 
-        let onnx_length = onnx_content.len() as u32;
-        output.extend_from_slice(&onnx_length.to_le_bytes());
-        output.extend_from_slice(&onnx_content);
+        if ai.info.input.is_none() {
+            // From the file, so onnxruntime finds the external data next to it.
+            let session = Session::builder().map_err(ort_err)?.commit_from_file(&onnx_path).map_err(ort_err)?;
+            if let Some(input) = session.inputs().first() {
+                let shape = match input.dtype() {
+                    ValueType::Tensor { shape, .. } => shape.iter().copied().collect(),
+                    _ => Vec::new(),
+                };
+                let layout = match shape.as_slice() {
+                    [_, 1 | 3, _, _] => Some("NCHW".to_owned()),
+                    [_, _, _, 1 | 3] => Some("NHWC".to_owned()),
+                    _ => None,
+                };
+                let spec = InputSpec { name: Some(input.name().to_owned()), shape, layout };
+                metadata.insert("input".into(), serde_json::to_value(spec)?);
+            }
+        }
 
-        fs::write(&output_path, output)
+        let key = read_signing_key(sign_key)?;
+        let external: Vec<(&str, &[u8])> = external.iter().map(|(name, map)| (*name, &map[..])).collect();
+        let file = File::create(&output_path)
             .with_context(|| format!("Failed to create output file: {}", output_path))?;
+        let mut out = BufWriter::new(file);
+        bqfile::write(&mut out, &mut metadata, &onnx_content, &external, key.as_ref())
+            .with_context(|| format!("Failed to write {}", output_path))?;
+        out.flush().with_context(|| format!("Failed to write {}", output_path))?;
 
         println!("New model: {}", output_path);
+        for (name, _) in &external {
+            println!("External data: {}", name);
+        }
+        if let Some(key) = key {
+            println!("Signed by: {}", bqfile::public_key_hex(&key));
+        }
+        Ok(())
+    }
+
+    /// Writes a new signing key to `name.key` and its public half to
+    /// `name.pub`. Existing keys are never overwritten.
+    pub fn create_signing_key(name: &str) -> Result<()> {
+        let key_path = format!("{}.key", name);
+        let pub_path = format!("{}.pub", name);
+        ensure!(!Path::new(&key_path).exists(), "{} already exists", key_path);
+
+        let key = bqfile::generate_key()?;
+        fs::write(&key_path, bqfile::key_to_hex(&key))
+            .with_context(|| format!("Failed to write {}", key_path))?;
+        fs::write(&pub_path, bqfile::public_key_hex(&key))
+            .with_context(|| format!("Failed to write {}", pub_path))?;
+
+        println!("Signing key: {} (keep it private)", key_path);
+        println!("Public key: {}", bqfile::public_key_hex(&key));
+        println!("Models it signs load without a warning once the public key is in {}; with that file, unsigned models no longer load", TRUSTED_KEYS_PATH);
         Ok(())
     }
 
+    /// Problems that would keep the model at `file_path` from loading or
+    /// from labelling right; empty when there are none. Errors only when the
+    /// file itself is unreadable, corrupted or wrongly signed.
+    pub fn validate(file_path: impl AsRef<Path>) -> Result<Vec<String>> {
+        let (metadata, payload) = Self::import_data(&file_path)?;
+        let mut problems = Vec::new();
+
+        if !Model::supports(&metadata.architecture) {
+            let expected: Vec<&str> = Model::architectures().collect();
+            problems.push(format!("architecture {:?} is not supported; expected one of {}", metadata.architecture, expected.join(", ")));
+        }
+        if metadata.modality == Modality::Audio && metadata.audio_config.is_none() {
+            problems.push("audio models need an audio_config".to_owned());
+        }
+        if metadata.info.model_version.as_deref().is_some_and(|v| !bqfile::is_semver(v)) {
+            problems.push(format!("model_version {:?} is not a semantic version", metadata.info.model_version.as_deref().unwrap_or_default()));
+        }
+
+        match Self::session_from_payload(&payload, Ep::Cpu) {
+            Ok(session) => problems.extend(class_dim_problem(&metadata, &tensor_shapes(session.outputs()))),
+            Err(e) => problems.push(format!("the ONNX graph does not load: {}", e)),
+        }
+        Ok(problems)
+    }
+
+    /// Writes the JSON, ONNX and external data of the `.bq` at `file_path`
+    /// back out as `name.json`, `name.onnx` and the data files' own names,
+    /// ready for `bq new`. They go to `out_dir`, or next to the `.bq`.
+    /// Existing files are never overwritten.
+    pub fn extract(file_path: impl AsRef<Path>, out_dir: Option<&Path>) -> Result<Vec<PathBuf>> {
+        let path = file_path.as_ref();
+        let (_metadata, payload) = Self::import_data(path)?;
+        let file = payload.file()?;
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown");
+        let dir = match out_dir {
+            Some(dir) => dir.to_path_buf(),
+            None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+
+        let mut outputs = vec![
+            (dir.join(format!("{}.json", stem)), file.json),
+            (dir.join(format!("{}.onnx", stem)), file.onnx),
+        ];
+        for (name, data) in &file.external {
+            ensure!(
+                Path::new(name).file_name().and_then(|n| n.to_str()) == Some(*name),
+                "Refusing to extract external data outside {}: {:?}",
+                dir.display(),
+                name
+            );
+            outputs.push((dir.join(name), *data));
+        }
+        for (out, _) in &outputs {
+            ensure!(!out.exists(), "{} already exists", out.display());
+        }
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        for (out, data) in &outputs {
+            fs::write(out, data).with_context(|| format!("Failed to write {}", out.display()))?;
+        }
+        Ok(outputs.into_iter().map(|(out, _)| out).collect())
+    }
+
+    /// Sets `fields` (`field=value`, see [`bqfile::parse_field`]) in the
+    /// metadata of the `.bq` at `file_path`, rewriting it in place. The ONNX
+    /// is copied over untouched; `sign_key` re-signs the result.
+    pub fn set_metadata(file_path: impl AsRef<Path>, fields: &[String], sign_key: Option<&Path>) -> Result<()> {
+        let path = file_path.as_ref();
+        let fields = fields
+            .iter()
+            .map(|field| bqfile::parse_field(field))
+            .collect::<Result<serde_json::Map<_, _>>>()?;
+        let key = read_signing_key(sign_key)?;
+
+        let tmp = path.with_extension("bq.tmp");
+        let written = write_metadata(path, &tmp, fields, key.as_ref());
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        written?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+
+        println!("Updated: {}", path.display());
+        if let Some(key) = key {
+            println!("Signed by: {}", bqfile::public_key_hex(&key));
+        }
+        Ok(())
+    }
+
+    /// What changed from the model at `old` to the one at `new`: metadata,
+    /// classes and tensor shapes, one line per change.
+    pub fn diff(old: impl AsRef<Path>, new: impl AsRef<Path>) -> Result<Vec<String>> {
+        let (a, a_payload) = Self::import_data(&old)?;
+        let (b, b_payload) = Self::import_data(&new)?;
+        let mut changes = Vec::new();
+
+        let fields = [
+            ("task", a.task.name().to_owned(), b.task.name().to_owned()),
+            ("architecture", a.architecture.clone(), b.architecture.clone()),
+            ("modality", format!("{:?}", a.modality), format!("{:?}", b.modality)),
+            ("post_processing", format!("{:?}", a.post_processing), format!("{:?}", b.post_processing)),
+            ("model_version", a.info.model_version.clone().unwrap_or_default(), b.info.model_version.clone().unwrap_or_default()),
+            ("license", a.info.license.clone().unwrap_or_default(), b.info.license.clone().unwrap_or_default()),
+        ];
+        for (field, before, after) in fields {
+            if before != after {
+                changes.push(format!("{}: {:?} → {:?}", field, before, after));
+            }
+        }
+
+        let added: Vec<&str> = b.classes.iter().filter(|c| !a.classes.contains(c)).map(String::as_str).collect();
+        let removed: Vec<&str> = a.classes.iter().filter(|c| !b.classes.contains(c)).map(String::as_str).collect();
+        if !added.is_empty() {
+            changes.push(format!("classes added ({}): {}", added.len(), added.join(", ")));
+        }
+        if !removed.is_empty() {
+            changes.push(format!("classes removed ({}): {}", removed.len(), removed.join(", ")));
+        }
+        if added.is_empty() && removed.is_empty() && a.classes != b.classes {
+            changes.push("classes reordered, so class ids differ".to_owned());
+        }
+
+        let a_session = Self::session_from_payload(&a_payload, Ep::Cpu)?;
+        let b_session = Self::session_from_payload(&b_payload, Ep::Cpu)?;
+        for (kind, before, after) in [
+            ("input", a_session.inputs(), b_session.inputs()),
+            ("output", a_session.outputs(), b_session.outputs()),
+        ] {
+            let (before, after) = (tensor_shapes(before), tensor_shapes(after));
+            for (name, shape) in &before {
+                match after.iter().find(|(other, _)| other == name) {
+                    None => changes.push(format!("{} {} removed", kind, name)),
+                    Some((_, new_shape)) if new_shape != shape => {
+                        changes.push(format!("{} {}: {:?} → {:?}", kind, name, shape, new_shape))
+                    }
+                    Some(_) => {}
+                }
+            }
+            for (name, shape) in &after {
+                if !before.iter().any(|(other, _)| other == name) {
+                    changes.push(format!("{} {} added: {:?}", kind, name, shape));
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Where the settings saved for the model at `file_path` live:
+    /// `models/speciesnet.bq` → `models/speciesnet.config.json`.
+    pub fn config_path(file_path: impl AsRef<Path>) -> PathBuf {
+        file_path.as_ref().with_extension("config.json")
+    }
+
+    /// The settings saved for the model at `file_path`, if any.
+    pub fn load_config(file_path: impl AsRef<Path>) -> Option<ModelConfig> {
+        let text = fs::read_to_string(Self::config_path(file_path)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Saves `config` next to the model so later sessions start from it.
+    pub fn save_config(file_path: impl AsRef<Path>, config: &ModelConfig) -> Result<()> {
+        let path = Self::config_path(file_path);
+        fs::write(&path, serde_json::to_string_pretty(config)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Where the [`ModelOrigin`] of the model at `file_path` is kept:
+    /// `models/speciesnet.bq` → `models/speciesnet.origin.json`.
+    pub fn origin_path(file_path: impl AsRef<Path>) -> PathBuf {
+        file_path.as_ref().with_extension("origin.json")
+    }
+
+    /// Where the model at `file_path` came from; `None` for models copied in by hand.
+    pub fn load_origin(file_path: impl AsRef<Path>) -> Option<ModelOrigin> {
+        let text = fs::read_to_string(Self::origin_path(file_path)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub fn save_origin(file_path: impl AsRef<Path>, origin: &ModelOrigin) -> Result<()> {
+        let path = Self::origin_path(file_path);
+        fs::write(&path, serde_json::to_string_pretty(origin)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Hex SHA-256 of a file, streamed so large models aren't read into memory.
+    pub fn file_sha256(file_path: impl AsRef<Path>) -> Result<String> {
+        let path = file_path.as_ref();
+        let mut file = File::open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)
+            .with_context(|| format!("Failed to hash {}", path.display()))?;
+        Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    /// The models in the configured index; see [`download::index_url`].
     pub async fn get_list_from_api() -> Result<Vec<AvailableModel>> {
-        let url = "https://boquila.org/api/models.json";
-        let listmodels: Vec<AvailableModel> = reqwest::get(url).await?.json().await?;
-        Ok(listmodels)
+        download::fetch_index(&download::index_url(None)).await
+    }
+}
+
+/// Keys of the signers whose models are trusted, one per line; see
+/// [`bqfile::parse_trusted_keys`].
+pub const TRUSTED_KEYS_PATH: &str = "models/trusted_keys";
+
+/// Refuses unsigned models and models signed by keys missing from
+/// [`TRUSTED_KEYS_PATH`] once that file exists. Until then they load, with a
+/// warning for unknown signers.
+fn check_signer(file: &BqFile, path: &Path) -> Result<()> {
+    let trusted = BQModel::trusted_keys()?;
+    let signer = file
+        .check_signer(trusted.as_deref())
+        .map_err(|e| anyhow::anyhow!("{} is {} (see {})", path.display(), e, TRUSTED_KEYS_PATH))?;
+    if let Signer::Unknown(sig) = signer {
+        eprintln!(
+            "⚠️ {} is signed by an unknown key {}. Anyone can sign a model; once you have checked the key, add {} to {}",
+            path.display(),
+            sig.fingerprint(),
+            sig.signer(),
+            TRUSTED_KEYS_PATH
+        );
     }
+    Ok(())
+}
+
+fn read_signing_key(path: Option<&Path>) -> Result<Option<SigningKey>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Some(bqfile::key_from_hex(&text)?))
+}
+
+fn write_metadata(path: &Path, tmp: &Path, fields: serde_json::Map<String, serde_json::Value>, key: Option<&SigningKey>) -> Result<()> {
+    let payload = BqPayload::open(path)?;
+    let file = File::create(tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+    let mut out = BufWriter::new(file);
+    bqfile::set_metadata(&payload.map, fields, &mut out, key)
+        .with_context(|| format!("Failed to rewrite {}", path.display()))?;
+    out.flush().with_context(|| format!("Failed to write {}", tmp.display()))?;
+    Ok(())
+}
+
+/// Name and shape of each tensor in `outlets`; `-1` marks dynamic dimensions.
+fn tensor_shapes(outlets: &[Outlet]) -> Vec<(String, Vec<i64>)> {
+    outlets
+        .iter()
+        .map(|outlet| {
+            let shape = match outlet.dtype() {
+                ValueType::Tensor { shape, .. } => shape.iter().copied().collect(),
+                _ => Vec::new(),
+            };
+            (outlet.name().to_owned(), shape)
+        })
+        .collect()
+}
+
+/// Whether some output has a dimension that fits the model's class list.
+/// Yolo rows hold 4 box values (5 for YOLOv5) before the class scores and,
+/// for segmentation, the mask coefficients after them. YOLOv10/26 output
+/// class ids and embedding models vectors, so there is nothing to check.
+fn class_dim_problem(metadata: &AIMetadata, outputs: &[(String, Vec<i64>)]) -> Option<String> {
+    let arch = metadata.architecture.to_lowercase();
+    if metadata.task == Task::Embed || matches!(arch.as_str(), "yolov10" | "yolov26") {
+        return None;
+    }
+    let n = metadata.classes.len() as i64;
+    let expected = match (arch.as_str(), metadata.task) {
+        ("yolo", Task::Segment) => {
+            let masks = outputs.get(1).and_then(|(_, shape)| shape.get(1)).copied().unwrap_or(32);
+            vec![n + 4 + masks]
+        }
+        ("yolo", _) => vec![n + 4, n + 5],
+        _ => vec![n],
+    };
+    // The batch dimension doesn't count; dynamic ones could be anything.
+    let fits = outputs
+        .iter()
+        .any(|(_, shape)| shape.iter().skip(1).any(|&d| d < 0 || expected.contains(&d)));
+    (!fits).then(|| {
+        let shapes: Vec<String> = outputs.iter().map(|(name, shape)| format!("{} {:?}", name, shape)).collect();
+        let expected: Vec<String> = expected.iter().map(i64::to_string).collect();
+        format!("{} classes, but no output has a dimension of {}: {}", n, expected.join(" or "), shapes.join(", "))
+    })
+}
+
+fn map_file(path: impl AsRef<Path>) -> Result<Mmap> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    // SAFETY: the mapping is read-only. Like any reader, we don't guard
+    // against the file being truncated underneath us.
+    unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map {}", path.display()))
 }
 
 fn analyze_folder(folder_path: &str) -> Result<Vec<AIMetadata>> {
@@ -259,53 +655,66 @@ fn analyze_folder(folder_path: &str) -> Result<Vec<AIMetadata>> {
     Ok(ai_models)
 }
 
-pub static CURRENT_AI: RwLock<Option<Model>> = RwLock::new(None);
-pub static CURRENT_AI2: RwLock<Option<Model>> = RwLock::new(None);
+/// Where `bundle install` puts newer geofence data than the app was built with.
+pub const GEOFENCE_PATH: &str = "models/geofence.json";
+
+/// The geofence data at [`GEOFENCE_PATH`] when there is any, else the copy
+/// built into the app.
 pub static GEOFENCE_DATA: LazyLock<HashMap<String, Vec<String>>> = LazyLock::new(|| {
-    serde_json::from_slice(include_bytes!("../../assets/geofence.json"))
-        .expect("parse embedded geofence data")
+    fs::read(GEOFENCE_PATH)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_else(|| {
+            serde_json::from_slice(include_bytes!("../../assets/geofence.json"))
+                .expect("parse embedded geofence data")
+        })
 });
 
+/// Provenance for outputs of [`process_imgbuf`] / [`process_audio`]: the
+/// active pipeline, or the primary model plus the crop classifier when it
+/// takes part (image models).
+pub fn run_provenance() -> Option<Provenance> {
+    if let Some(pipeline) = Pipeline::active() {
+        return Some(pipeline.provenance());
+    }
+    Some(run_provenance_with(&GlobalBQ::First.handle()?, GlobalBQ::Second.handle().as_ref()))
+}
+
+/// [`run_provenance`] for an explicit model and crop classifier.
+pub fn run_provenance_with(model: &ModelHandle, cls: Option<&ModelHandle>) -> Provenance {
+    match model.metadata().modality {
+        Modality::Image => Pipeline::from_models(model.clone(), cls.cloned()).provenance(),
+        Modality::Audio => model.provenance(),
+    }
+}
+
+/// Runs the active pipeline on `img`, else the `GlobalBQ` pair.
 #[inline(always)]
 pub fn process_imgbuf(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Result<AIOutputs> {
-    let mut outputs = GlobalBQ::First.run(&AIInput::Image(img))?;
-    process_with_ai2(&mut outputs, img);
-    Ok(outputs)
+    Pipeline::current().context("no model loaded")?.run(img)
+}
+
+/// [`process_imgbuf`] over many images, batching the first stage.
+pub fn process_imgbufs(imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Result<Vec<AIOutputs>> {
+    Pipeline::current().context("no model loaded")?.run_images(imgs)
 }
 
 #[inline(always)]
 pub fn process_audio(audio: &AudioData) -> Result<AIOutputs> {
-    GlobalBQ::First.run(&AIInput::Audio(audio))
+    process_audio_with(Pipeline::current().context("no model loaded")?.model(), audio)
 }
 
-fn process_with_ai2(outputs: &mut AIOutputs, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Option<()> {
-    let ai2 = CURRENT_AI2.read().ok()?;
-    let ai2_ref = ai2.as_ref()?;
-
-    match outputs {
-        AIOutputs::ObjectDetection(detections) => {
-            for xyxyc in detections.iter_mut() {
-                let sliced_img = slice_image(img, &xyxyc.xyxy);
-                let cls_output = ai2_ref.run(&AIInput::Image(&sliced_img));
-                if let AIOutputs::Classification(probs) = cls_output {
-                    xyxyc.extra_cls = Some(probs);
-                }
-            }
-        }
-        AIOutputs::Segmentation(segmentations) => {
-            for segc in segmentations {
-                let xyxyc = &mut segc.bbox;
-                let sliced_img = slice_image(img, &xyxyc.xyxy);
-                let cls_output = ai2_ref.run(&AIInput::Image(&sliced_img));
-                if let AIOutputs::Classification(probs) = cls_output {
-                    xyxyc.extra_cls = Some(probs);
-                }
-            }
-        }
-        _ => {}
-    }
+/// Runs `model` on `img`, then `cls` on every detected crop.
+pub fn process_imgbuf_with(
+    model: &ModelHandle,
+    cls: Option<&ModelHandle>,
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+) -> Result<AIOutputs> {
+    Pipeline::from_models(model.clone(), cls.cloned()).run(img)
+}
 
-    Some(())
+pub fn process_audio_with(model: &ModelHandle, audio: &AudioData) -> Result<AIOutputs> {
+    model.run(&AIInput::Audio(audio))
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
@@ -356,6 +765,20 @@ impl Ep {
     pub const fn is_local(&self) -> bool {
         !matches!(self, Ep::BoquilaHubRemote)
     }
+
+    /// Parses a local execution provider from a CLI-style name ("cpu", "gpu",
+    /// "cuda", "webgpu"). "gpu" resolves to [`Ep::gpu`].
+    pub fn from_name(s: &str) -> Option<Ep> {
+        match s.to_lowercase().as_str() {
+            "cpu" => Some(Ep::Cpu),
+            "gpu" => Some(Ep::gpu()),
+            #[cfg(feature = "cuda")]
+            "cuda" => Some(Ep::Cuda),
+            #[cfg(feature = "webgpu")]
+            "webgpu" => Some(Ep::WebGPU),
+            _ => None,
+        }
+    }
 }
 
 impl AsRef<str> for Ep {
@@ -390,6 +813,7 @@ impl AIMetadataRaw {
             name: name.to_owned(),
             modality,
             audio_config: self.audio_config,
+            info: self.info,
         }
     }
 }
@@ -403,6 +827,7 @@ pub struct AIMetadata {
     pub name: String,
     pub modality: Modality,
     pub audio_config: Option<AudioConfig>,
+    pub info: ModelInfo,
 }
 
 impl AIMetadata {
@@ -0,0 +1,398 @@
+use super::abstractions::{AIMetadataRaw, ModelInfo};
+use anyhow::{bail, ensure, Context, Result};
+use ed25519_dalek::{Signature, Signer as _, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+
+pub const MAGIC: &[u8; 7] = b"BQMODEL";
+/// The original layout: `u32` lengths and one ONNX section. Version 2 files
+/// that fit in it still use it, so old readers load them.
+pub const LAYOUT_V1: u8 = 1;
+/// `u64` lengths and named sections, for payloads past 4 GB and ONNX
+/// models with external data files.
+pub const LAYOUT_V2: u8 = 2;
+/// The `bq_version` this build writes into the metadata JSON.
+pub const BQ_VERSION: u32 = 2;
+pub const SIGNATURE_MAGIC: &[u8; 5] = b"BQSIG";
+const SIGNATURE_LEN: usize = SIGNATURE_MAGIC.len() + 32 + 64;
+/// Name of the ONNX graph's section in layout 2; the others are its external
+/// data files, named as the graph refers to them.
+pub const GRAPH_SECTION: &str = "model.onnx";
+
+/// A `.bq` file split into its sections. Layout 1:
+///
+/// ```text
+/// "BQMODEL" · 1u8 · json_len: u32 LE · JSON · onnx_len: u32 LE · ONNX · [signature]
+/// ```
+///
+/// Layout 2:
+///
+/// ```text
+/// "BQMODEL" · 2u8 · json_len: u64 LE · JSON
+///     · { name_len: u16 LE · name · data_len: u64 LE · data }… · [signature]
+/// ```
+///
+/// where the first section is the graph, [`GRAPH_SECTION`]. Version 2
+/// metadata puts `bq_version`, the section hashes and the other
+/// [`ModelInfo`] fields in the JSON, and may append a signature: `"BQSIG"`,
+/// the 32-byte ed25519 public key and the 64-byte signature of the JSON
+/// section, which covers the rest through the hashes.
+pub struct BqFile<'a> {
+    pub layout: u8,
+    pub json: &'a [u8],
+    pub onnx: &'a [u8],
+    /// External data files, by the name the graph refers to them by.
+    pub external: Vec<(&'a str, &'a [u8])>,
+    pub signature: Option<BqSignature>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BqSignature {
+    pub public_key: [u8; 32],
+    pub signature: [u8; 64],
+}
+
+impl BqSignature {
+    /// Hex of the signer's public key, the form keys are shared in.
+    pub fn signer(&self) -> String {
+        hex(&self.public_key)
+    }
+
+    /// Short form of the signer's key for display: the first 8 bytes of its
+    /// SHA-256, as `3f2a:91bc:04de:7711`.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key)
+    }
+}
+
+/// Who signed a `.bq` file, checked against a list of trusted keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signer {
+    Unsigned,
+    Trusted(BqSignature),
+    /// A valid signature, but by a key nobody vouched for: anyone can
+    /// re-sign a modified model with a fresh key.
+    Unknown(BqSignature),
+}
+
+/// The JSON section and the offset it ends at. Only the header and the JSON
+/// need to be present, so this works on the head of a file.
+pub fn json_section(content: &[u8]) -> Result<(&[u8], usize)> {
+    ensure!(content.len() >= 8, "File too short to be a valid .bq file");
+    ensure!(&content[..7] == MAGIC, "Invalid file format: missing BQMODEL magic string");
+    let (json_length, json_start) = match content[7] {
+        LAYOUT_V1 => {
+            ensure!(content.len() >= 12, "File too short: missing JSON length");
+            (u32::from_le_bytes(content[8..12].try_into().context("Failed to read JSON length")?) as u64, 12)
+        }
+        LAYOUT_V2 => {
+            ensure!(content.len() >= 16, "File too short: missing JSON length");
+            (u64::from_le_bytes(content[8..16].try_into().context("Failed to read JSON length")?), 16)
+        }
+        version => bail!("Unsupported .bq version: {}", version),
+    };
+    let json_end = section_end(content, json_start, json_length).context("File truncated: JSON section extends beyond file end")?;
+    Ok((&content[json_start..json_end], json_end))
+}
+
+fn section_end(content: &[u8], start: usize, length: u64) -> Option<usize> {
+    let end = start.checked_add(usize::try_from(length).ok()?)?;
+    (end <= content.len()).then_some(end)
+}
+
+impl<'a> BqFile<'a> {
+    pub fn parse(content: &'a [u8]) -> Result<Self> {
+        let (json, json_end) = json_section(content)?;
+        let layout = content[7];
+        let mut at = json_end;
+        let mut sections = Vec::new();
+        if layout == LAYOUT_V1 {
+            ensure!(content.len() >= at + 4, "File truncated: missing ONNX length");
+            let onnx_length = u32::from_le_bytes(content[at..at + 4].try_into()?) as u64;
+            let end = section_end(content, at + 4, onnx_length).context("File truncated: ONNX section extends beyond file end")?;
+            sections.push((GRAPH_SECTION, &content[at + 4..end]));
+            at = end;
+        } else {
+            while at < content.len() && !is_signature(&content[at..]) {
+                ensure!(content.len() >= at + 2, "File truncated: missing section name length");
+                let name_length = u16::from_le_bytes(content[at..at + 2].try_into()?) as u64;
+                let name_end = section_end(content, at + 2, name_length).context("File truncated: section name extends beyond file end")?;
+                let name = std::str::from_utf8(&content[at + 2..name_end]).context("Section name is not UTF-8")?;
+                ensure!(content.len() >= name_end + 8, "File truncated: missing length of section {}", name);
+                let length = u64::from_le_bytes(content[name_end..name_end + 8].try_into()?);
+                let end = section_end(content, name_end + 8, length)
+                    .with_context(|| format!("File truncated: section {} extends beyond file end", name))?;
+                sections.push((name, &content[name_end + 8..end]));
+                at = end;
+            }
+        }
+
+        // Sectioned files end at a signature or at the end of the file.
+        // Version 1 readers never looked past the ONNX section, so other
+        // bytes there are left alone.
+        let trailer = &content[at..];
+        let signature = if is_signature(trailer) {
+            let keys = &trailer[SIGNATURE_MAGIC.len()..];
+            Some(BqSignature {
+                public_key: keys[..32].try_into()?,
+                signature: keys[32..].try_into()?,
+            })
+        } else {
+            None
+        };
+
+        let mut sections = sections.into_iter();
+        let onnx = match sections.next() {
+            Some((GRAPH_SECTION, onnx)) => onnx,
+            _ => bail!("The first section must be the ONNX graph, {}", GRAPH_SECTION),
+        };
+        Ok(Self {
+            layout,
+            json,
+            onnx,
+            external: sections.collect(),
+            signature,
+        })
+    }
+
+    /// Checks the sections against the hashes in `info` and the signature,
+    /// when the file has them. Version 1 files have neither and always pass.
+    pub fn verify(&self, info: &ModelInfo) -> Result<()> {
+        if let Some(expected) = &info.onnx_sha256 {
+            check_sha256(GRAPH_SECTION, self.onnx, expected)?;
+        }
+        for (name, data) in &self.external {
+            match info.data_sha256.get(*name) {
+                Some(expected) => check_sha256(name, data, expected)?,
+                None if info.onnx_sha256.is_some() => bail!("No checksum for external data {}", name),
+                None => {}
+            }
+        }
+        if self.signature.is_some() {
+            ensure!(info.onnx_sha256.is_some(), "Signed .bq file has no onnx_sha256 to sign");
+        }
+        self.verify_signature()
+    }
+
+    /// Checks the signature over the JSON section, when there is one, but
+    /// not the sections against their hashes: cheap enough to list many models.
+    pub fn verify_signature(&self) -> Result<()> {
+        if let Some(sig) = &self.signature {
+            let key = VerifyingKey::from_bytes(&sig.public_key).context("Invalid signer public key")?;
+            key.verify(self.json, &Signature::from_bytes(&sig.signature))
+                .map_err(|_| anyhow::anyhow!("Invalid signature from {}", sig.signer()))?;
+        }
+        Ok(())
+    }
+
+    /// The file's signer, trusted when its key is in `trusted`. This only
+    /// looks at the key; [`Self::verify`] checks the signature itself.
+    pub fn signer(&self, trusted: &[[u8; 32]]) -> Signer {
+        match self.signature {
+            None => Signer::Unsigned,
+            Some(sig) if trusted.contains(&sig.public_key) => Signer::Trusted(sig),
+            Some(sig) => Signer::Unknown(sig),
+        }
+    }
+
+    /// [`Self::signer`], refused unless trusted when there is a trust list.
+    /// `trusted` is `None` without one: then every file passes, since
+    /// stripping a signature would otherwise be a way past the list.
+    pub fn check_signer(&self, trusted: Option<&[[u8; 32]]>) -> Result<Signer> {
+        let signer = self.signer(trusted.unwrap_or_default());
+        if trusted.is_some() {
+            match signer {
+                Signer::Trusted(_) => {}
+                Signer::Unsigned => bail!("unsigned, and only models signed by a trusted key load"),
+                Signer::Unknown(sig) => bail!(
+                    "signed by {}, which is not trusted; add its key {} to trust it",
+                    sig.fingerprint(),
+                    sig.signer()
+                ),
+            }
+        }
+        Ok(signer)
+    }
+}
+
+/// Reads a trusted-keys file: one hex public key per line, as `bq keygen`
+/// writes them to `name.pub`, optionally followed by a note on who it
+/// belongs to. Blank lines and lines starting with `#` are skipped.
+pub fn parse_trusted_keys(text: &str) -> Result<Vec<[u8; 32]>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let key = line.split_whitespace().next().unwrap_or_default();
+            unhex(key)
+                .and_then(|bytes| bytes.try_into().ok())
+                .with_context(|| format!("Line {}: {:?} is not a 32-byte hex public key", i + 1, key))
+        })
+        .collect()
+}
+
+/// See [`BqSignature::fingerprint`].
+pub fn fingerprint(public_key: &[u8; 32]) -> String {
+    let digest = Sha256::digest(public_key);
+    digest[..8].chunks(2).map(hex).collect::<Vec<_>>().join(":")
+}
+
+fn is_signature(trailer: &[u8]) -> bool {
+    trailer.len() == SIGNATURE_LEN && trailer.starts_with(SIGNATURE_MAGIC)
+}
+
+fn check_sha256(name: &str, data: &[u8], expected: &str) -> Result<()> {
+    let actual = sha256_hex(data);
+    ensure!(
+        actual.eq_ignore_ascii_case(expected),
+        "Checksum mismatch in {}: expected {}, got {}; the file is corrupted or incomplete",
+        name,
+        expected,
+        actual
+    );
+    Ok(())
+}
+
+/// Writes a version 2 `.bq` file to `out`. `metadata` is the model's JSON
+/// object; `bq_version` and the section hashes are set on it before it is
+/// written, and the file is signed with `key` when given. Layout 1 is used
+/// when everything fits in it, layout 2 otherwise.
+pub fn write(
+    out: &mut impl Write,
+    metadata: &mut serde_json::Map<String, serde_json::Value>,
+    onnx: &[u8],
+    external: &[(&str, &[u8])],
+    key: Option<&SigningKey>,
+) -> Result<()> {
+    if let Some(version) = metadata.get("model_version").and_then(|v| v.as_str()) {
+        ensure!(is_semver(version), "model_version {:?} is not a semantic version (e.g. 1.2.0)", version);
+    }
+    for (name, _) in external {
+        ensure!(*name != GRAPH_SECTION && u16::try_from(name.len()).is_ok(), "Invalid external data name {:?}", name);
+    }
+    metadata.insert("bq_version".into(), BQ_VERSION.into());
+    metadata.insert("onnx_sha256".into(), sha256_hex(onnx).into());
+    if external.is_empty() {
+        metadata.remove("data_sha256");
+    } else {
+        let hashes: serde_json::Map<_, _> = external
+            .iter()
+            .map(|(name, data)| (name.to_string(), sha256_hex(data).into()))
+            .collect();
+        metadata.insert("data_sha256".into(), hashes.into());
+    }
+    let json = serde_json::to_vec_pretty(metadata)?;
+
+    let fits_v1 = external.is_empty() && u32::try_from(json.len()).is_ok() && u32::try_from(onnx.len()).is_ok();
+    out.write_all(MAGIC)?;
+    if fits_v1 {
+        out.write_all(&[LAYOUT_V1])?;
+        out.write_all(&(json.len() as u32).to_le_bytes())?;
+        out.write_all(&json)?;
+        out.write_all(&(onnx.len() as u32).to_le_bytes())?;
+        out.write_all(onnx)?;
+    } else {
+        out.write_all(&[LAYOUT_V2])?;
+        out.write_all(&(json.len() as u64).to_le_bytes())?;
+        out.write_all(&json)?;
+        for (name, data) in std::iter::once(&(GRAPH_SECTION, onnx)).chain(external) {
+            out.write_all(&(name.len() as u16).to_le_bytes())?;
+            out.write_all(name.as_bytes())?;
+            out.write_all(&(data.len() as u64).to_le_bytes())?;
+            out.write_all(data)?;
+        }
+    }
+    if let Some(key) = key {
+        out.write_all(SIGNATURE_MAGIC)?;
+        out.write_all(key.verifying_key().as_bytes())?;
+        out.write_all(&key.sign(&json).to_bytes())?;
+    }
+    Ok(())
+}
+
+/// Rewrites the `.bq` in `content` to `out` with `fields` set in its
+/// metadata; a `null` value removes the field. The sections are copied as
+/// they are, after checking them against their hashes. The old signature no
+/// longer holds and is dropped, unless `key` signs the new file.
+pub fn set_metadata(
+    content: &[u8],
+    fields: serde_json::Map<String, serde_json::Value>,
+    out: &mut impl Write,
+    key: Option<&SigningKey>,
+) -> Result<()> {
+    let file = BqFile::parse(content)?;
+    let info = serde_json::from_slice::<AIMetadataRaw>(file.json)
+        .context("Failed to deserialize JSON into AI metadata")?
+        .info;
+    file.verify(&info)?;
+
+    let mut metadata: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(file.json)?;
+    for (field, value) in fields {
+        if value.is_null() {
+            metadata.remove(&field);
+        } else {
+            metadata.insert(field, value);
+        }
+    }
+    serde_json::from_value::<AIMetadataRaw>(metadata.clone().into())
+        .context("The edited metadata is no longer valid")?;
+    write(out, &mut metadata, file.onnx, &file.external, key)
+}
+
+/// Parses a `field=value` argument. The value is read as JSON when it is
+/// valid JSON (`classes=["deer","fox"]`, `license=null`) and as text
+/// otherwise (`license=CC-BY-4.0`).
+pub fn parse_field(arg: &str) -> Result<(String, serde_json::Value)> {
+    let (field, value) = arg.split_once('=').with_context(|| format!("Expected field=value, got {:?}", arg))?;
+    ensure!(!field.is_empty(), "Missing field name in {:?}", arg);
+    let value = serde_json::from_str(value).unwrap_or_else(|_| value.into());
+    Ok((field.to_owned(), value))
+}
+
+/// `MAJOR.MINOR.PATCH`, optionally followed by `-pre` and/or `+build`.
+pub fn is_semver(version: &str) -> bool {
+    let core = version.split(['-', '+']).next().unwrap_or("");
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() == 3 && parts.iter().all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// A fresh signing key from the OS random source.
+pub fn generate_key() -> Result<SigningKey> {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).map_err(|e| anyhow::anyhow!("Failed to gather randomness: {}", e))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// Reads a signing key written by [`key_to_hex`].
+pub fn key_from_hex(text: &str) -> Result<SigningKey> {
+    let bytes = unhex(text.trim()).context("Signing key is not valid hex")?;
+    let seed: [u8; 32] = bytes.as_slice().try_into().map_err(|_| anyhow::anyhow!("Signing key must be 32 bytes"))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub fn key_to_hex(key: &SigningKey) -> String {
+    hex(key.as_bytes())
+}
+
+/// Hex of the public half of `key`, as [`BqSignature::signer`] reports it.
+pub fn public_key_hex(key: &SigningKey) -> String {
+    hex(key.verifying_key().as_bytes())
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,155 @@
+use super::abstractions::{AvailableModel, ModelOrigin};
+use super::bq::BQModel;
+use super::download;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// The bundle's index, in the same format as the online one.
+pub const INDEX_NAME: &str = "models.json";
+pub const GEOFENCE_NAME: &str = "geofence.json";
+const MODELS_DIR: &str = "models";
+
+/// Packs the `.bq` files at `models` into a zip at `out` for machines
+/// without internet. `models.json` lists them: their entry from `index`
+/// when there is one, with hash, version and size taken from the file
+/// itself. `geofence` goes in as `geofence.json`. Unzipped, the archive is
+/// also a mirror `pull --registry` can read.
+pub fn create(out: &Path, models: &[PathBuf], index: &[AvailableModel], geofence: &[u8]) -> Result<Vec<AvailableModel>> {
+    let file = File::create(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    // Models don't compress; storing keeps packing and installing fast.
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    let mut entries = Vec::with_capacity(models.len());
+    for path in models {
+        let file_name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .with_context(|| format!("Invalid model file name: {}", path.display()))?;
+        let stem = file_name.strip_suffix(".bq").unwrap_or(file_name);
+        let metadata = BQModel::from_file_to_metadata(path)?;
+        let mut entry = index
+            .iter()
+            .find(|m| download::file_name(m) == Some(file_name) || m.name == stem)
+            .cloned()
+            .unwrap_or_else(|| AvailableModel {
+                name: stem.to_owned(),
+                description: String::new(),
+                download_link: String::new(),
+                sha256: None,
+                version: None,
+                size: None,
+            });
+        entry.download_link = format!("{}/{}", MODELS_DIR, file_name);
+        entry.sha256 = Some(BQModel::file_sha256(path)?);
+        entry.version = metadata.info.model_version.or(entry.version);
+        entry.size = Some(fs::metadata(path)?.len());
+
+        zip.start_file(entry.download_link.as_str(), options)?;
+        let mut source = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        io::copy(&mut source, &mut zip).with_context(|| format!("Failed to pack {}", path.display()))?;
+        entries.push(entry);
+    }
+
+    zip.start_file(INDEX_NAME, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&entries)?)?;
+    zip.start_file(GEOFENCE_NAME, options)?;
+    zip.write_all(geofence)?;
+    zip.finish()?.flush()?;
+    Ok(entries)
+}
+
+/// What [`install`] did with one model of a bundle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Installed {
+    New,
+    Updated,
+    /// The same file was already there.
+    Unchanged,
+}
+
+/// Installs the bundle at `archive` into `models_dir`. Each model is checked
+/// against its hash before it replaces a copy already there, and remembers
+/// the bundle as its origin. The geofence data goes to `geofence.json`,
+/// once it parses; a bundle with empty or malformed data is refused before
+/// anything is installed.
+pub fn install(archive: &Path, models_dir: &Path) -> Result<Vec<(AvailableModel, Installed)>> {
+    let file = File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let mut zip = ZipArchive::new(file).with_context(|| format!("{} is not a model bundle", archive.display()))?;
+    let entries: Vec<AvailableModel> = {
+        let index = zip.by_name(INDEX_NAME).context("The bundle has no models.json")?;
+        serde_json::from_reader(index).context("Failed to parse the bundle's models.json")?
+    };
+    let geofence = match zip.by_name(GEOFENCE_NAME) {
+        Ok(data) => Some(read_geofence(data)?),
+        Err(_) => None,
+    };
+    let source = format!("bundle {}", archive.file_name().and_then(|s| s.to_str()).unwrap_or("unknown"));
+    fs::create_dir_all(models_dir).with_context(|| format!("Failed to create {}", models_dir.display()))?;
+
+    let mut installed = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let file_name = download::file_name(&entry).with_context(|| format!("Invalid link for {}", entry.name))?;
+        let dest = models_dir.join(file_name);
+        let outcome = if !dest.exists() {
+            Installed::New
+        } else if entry.sha256.is_some() && !download::is_outdated(&dest, &entry)? {
+            Installed::Unchanged
+        } else {
+            Installed::Updated
+        };
+
+        if outcome != Installed::Unchanged {
+            let part = download::part_path(&dest);
+            let mut packed = zip
+                .by_name(&entry.download_link)
+                .with_context(|| format!("The bundle is missing {}", entry.download_link))?;
+            let mut out = File::create(&part).with_context(|| format!("Failed to create {}", part.display()))?;
+            io::copy(&mut packed, &mut out).with_context(|| format!("Failed to unpack {}", entry.name))?;
+            drop(out);
+
+            let actual = BQModel::file_sha256(&part)?;
+            if entry.sha256.as_ref().is_some_and(|expected| !actual.eq_ignore_ascii_case(expected)) {
+                let _ = fs::remove_file(&part);
+                bail!("{} in the bundle is corrupted: checksum mismatch", entry.name);
+            }
+            fs::rename(&part, &dest).with_context(|| format!("Failed to move {} into place", dest.display()))?;
+        }
+        if outcome != Installed::Unchanged || BQModel::load_origin(&dest).is_none() {
+            let origin = ModelOrigin {
+                source: source.clone(),
+                sha256: entry.sha256.clone(),
+            };
+            BQModel::save_origin(&dest, &origin)?;
+        }
+        installed.push((entry, outcome));
+    }
+
+    if let Some(geofence) = geofence {
+        // Through a temporary file, so a failed write never leaves the app
+        // with half the data.
+        let dest = models_dir.join(GEOFENCE_NAME);
+        let part = download::part_path(&dest);
+        fs::write(&part, serde_json::to_vec(&geofence)?).with_context(|| format!("Failed to write {}", part.display()))?;
+        fs::rename(&part, &dest).with_context(|| format!("Failed to move {} into place", dest.display()))?;
+    }
+    Ok(installed)
+}
+
+/// The bundle's geofence data, refused unless it maps taxa to countries and
+/// lists at least one.
+fn read_geofence(data: impl io::Read) -> Result<HashMap<String, Vec<String>>> {
+    let geofence: HashMap<String, Vec<String>> =
+        serde_json::from_reader(data).context("Failed to parse the bundle's geofence.json")?;
+    if geofence.is_empty() {
+        bail!("The bundle's geofence.json is empty");
+    }
+    Ok(geofence)
+}
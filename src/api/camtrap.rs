@@ -0,0 +1,609 @@
+use super::abstractions::*;
+use super::deployment::DeploymentLocation;
+use super::events::{group_events, EventConfig};
+use super::formats::{has_ext, VIDEO_FORMATS};
+use super::metadata::{media_timestamp, ImageMetadata};
+use super::processing::post::{capitalize, SpeciesRecord};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Camtrap DP version the package and table schemas point at.
+const CAMTRAP_DP_BASE: &str = "https://raw.githubusercontent.com/tdwg/camtrap-dp/1.0";
+
+/// What a model label means for Camtrap DP's `observationType` and taxon
+/// columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Taxon {
+    pub observation_type: &'static str,
+    pub scientific_name: Option<String>,
+    pub taxon_rank: Option<&'static str>,
+    pub vernacular_name: Option<String>,
+}
+
+impl Taxon {
+    fn of_type(observation_type: &'static str) -> Self {
+        Self {
+            observation_type,
+            scientific_name: None,
+            taxon_rank: None,
+            vernacular_name: None,
+        }
+    }
+
+    fn animal(name: String, rank: Option<&'static str>, vernacular: Option<String>) -> Self {
+        Self {
+            observation_type: "animal",
+            scientific_name: Some(name),
+            taxon_rank: rank,
+            vernacular_name: vernacular,
+        }
+    }
+
+    /// Reads a model label: a raw `SpeciesRecord` line
+    /// (`uuid;class;order;family;genus;species;common_name`), a name produced
+    /// by the rollup ("Genus species (common)", "Genus sp.", "Family x"…), or
+    /// a plain class name, which is kept as-is without a rank.
+    pub fn from_label(label: &str) -> Self {
+        if let Ok(record) = SpeciesRecord::new(label) {
+            let common = record.common_name.as_deref().unwrap_or("").to_lowercase();
+            return match common.as_str() {
+                "blank" => Self::of_type("blank"),
+                "human" => Self::of_type("human"),
+                "vehicle" => Self::of_type("vehicle"),
+                "no cv result" => Self::of_type("unknown"),
+                _ => match record.scientific_name() {
+                    Some((name, rank)) => Self::animal(name, Some(rank), record.common_name.clone()),
+                    None => Self::of_type("animal"),
+                },
+            };
+        }
+
+        let (name, vernacular) = match label.trim().rsplit_once(" (") {
+            Some((name, rest)) if rest.ends_with(')') => {
+                (name.trim(), Some(rest.trim_end_matches(')').to_string()))
+            }
+            _ => (label.trim(), None),
+        };
+        match name.to_lowercase().as_str() {
+            "" => return Self::of_type("unknown"),
+            "blank" | "empty" => return Self::of_type("blank"),
+            "human" | "person" | "people" => return Self::of_type("human"),
+            "vehicle" => return Self::of_type("vehicle"),
+            "animal" => return Self::of_type("animal"),
+            _ => {}
+        }
+        for (prefix, rank) in [("Family ", "family"), ("Order ", "order"), ("Class ", "class")] {
+            if let Some(taxon) = name.strip_prefix(prefix) {
+                return Self::animal(capitalize(taxon), Some(rank), vernacular);
+            }
+        }
+        if let Some(genus) = name.strip_suffix(" sp.") {
+            return Self::animal(capitalize(genus), Some("genus"), vernacular);
+        }
+        let rank = vernacular.is_some().then_some("species");
+        Self::animal(name.to_string(), rank, vernacular)
+    }
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct DeploymentRow {
+    #[serde(rename = "deploymentID")]
+    deployment_id: String,
+    #[serde(rename = "locationID")]
+    location_id: Option<String>,
+    location_name: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    coordinate_uncertainty: Option<u32>,
+    deployment_start: String,
+    deployment_end: String,
+    setup_by: Option<String>,
+    #[serde(rename = "cameraID")]
+    camera_id: Option<String>,
+    camera_model: Option<String>,
+    camera_delay: Option<u32>,
+    camera_height: Option<f32>,
+    camera_depth: Option<f32>,
+    camera_tilt: Option<i32>,
+    camera_heading: Option<u32>,
+    detection_distance: Option<f32>,
+    timestamp_issues: Option<bool>,
+    bait_use: Option<bool>,
+    feature_type: Option<String>,
+    habitat: Option<String>,
+    deployment_groups: Option<String>,
+    deployment_tags: Option<String>,
+    deployment_comments: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct MediaRow {
+    #[serde(rename = "mediaID")]
+    media_id: String,
+    #[serde(rename = "deploymentID")]
+    deployment_id: String,
+    capture_method: Option<String>,
+    timestamp: String,
+    file_path: String,
+    file_public: bool,
+    file_name: Option<String>,
+    file_mediatype: String,
+    exif_data: Option<String>,
+    favorite: Option<bool>,
+    media_comments: Option<String>,
+}
+
+#[derive(Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ObservationRow {
+    #[serde(rename = "observationID")]
+    observation_id: String,
+    #[serde(rename = "deploymentID")]
+    deployment_id: String,
+    #[serde(rename = "mediaID")]
+    media_id: Option<String>,
+    #[serde(rename = "eventID")]
+    event_id: Option<String>,
+    event_start: String,
+    event_end: String,
+    observation_level: &'static str,
+    observation_type: &'static str,
+    camera_setup_type: Option<String>,
+    scientific_name: Option<String>,
+    count: Option<u32>,
+    life_stage: Option<String>,
+    sex: Option<String>,
+    behavior: Option<String>,
+    #[serde(rename = "individualID")]
+    individual_id: Option<String>,
+    individual_position_radius: Option<f32>,
+    individual_position_angle: Option<f32>,
+    individual_speed: Option<f32>,
+    bbox_x: Option<f32>,
+    bbox_y: Option<f32>,
+    bbox_width: Option<f32>,
+    bbox_height: Option<f32>,
+    classification_method: Option<&'static str>,
+    classified_by: Option<String>,
+    classification_timestamp: Option<String>,
+    classification_probability: Option<f32>,
+    observation_tags: Option<String>,
+    observation_comments: Option<String>,
+}
+
+/// One detection/classification reduced to what an observation row needs.
+struct Sighting {
+    label: String,
+    prob: f32,
+    count: u32,
+    bbox: Option<[f32; 4]>, // x, y, w, h normalised to 0..1
+}
+
+/// What a deployment row is built from: its time span plus the first camera
+/// and position any of its images reported.
+struct Deployment {
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    camera_id: Option<String>,
+    camera_model: Option<String>,
+    name: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+/// Accumulates the three tables while walking the media.
+#[derive(Default)]
+struct Package {
+    deployments: BTreeMap<String, Deployment>,
+    media: Vec<MediaRow>,
+    observations: Vec<ObservationRow>,
+    taxa: BTreeMap<String, Taxon>,
+}
+
+fn iso(ts: &DateTime<Local>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+fn capture_method(metadata: Option<&ImageMetadata>) -> Option<String> {
+    match metadata?.trigger.as_deref()? {
+        "motion" | "external" => Some("activityDetection".to_string()),
+        "timelapse" => Some("timeLapse".to_string()),
+        _ => None,
+    }
+}
+
+fn mediatype(path: &Path) -> String {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg".into(),
+        "png" => "image/png".into(),
+        "mov" => "video/quicktime".into(),
+        "avi" => "video/x-msvideo".into(),
+        "mkv" => "video/x-matroska".into(),
+        "mp4" => "video/mp4".into(),
+        ext if has_ext(ext, VIDEO_FORMATS) => format!("video/{}", ext),
+        ext => format!("image/{}", ext),
+    }
+}
+
+fn relative_path(path: &Path, root: Option<&Path>) -> String {
+    root.and_then(|r| path.strip_prefix(r).ok())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Deployment = folder the media sits in, relative to `root`.
+pub fn deployment_id(path: &Path, root: Option<&Path>) -> String {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let rel = relative_path(dir, root);
+    if rel.is_empty() {
+        root.or(Some(dir))
+            .and_then(|d| d.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "deployment".to_string())
+    } else {
+        rel
+    }
+}
+
+fn box_sighting(xyxyc: &XYXYc, dims: Option<(u32, u32)>) -> Sighting {
+    let b = &xyxyc.xyxy;
+    let (_, label, prob) = xyxyc.species();
+    let label = label.to_owned();
+    let bbox = dims.map(|(w, h)| {
+        let (w, h) = (w as f32, h as f32);
+        [
+            (b.x1 / w).clamp(0.0, 1.0),
+            (b.y1 / h).clamp(0.0, 1.0),
+            ((b.x2 - b.x1) / w).clamp(0.0, 1.0),
+            ((b.y2 - b.y1) / h).clamp(0.0, 1.0),
+        ]
+    });
+    Sighting { label, prob, count: 1, bbox }
+}
+
+fn sightings(output: &AIOutputs, dims: Option<(u32, u32)>) -> Vec<Sighting> {
+    match output {
+        AIOutputs::ObjectDetection(boxes) => boxes.iter().map(|b| box_sighting(b, dims)).collect(),
+        AIOutputs::Segmentation(segs) => segs.iter().map(|s| box_sighting(&s.bbox, dims)).collect(),
+        AIOutputs::PointDetection(points) => points
+            .iter()
+            .map(|p| Sighting {
+                label: p.label.clone(),
+                prob: p.xy.prob,
+                count: 1,
+                bbox: None,
+            })
+            .collect(),
+        AIOutputs::Classification(probs) => probs
+            .top()
+            .map(|p| Sighting {
+                label: p.label.clone(),
+                prob: p.prob,
+                count: 1,
+                bbox: None,
+            })
+            .into_iter()
+            .collect(),
+        AIOutputs::AudioClassification(_) | AIOutputs::Embed(_) => Vec::new(),
+    }
+}
+
+/// Per-label summary over a video's analysed frames: the most individuals
+/// seen in a single frame and the best confidence.
+fn video_sightings(video: &PredVideo) -> Vec<Sighting> {
+    let mut by_label: BTreeMap<String, (u32, f32)> = BTreeMap::new();
+    for output in video.frames.iter().flatten() {
+        let mut in_frame: BTreeMap<String, (u32, f32)> = BTreeMap::new();
+        for s in sightings(output, None) {
+            let entry = in_frame.entry(s.label).or_insert((0, 0.0));
+            entry.0 += s.count;
+            entry.1 = entry.1.max(s.prob);
+        }
+        for (label, (count, prob)) in in_frame {
+            let entry = by_label.entry(label).or_insert((0, 0.0));
+            entry.0 = entry.0.max(count);
+            entry.1 = entry.1.max(prob);
+        }
+    }
+    by_label
+        .into_iter()
+        .map(|(label, (count, prob))| Sighting { label, prob, count, bbox: None })
+        .collect()
+}
+
+impl Package {
+    /// Adds the media row and widens its deployment. A new deployment is
+    /// placed by the nearest `deployment.json`, else by the first GPS fix.
+    /// Returns the deployment and media ids for the observations.
+    fn add_media(
+        &mut self,
+        path: &Path,
+        root: Option<&Path>,
+        (start, end): (DateTime<Local>, DateTime<Local>),
+        metadata: Option<&ImageMetadata>,
+    ) -> (String, String) {
+        let deployment_id = deployment_id(path, root);
+        let deployment = self
+            .deployments
+            .entry(deployment_id.clone())
+            .or_insert_with(|| {
+                let dir = path.parent().unwrap_or(Path::new(""));
+                let location = DeploymentLocation::find(dir, root);
+                Deployment {
+                    start,
+                    end,
+                    camera_id: None,
+                    camera_model: None,
+                    name: location.as_ref().and_then(|l| l.name.clone()),
+                    latitude: location.as_ref().map(|l| l.latitude),
+                    longitude: location.as_ref().map(|l| l.longitude),
+                }
+            });
+        deployment.start = deployment.start.min(start);
+        deployment.end = deployment.end.max(end);
+        if let Some(m) = metadata {
+            deployment.camera_id = deployment.camera_id.take().or_else(|| m.serial_number.clone());
+            deployment.camera_model = deployment.camera_model.take().or_else(|| m.camera());
+            if deployment.latitude.is_none() && m.latitude.is_some() && m.longitude.is_some() {
+                (deployment.latitude, deployment.longitude) = (m.latitude, m.longitude);
+            }
+        }
+
+        let media_id = format!("m{:06}", self.media.len() + 1);
+        self.media.push(MediaRow {
+            media_id: media_id.clone(),
+            deployment_id: deployment_id.clone(),
+            capture_method: capture_method(metadata),
+            timestamp: iso(&start),
+            file_path: relative_path(path, root),
+            file_public: false,
+            file_name: path.file_name().map(|n| n.to_string_lossy().into_owned()),
+            file_mediatype: mediatype(path),
+            exif_data: metadata.and_then(|m| serde_json::to_string(m).ok()),
+            ..Default::default()
+        });
+        (deployment_id, media_id)
+    }
+
+    /// One observation per sighting on top of `base`, or a single blank one.
+    fn add_observations(&mut self, base: ObservationRow, sightings: Vec<Sighting>) {
+        if sightings.is_empty() {
+            self.push_observation(ObservationRow {
+                observation_type: "blank",
+                ..base
+            });
+            return;
+        }
+        for s in sightings {
+            let taxon = Taxon::from_label(&s.label);
+            let [bbox_x, bbox_y, bbox_width, bbox_height] = match s.bbox {
+                Some(b) => b.map(Some),
+                None => [None; 4],
+            };
+            if let Some(name) = &taxon.scientific_name {
+                self.taxa.entry(name.clone()).or_insert_with(|| taxon.clone());
+            }
+            self.push_observation(ObservationRow {
+                observation_type: taxon.observation_type,
+                scientific_name: taxon.scientific_name,
+                count: Some(s.count),
+                bbox_x,
+                bbox_y,
+                bbox_width,
+                bbox_height,
+                classification_probability: Some(s.prob),
+                ..base.clone()
+            });
+        }
+    }
+
+    fn push_observation(&mut self, mut row: ObservationRow) {
+        row.observation_id = format!("o{:06}", self.observations.len() + 1);
+        self.observations.push(row);
+    }
+}
+
+fn observation_base(
+    deployment_id: String,
+    (start, end): (DateTime<Local>, DateTime<Local>),
+    provenance: Option<&Provenance>,
+) -> ObservationRow {
+    ObservationRow {
+        deployment_id,
+        event_start: iso(&start),
+        event_end: iso(&end),
+        observation_level: "media",
+        classification_method: provenance.map(|_| "machine"),
+        classified_by: provenance.map(|p| p.stamp.model.clone()),
+        classification_timestamp: provenance.map(|p| p.created_at.clone()),
+        ..Default::default()
+    }
+}
+
+fn write_table<T: Serialize>(path: &Path, rows: &[T]) -> Result<()> {
+    let mut writer =
+        csv::Writer::from_path(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn resource(name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "path": format!("{}.csv", name),
+        "profile": "tabular-data-resource",
+        "format": "csv",
+        "mediatype": "text/csv",
+        "encoding": "utf-8",
+        "schema": format!("{}/{}-table-schema.json", CAMTRAP_DP_BASE, name),
+    })
+}
+
+/// Writes a Camtrap DP package (`deployments.csv`, `media.csv`,
+/// `observations.csv`, `datapackage.json`) into `dir`. Each folder of media
+/// is one deployment; unprocessed files are left out. Timestamps, camera and
+/// coordinates come from image EXIF where present.
+///
+/// With `events`, image observations are made per event (burst) instead of
+/// per image; videos always get media-level observations.
+pub fn write_camtrap_dp(
+    dir: impl AsRef<Path>,
+    imgs: &[PredImg],
+    videos: &[PredVideo],
+    root: Option<&Path>,
+    events: Option<&EventConfig>,
+) -> Result<()> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let mut package = Package::default();
+    let mut media_of_img: HashMap<usize, (String, String)> = HashMap::new();
+    for (i, img) in imgs.iter().enumerate().filter(|(_, p)| p.wasprocessed) {
+        let Some(ts) = media_timestamp(&img.file_path, img.metadata.as_ref()) else {
+            continue;
+        };
+        let (deployment_id, media_id) =
+            package.add_media(&img.file_path, root, (ts, ts), img.metadata.as_ref());
+        if events.is_some() {
+            media_of_img.insert(i, (deployment_id, media_id));
+            continue;
+        }
+        let dims = image::image_dimensions(&img.file_path).ok();
+        let found = img
+            .aioutput
+            .as_ref()
+            .map(|o| sightings(o, dims))
+            .unwrap_or_default();
+        let base = ObservationRow {
+            media_id: Some(media_id),
+            ..observation_base(deployment_id, (ts, ts), img.provenance.as_ref())
+        };
+        package.add_observations(base, found);
+    }
+    if let Some(config) = events {
+        for (n, event) in group_events(imgs, config.gap_s).iter().enumerate() {
+            // Unprocessed members have no media row; skip events made only of them.
+            let Some((deployment_id, _)) = event.members.iter().find_map(|i| media_of_img.get(i)) else {
+                continue;
+            };
+            let summary = event.summarize(imgs, config.label);
+            let provenance = event.members.iter().find_map(|&i| imgs[i].provenance.as_ref());
+            let base = ObservationRow {
+                event_id: Some(format!("e{:06}", n + 1)),
+                observation_level: "event",
+                ..observation_base(deployment_id.clone(), (event.start, event.end), provenance)
+            };
+            let found = summary
+                .label
+                .map(|label| Sighting {
+                    label,
+                    prob: summary.prob.unwrap_or_default(),
+                    count: summary.max_count.max(1),
+                    bbox: None,
+                })
+                .into_iter()
+                .collect();
+            package.add_observations(base, found);
+        }
+    }
+    for video in videos.iter().filter(|p| p.wasprocessed) {
+        let Some(start) = media_timestamp(&video.file_path, None) else {
+            continue;
+        };
+        let duration_ms = if video.fps > 0.0 {
+            (video.n_frames as f64 / video.fps * 1000.0) as i64
+        } else {
+            0
+        };
+        let end = start + chrono::Duration::milliseconds(duration_ms);
+        let (deployment_id, media_id) = package.add_media(&video.file_path, root, (start, end), None);
+        let base = ObservationRow {
+            media_id: Some(media_id),
+            ..observation_base(deployment_id, (start, end), video.provenance.as_ref())
+        };
+        package.add_observations(base, video_sightings(video));
+    }
+
+    let deployments: Vec<DeploymentRow> = package
+        .deployments
+        .iter()
+        .map(|(id, d)| DeploymentRow {
+            deployment_id: id.clone(),
+            location_name: d.name.clone().or_else(|| Some(id.clone())),
+            latitude: d.latitude,
+            longitude: d.longitude,
+            deployment_start: iso(&d.start),
+            deployment_end: iso(&d.end),
+            camera_id: d.camera_id.clone(),
+            camera_model: d.camera_model.clone(),
+            ..Default::default()
+        })
+        .collect();
+    write_table(&dir.join("deployments.csv"), &deployments)?;
+    write_table(&dir.join("media.csv"), &package.media)?;
+    write_table(&dir.join("observations.csv"), &package.observations)?;
+
+    let temporal = match (
+        package.deployments.values().map(|d| d.start).min(),
+        package.deployments.values().map(|d| d.end).max(),
+    ) {
+        (Some(start), Some(end)) => serde_json::json!({
+            "start": start.format("%Y-%m-%d").to_string(),
+            "end": end.format("%Y-%m-%d").to_string(),
+        }),
+        _ => serde_json::Value::Null,
+    };
+    let taxonomic: Vec<serde_json::Value> = package
+        .taxa
+        .values()
+        .map(|t| {
+            let mut taxon = serde_json::json!({ "scientificName": t.scientific_name });
+            if let Some(rank) = t.taxon_rank {
+                taxon["taxonRank"] = rank.into();
+            }
+            if let Some(name) = &t.vernacular_name {
+                taxon["vernacularNames"] = serde_json::json!({ "eng": name });
+            }
+            taxon
+        })
+        .collect();
+    let title = root
+        .and_then(|r| r.file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "BoquilaHUB export".to_string());
+    let observation_levels = if events.is_some() { vec!["media", "event"] } else { vec!["media"] };
+    let datapackage = serde_json::json!({
+        "profile": format!("{}/camtrap-dp-profile.json", CAMTRAP_DP_BASE),
+        "created": iso(&Local::now()),
+        "contributors": [{ "title": "BoquilaHUB", "role": "publisher" }],
+        "project": {
+            "title": title,
+            "samplingDesign": "opportunistic",
+            "captureMethod": ["activityDetection"],
+            "individualAnimals": false,
+            "observationLevel": observation_levels,
+        },
+        "temporal": temporal,
+        "taxonomic": taxonomic,
+        "resources": [resource("deployments"), resource("media"), resource("observations")],
+    });
+    std::fs::write(
+        dir.join("datapackage.json"),
+        serde_json::to_string_pretty(&datapackage)?,
+    )?;
+    Ok(())
+}
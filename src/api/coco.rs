@@ -0,0 +1,461 @@
+use super::abstractions::*;
+use anyhow::{bail, ensure, Context, Result};
+use bitvec::vec::BitVec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// The subset of a COCO `instances.json` BoquilaHUB reads and writes.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CocoDataset {
+    #[serde(default)]
+    pub images: Vec<CocoImage>,
+    #[serde(default)]
+    pub annotations: Vec<CocoAnnotation>,
+    #[serde(default)]
+    pub categories: Vec<CocoCategory>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CocoImage {
+    pub id: u64,
+    pub file_name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CocoAnnotation {
+    pub id: u64,
+    pub image_id: u64,
+    pub category_id: u64,
+    pub bbox: [f32; 4], // x, y, w, h
+    #[serde(default)]
+    pub area: f32,
+    #[serde(default)]
+    pub iscrowd: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segmentation: Option<CocoSegmentation>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CocoSegmentation {
+    Rle(CocoRle),
+    Polygons(Vec<Vec<f32>>),
+}
+
+/// Column-major run lengths over the whole image, starting with a run of 0s.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CocoRle {
+    pub size: [u32; 2], // height, width
+    pub counts: RleCounts,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RleCounts {
+    Compressed(String),
+    Raw(Vec<u32>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CocoCategory {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub supercategory: String,
+}
+
+// COCO category ids start at 1; ours start at 0.
+fn category_id(class_id: u32) -> u64 {
+    class_id as u64 + 1
+}
+
+fn class_id(category_id: u64) -> u32 {
+    category_id.saturating_sub(1) as u32
+}
+
+/// Builds a COCO dataset from processed images. Detections and segmentations
+/// become annotations (with `score`); other outputs leave the image without
+/// annotations. With `root`, `file_name` is relative to it so the JSON can
+/// travel with the folder.
+pub fn to_coco(preds: &[PredImg], root: Option<&Path>) -> Result<CocoDataset> {
+    let mut coco = CocoDataset::default();
+    let mut categories: BTreeMap<u32, String> = BTreeMap::new();
+
+    for pred in preds.iter().filter(|p| p.wasprocessed) {
+        if let Some(provenance) = &pred.provenance {
+            for (i, name) in provenance.classes.iter().enumerate() {
+                categories.entry(i as u32).or_insert_with(|| name.clone());
+            }
+        }
+
+        let (width, height) = image::image_dimensions(&pred.file_path)
+            .with_context(|| format!("Failed to read {}", pred.file_path.display()))?;
+        let image_id = coco.images.len() as u64 + 1;
+        coco.images.push(CocoImage {
+            id: image_id,
+            file_name: relative_name(&pred.file_path, root),
+            width,
+            height,
+        });
+
+        let boxes: Vec<(&XYXYc, Option<&BitMatrix>)> = match &pred.aioutput {
+            Some(AIOutputs::ObjectDetection(b)) => b.iter().map(|b| (b, None)).collect(),
+            Some(AIOutputs::Segmentation(s)) => s.iter().map(|s| (&s.bbox, Some(&s.mask))).collect(),
+            _ => Vec::new(),
+        };
+        for (xyxyc, mask) in boxes {
+            let b = &xyxyc.xyxy;
+            categories.entry(b.class_id).or_insert_with(|| xyxyc.label.clone());
+            let (segmentation, area) = match mask {
+                Some(mask) => {
+                    let (counts, area) = encode_mask(mask, b, width, height);
+                    let rle = CocoRle {
+                        size: [height, width],
+                        counts: RleCounts::Compressed(rle_to_string(&counts)),
+                    };
+                    (Some(CocoSegmentation::Rle(rle)), area as f32)
+                }
+                None => (None, (b.x2 - b.x1) * (b.y2 - b.y1)),
+            };
+            coco.annotations.push(CocoAnnotation {
+                id: coco.annotations.len() as u64 + 1,
+                image_id,
+                category_id: category_id(b.class_id),
+                bbox: [b.x1, b.y1, b.x2 - b.x1, b.y2 - b.y1],
+                area,
+                iscrowd: 0,
+                score: Some(b.prob),
+                segmentation,
+            });
+        }
+    }
+
+    coco.categories = categories
+        .into_iter()
+        .map(|(id, name)| CocoCategory {
+            id: category_id(id),
+            name,
+            supercategory: String::new(),
+        })
+        .collect();
+    Ok(coco)
+}
+
+pub fn write_coco(path: impl AsRef<Path>, coco: &CocoDataset) -> Result<()> {
+    let path = path.as_ref();
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    serde_json::to_writer(std::io::BufWriter::new(file), coco)?;
+    Ok(())
+}
+
+pub fn read_coco(path: impl AsRef<Path>) -> Result<CocoDataset> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("Failed to parse COCO file {}", path.display()))
+}
+
+/// Replaces the predictions of every image in `preds` that `coco` lists,
+/// matching `file_name` against the path relative to `root` and then against
+/// the bare file name, when only one image has it. Imported annotations
+/// count as processed, with `prob` taken from `score` (1.0 for ground
+/// truth). Boxes are clipped to the image and dropped when nothing is left.
+/// Returns the indices updated; malformed segmentations are an error.
+pub fn apply_coco(preds: &mut [PredImg], coco: &CocoDataset, root: Option<&Path>) -> Result<Vec<usize>> {
+    let names: HashMap<u64, &str> = coco.categories.iter().map(|c| (c.id, c.name.as_str())).collect();
+    let mut by_image: HashMap<u64, Vec<&CocoAnnotation>> = HashMap::new();
+    for ann in &coco.annotations {
+        by_image.entry(ann.image_id).or_default().push(ann);
+    }
+
+    let mut updated = Vec::new();
+    for image in &coco.images {
+        let Some(i) = find_pred(preds, &image.file_name, root) else {
+            continue;
+        };
+        let anns = by_image.remove(&image.id).unwrap_or_default();
+        let has_masks = anns.iter().any(|a| a.segmentation.is_some());
+
+        let mut boxes = Vec::with_capacity(anns.len());
+        let mut segs = Vec::with_capacity(anns.len());
+        for ann in anns {
+            let (width, height) = match &ann.segmentation {
+                Some(CocoSegmentation::Rle(rle)) => (rle.size[1], rle.size[0]),
+                _ => (image.width, image.height),
+            };
+            let Some([x1, y1, x2, y2]) = clip_bbox(ann.bbox, width, height) else {
+                continue;
+            };
+            let label = names
+                .get(&ann.category_id)
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| ann.category_id.to_string());
+            let xyxy = XYXY::new(x1, y1, x2, y2, ann.score.unwrap_or(1.0), class_id(ann.category_id));
+            let xyxyc = XYXYc::new(xyxy, label);
+            if has_masks {
+                let mask = match &ann.segmentation {
+                    Some(seg) => decode_segmentation(seg, &xyxy)
+                        .with_context(|| format!("Invalid segmentation in annotation {}", ann.id))?,
+                    None => None,
+                };
+                segs.push(SEGc::new(mask.unwrap_or_else(full_mask), xyxyc));
+            } else {
+                boxes.push(xyxyc);
+            }
+        }
+
+        let pred = &mut preds[i];
+        pred.aioutput = Some(if has_masks {
+            AIOutputs::Segmentation(segs)
+        } else {
+            AIOutputs::ObjectDetection(boxes)
+        });
+        pred.wasprocessed = true;
+        pred.provenance = None;
+        updated.push(i);
+    }
+    Ok(updated)
+}
+
+fn relative_name(path: &Path, root: Option<&Path>) -> String {
+    let rel = root
+        .and_then(|r| path.strip_prefix(r).ok())
+        .unwrap_or_else(|| Path::new(path.file_name().unwrap_or(path.as_os_str())));
+    rel.to_string_lossy().replace('\\', "/")
+}
+
+fn find_pred(preds: &[PredImg], file_name: &str, root: Option<&Path>) -> Option<usize> {
+    preds
+        .iter()
+        .position(|p| relative_name(&p.file_path, root) == file_name)
+        .or_else(|| {
+            // Camera-trap cards reuse names like IMG_0001.JPG, so a name
+            // shared by several images says nothing about which one.
+            let bare = Path::new(file_name).file_name()?;
+            let mut matches = preds
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| p.file_path.file_name() == Some(bare));
+            let (i, _) = matches.next()?;
+            matches.next().is_none().then_some(i)
+        })
+}
+
+/// A COCO `[x, y, w, h]` as corners inside a `width`x`height` image, or
+/// None when it is empty there or not a number.
+fn clip_bbox(bbox: [f32; 4], width: u32, height: u32) -> Option<[f32; 4]> {
+    if !bbox.iter().all(|v| v.is_finite()) {
+        return None;
+    }
+    let [x, y, w, h] = bbox;
+    let (x1, x2) = (x.max(0.0), (x + w).min(width as f32));
+    let (y1, y2) = (y.max(0.0), (y + h).min(height as f32));
+    (x2 > x1 && y2 > y1).then_some([x1, y1, x2, y2])
+}
+
+fn full_mask() -> BitMatrix {
+    BitMatrix {
+        data: BitVec::repeat(true, 1),
+        width: 1,
+        height: 1,
+    }
+}
+
+/// Pixel rectangle a bbox-relative mask is stretched over, same as
+/// `render::draw_seg_from_imgbuf`: offset `floor(x1)`, size `x2 - x1`.
+fn mask_rect(b: &XYXY) -> (i64, i64, usize, usize) {
+    (
+        b.x1.floor() as i64,
+        b.y1.floor() as i64,
+        (b.x2 - b.x1).max(0.0) as usize,
+        (b.y2 - b.y1).max(0.0) as usize,
+    )
+}
+
+/// Alternating 0/1 run lengths, starting with a (possibly empty) run of 0s.
+#[derive(Default)]
+struct Runs {
+    counts: Vec<u32>,
+    current: bool,
+    run: u32,
+}
+
+impl Runs {
+    fn push(&mut self, on: bool, n: u32) {
+        if on != self.current {
+            self.counts.push(self.run);
+            self.run = 0;
+            self.current = on;
+        }
+        self.run += n;
+    }
+
+    fn finish(mut self) -> Vec<u32> {
+        self.counts.push(self.run);
+        self.counts
+    }
+}
+
+/// Full-image, column-major run lengths for a bbox-relative mask, plus its
+/// pixel area.
+fn encode_mask(mask: &BitMatrix, b: &XYXY, width: u32, height: u32) -> (Vec<u32>, u32) {
+    let (x_off, y_off, w, h) = mask_rect(b);
+    let mut runs = Runs::default();
+    let mut area = 0u32;
+    for img_x in 0..width as i64 {
+        let x = img_x - x_off;
+        if x < 0 || x as usize >= w {
+            runs.push(false, height);
+            continue;
+        }
+        let mask_x = (x as f32 / w as f32 * mask.width as f32).floor() as usize;
+        for img_y in 0..height as i64 {
+            let y = img_y - y_off;
+            let on = y >= 0 && (y as usize) < h && {
+                let mask_y = (y as f32 / h as f32 * mask.height as f32).floor() as usize;
+                mask_x < mask.width && mask_y < mask.height && mask.data[mask_y * mask.width + mask_x]
+            };
+            runs.push(on, 1);
+            area += on as u32;
+        }
+    }
+    (runs.finish(), area)
+}
+
+/// Crops a full-image segmentation to the annotation's bbox, already
+/// clipped by [`clip_bbox`], at full resolution. `None` for an empty
+/// polygon list or a box under a pixel wide.
+fn decode_segmentation(seg: &CocoSegmentation, b: &XYXY) -> Result<Option<BitMatrix>> {
+    let (x_off, y_off, w, h) = mask_rect(b);
+    if w == 0 || h == 0 {
+        return Ok(None);
+    }
+    let mut data = BitVec::repeat(false, w * h);
+    match seg {
+        CocoSegmentation::Rle(rle) => {
+            let counts = match &rle.counts {
+                RleCounts::Compressed(s) => rle_from_string(s)?,
+                RleCounts::Raw(c) => c.clone(),
+            };
+            let [rle_h, rle_w] = rle.size;
+            let total: u64 = counts.iter().map(|&n| n as u64).sum();
+            ensure!(
+                total == rle_h as u64 * rle_w as u64,
+                "RLE covers {} pixels, but the image has {}x{}",
+                total,
+                rle_w,
+                rle_h
+            );
+            let mut pos = 0usize;
+            for (i, &n) in counts.iter().enumerate() {
+                if i % 2 == 1 {
+                    for p in pos..pos + n as usize {
+                        let (img_x, img_y) = ((p / rle_h as usize) as i64, (p % rle_h as usize) as i64);
+                        let (x, y) = (img_x - x_off, img_y - y_off);
+                        if x >= 0 && y >= 0 && (x as usize) < w && (y as usize) < h {
+                            data.set(y as usize * w + x as usize, true);
+                        }
+                    }
+                }
+                pos += n as usize;
+            }
+        }
+        CocoSegmentation::Polygons(polys) => {
+            if polys.is_empty() {
+                return Ok(None);
+            }
+            for y in 0..h {
+                for x in 0..w {
+                    let (px, py) = ((x_off + x as i64) as f32 + 0.5, (y_off + y as i64) as f32 + 0.5);
+                    if polys.iter().any(|poly| point_in_polygon(poly, px, py)) {
+                        data.set(y * w + x, true);
+                    }
+                }
+            }
+        }
+    }
+    Ok(Some(BitMatrix { data, width: w, height: h }))
+}
+
+/// Even-odd test against a flat `[x0, y0, x1, y1, ...]` polygon.
+fn point_in_polygon(poly: &[f32], px: f32, py: f32) -> bool {
+    let n = poly.len() / 2;
+    let mut inside = false;
+    let mut j = n.wrapping_sub(1);
+    for i in 0..n {
+        let (xi, yi) = (poly[2 * i], poly[2 * i + 1]);
+        let (xj, yj) = (poly[2 * j], poly[2 * j + 1]);
+        if (yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// pycocotools' compressed RLE string (`rleToString`).
+pub fn rle_to_string(counts: &[u32]) -> String {
+    let mut s = String::new();
+    for i in 0..counts.len() {
+        let mut x = counts[i] as i64;
+        if i > 2 {
+            x -= counts[i - 2] as i64;
+        }
+        loop {
+            let mut c = (x & 0x1f) as u8;
+            x >>= 5;
+            let more = if c & 0x10 != 0 { x != -1 } else { x != 0 };
+            if more {
+                c |= 0x20;
+            }
+            s.push((c + 48) as char);
+            if !more {
+                break;
+            }
+        }
+    }
+    s
+}
+
+/// Inverse of [`rle_to_string`] (`rleFrString`). Rejects characters outside
+/// the encoding, numbers cut short, and counts that don't fit a `u32`.
+pub fn rle_from_string(s: &str) -> Result<Vec<u32>> {
+    // 7 groups of 5 bits hold any difference of two u32s and its sign.
+    const MAX_GROUPS: usize = 7;
+    let bytes = s.as_bytes();
+    let mut counts: Vec<u32> = Vec::new();
+    let mut p = 0;
+    while p < bytes.len() {
+        let mut x: i64 = 0;
+        let mut k = 0;
+        loop {
+            let c = match bytes[p].checked_sub(48) {
+                Some(c) if c < 64 => c as i64,
+                _ => bail!("Invalid character {:?} in RLE counts", bytes[p] as char),
+            };
+            ensure!(k < MAX_GROUPS, "RLE count at byte {} is too long", p);
+            x |= (c & 0x1f) << (5 * k);
+            p += 1;
+            k += 1;
+            if c & 0x20 == 0 {
+                if c & 0x10 != 0 {
+                    x |= -1i64 << (5 * k);
+                }
+                break;
+            }
+            ensure!(p < bytes.len(), "RLE counts end in the middle of a number");
+        }
+        if counts.len() > 2 {
+            x += counts[counts.len() - 2] as i64;
+        }
+        counts.push(u32::try_from(x).with_context(|| format!("RLE count {} is out of range", x))?);
+    }
+    Ok(counts)
+}
@@ -0,0 +1,200 @@
+use super::abstractions::*;
+use super::camtrap::deployment_id;
+use super::geojson::{Feature, FeatureCollection, Geometry};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// File next to the media that pins a folder, and every folder under it,
+/// to a place.
+pub const LOCATION_FILE: &str = "deployment.json";
+
+/// Where a camera or recorder stood, as set by the user for a folder.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DeploymentLocation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl DeploymentLocation {
+    /// The `deployment.json` in `dir`, if there is a valid one.
+    pub fn read(dir: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(dir.join(LOCATION_FILE)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// The nearest `deployment.json` from `dir` upwards, stopping at `root`.
+    pub fn find(dir: &Path, root: Option<&Path>) -> Option<Self> {
+        for ancestor in dir.ancestors() {
+            if let Some(location) = Self::read(ancestor) {
+                return Some(location);
+            }
+            if root.is_none_or(|root| ancestor == root) {
+                break;
+            }
+        }
+        None
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(LOCATION_FILE);
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// One file's best prediction, from [`AIOutputs::dominant_prob`].
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FileDetection {
+    pub file: String,
+    pub label: String,
+    pub prob: f32,
+}
+
+/// Everything a map layer shows for one deployment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeploymentSummary {
+    pub id: String,
+    pub name: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Processed files in the deployment.
+    pub files: usize,
+    /// Files per dominant label.
+    pub counts: BTreeMap<String, u32>,
+    pub detections: Vec<FileDetection>,
+}
+
+/// Groups processed images and videos by deployment (the folder they sit in,
+/// relative to `root`). A deployment's position comes from the nearest
+/// `deployment.json`, else from the first image with EXIF GPS.
+pub fn summarize(imgs: &[PredImg], videos: &[PredVideo], root: Option<&Path>) -> Vec<DeploymentSummary> {
+    let mut deployments: BTreeMap<String, DeploymentSummary> = BTreeMap::new();
+    let files = imgs
+        .iter()
+        .filter(|img| img.wasprocessed)
+        .map(|img| (&img.file_path, img.aioutput.as_ref().and_then(AIOutputs::dominant_prob), img.metadata.as_ref()))
+        .chain(videos.iter().filter(|video| video.wasprocessed).map(|video| {
+            let best = video
+                .frames
+                .iter()
+                .flatten()
+                .filter_map(AIOutputs::dominant_prob)
+                .max_by(|a, b| a.2.total_cmp(&b.2));
+            (&video.file_path, best, None)
+        }));
+
+    for (path, best, metadata) in files {
+        let id = deployment_id(path, root);
+        let deployment = deployments.entry(id.clone()).or_insert_with(|| {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let location = DeploymentLocation::find(dir, root);
+            DeploymentSummary {
+                id,
+                name: location.as_ref().and_then(|l| l.name.clone()),
+                latitude: location.as_ref().map(|l| l.latitude),
+                longitude: location.as_ref().map(|l| l.longitude),
+                files: 0,
+                counts: BTreeMap::new(),
+                detections: Vec::new(),
+            }
+        });
+        let gps = metadata.filter(|m| m.latitude.is_some() && m.longitude.is_some());
+        if let Some(m) = gps.filter(|_| deployment.latitude.is_none()) {
+            (deployment.latitude, deployment.longitude) = (m.latitude, m.longitude);
+        }
+        deployment.files += 1;
+        if let Some((_, label, prob)) = best {
+            *deployment.counts.entry(label.to_owned()).or_default() += 1;
+            deployment.detections.push(FileDetection {
+                file: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                label: label.to_owned(),
+                prob,
+            });
+        }
+    }
+    deployments.into_values().collect()
+}
+
+impl DeploymentSummary {
+    fn position(&self) -> Option<(f64, f64)> {
+        Some((self.longitude?, self.latitude?))
+    }
+
+    fn title(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+}
+
+/// One point per located deployment, with its counts and detections.
+/// Deployments without a position are left out.
+pub fn to_geojson(deployments: &[DeploymentSummary]) -> FeatureCollection {
+    let features = deployments
+        .iter()
+        .filter_map(|d| {
+            let (lon, lat) = d.position()?;
+            let mut properties = Map::new();
+            properties.insert("deployment".into(), d.id.as_str().into());
+            if let Some(name) = &d.name {
+                properties.insert("name".into(), name.as_str().into());
+            }
+            properties.insert("files".into(), d.files.into());
+            properties.insert("counts".into(), serde_json::to_value(&d.counts).unwrap_or_default());
+            properties.insert("detections".into(), serde_json::to_value(&d.detections).unwrap_or_default());
+            Some(Feature::new(Geometry::Point([lon, lat]), properties))
+        })
+        .collect();
+    FeatureCollection { crs: None, features }
+}
+
+/// KML with a placemark per located deployment. Counts go in both the
+/// balloon text and `ExtendedData`, so Google Earth shows them and QGIS
+/// reads them as fields.
+pub fn to_kml(deployments: &[DeploymentSummary]) -> String {
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n",
+    );
+    for d in deployments {
+        let Some((lon, lat)) = d.position() else { continue };
+        let _ = writeln!(kml, "<Placemark>\n<name>{}</name>", xml_escape(d.title()));
+
+        let mut description = format!("{} files", d.files);
+        for (label, count) in &d.counts {
+            let _ = write!(description, "\n{}: {}", label, count);
+        }
+        let _ = writeln!(kml, "<description>{}</description>", xml_escape(&description));
+
+        kml.push_str("<ExtendedData>\n");
+        let mut data = |name: &str, value: &str| {
+            let _ = writeln!(kml, "<Data name=\"{}\"><value>{}</value></Data>", xml_escape(name), xml_escape(value));
+        };
+        data("deployment", &d.id);
+        data("files", &d.files.to_string());
+        for (label, count) in &d.counts {
+            data(label, &count.to_string());
+        }
+        kml.push_str("</ExtendedData>\n");
+
+        let _ = writeln!(kml, "<Point><coordinates>{},{}</coordinates></Point>\n</Placemark>", lon, lat);
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+pub fn write_kml(path: impl AsRef<Path>, deployments: &[DeploymentSummary]) -> Result<()> {
+    let path = path.as_ref();
+    std::fs::write(path, to_kml(deployments)).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,241 @@
+use super::abstractions::AvailableModel;
+use super::bq::BQModel;
+use anyhow::{bail, ensure, Context, Result};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// The model index `pull` reads unless `--registry` or `BOQUILAHUB_REGISTRY`
+/// names another.
+pub const DEFAULT_INDEX: &str = "https://boquila.org/api/models.json";
+
+/// The model index to use: `flag`, else the `BOQUILAHUB_REGISTRY`
+/// environment variable, else [`DEFAULT_INDEX`]. It may be a `file://` URL
+/// or a plain path, for mirrors on a local disk or network share.
+pub fn index_url(flag: Option<&str>) -> String {
+    flag.map(str::to_owned)
+        .or_else(|| std::env::var("BOQUILAHUB_REGISTRY").ok().filter(|url| !url.is_empty()))
+        .unwrap_or_else(|| DEFAULT_INDEX.to_owned())
+}
+
+/// The path a `file://` URL or plain path points to; `None` for http(s).
+pub fn local_path(url: &str) -> Option<PathBuf> {
+    match url.strip_prefix("file://") {
+        // file:///C:/models → C:/models
+        Some(path) if matches!(path.as_bytes(), [b'/', _, b':', ..]) => Some(PathBuf::from(&path[1..])),
+        Some(path) => Some(PathBuf::from(path)),
+        None if url.contains("://") => None,
+        None => Some(PathBuf::from(url)),
+    }
+}
+
+/// `link`, from the index at `index`, made absolute. Links without a scheme
+/// are relative to the index's folder, so a mirror can be copied as a whole.
+pub fn resolve_link(index: &str, link: &str) -> String {
+    if link.contains("://") || Path::new(link).is_absolute() {
+        return link.to_owned();
+    }
+    match index.rfind(['/', '\\']) {
+        Some(end) => format!("{}/{}", &index[..end], link),
+        None => link.to_owned(),
+    }
+}
+
+/// The models listed by the index at `url`, with their links resolved.
+pub async fn fetch_index(url: &str) -> Result<Vec<AvailableModel>> {
+    let models: Vec<AvailableModel> = match local_path(url) {
+        Some(path) => {
+            let text = fs::read(&path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_slice(&text).with_context(|| format!("Failed to parse {}", path.display()))?
+        }
+        None => reqwest::get(url).await?.error_for_status()?.json().await?,
+    };
+    Ok(models
+        .into_iter()
+        .map(|model| AvailableModel {
+            download_link: resolve_link(url, &model.download_link),
+            ..model
+        })
+        .collect())
+}
+
+/// The file name `model` is saved under in `models/`: the last part of its link.
+pub fn file_name(model: &AvailableModel) -> Option<&str> {
+    let link = model.download_link.split(['?', '#']).next()?;
+    link.rsplit(['/', '\\']).next().filter(|name| !name.is_empty() && *name != "..")
+}
+
+/// Whether the copy at `path` is older than `model`: its hash differs from
+/// the index's or, when the index has none, so does its `model_version`.
+/// Without either there is no telling, and it counts as current.
+pub fn is_outdated(path: &Path, model: &AvailableModel) -> Result<bool> {
+    if let Some(expected) = &model.sha256 {
+        return Ok(!BQModel::file_sha256(path)?.eq_ignore_ascii_case(expected));
+    }
+    let Some(version) = &model.version else {
+        return Ok(false);
+    };
+    let local = BQModel::from_file_to_metadata(path)?.info.model_version;
+    Ok(local.as_deref() != Some(version.as_str()))
+}
+
+/// Where an unfinished download of `dest` is kept.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Where the ETag or Last-Modified date of an unfinished HTTP download of
+/// `dest` is kept, so it's only resumed against the same file.
+pub fn validator_path(dest: &Path) -> PathBuf {
+    let mut name = part_path(dest).into_os_string();
+    name.push(".validator");
+    PathBuf::from(name)
+}
+
+/// How [`fetch`] filled the `.part` file.
+enum Fetched {
+    /// From the first byte.
+    Whole,
+    /// After the bytes an earlier run left.
+    Resumed,
+    /// The server refused the range asked for; nothing was written.
+    Refused,
+}
+
+/// Streams `url` to `dest` through [`part_path`], so an interrupted download
+/// resumes where it stopped: over HTTP with a `Range` request guarded by
+/// `If-Range`, from a `file://` mirror by seeking. `progress` gets the bytes
+/// so far and the total, when known. With `sha256`, the finished file must
+/// match it or is deleted. A resume the server refuses, or one that doesn't
+/// match `sha256`, starts over from the first byte once.
+pub async fn download(url: &str, dest: &Path, sha256: Option<&str>, mut progress: impl FnMut(u64, Option<u64>)) -> Result<()> {
+    let part = part_path(dest);
+    let validator = validator_path(dest);
+    let mut restarted = false;
+    loop {
+        let resumed = match fetch(url, &part, &validator, &mut progress).await? {
+            Fetched::Whole => false,
+            Fetched::Resumed => true,
+            Fetched::Refused => {
+                ensure!(!restarted, "The server refused to resume the download");
+                discard(&part, &validator).await;
+                restarted = true;
+                continue;
+            }
+        };
+
+        if let Some(expected) = sha256 {
+            let actual = BQModel::file_sha256(&part)?;
+            if !actual.eq_ignore_ascii_case(expected) {
+                discard(&part, &validator).await;
+                if resumed && !restarted {
+                    // The bytes kept from the earlier run may be the culprit.
+                    restarted = true;
+                    continue;
+                }
+                bail!("Checksum mismatch: expected {}, got {}; the download was discarded", expected, actual);
+            }
+        }
+        let _ = fs::remove_file(&validator).await;
+        return fs::rename(&part, dest)
+            .await
+            .with_context(|| format!("Failed to move {} into place", dest.display()));
+    }
+}
+
+async fn discard(part: &Path, validator: &Path) {
+    let _ = fs::remove_file(part).await;
+    let _ = fs::remove_file(validator).await;
+}
+
+/// One pass of [`download`]: appends what `part` is missing of `url`.
+async fn fetch(url: &str, part: &Path, validator: &Path, progress: &mut impl FnMut(u64, Option<u64>)) -> Result<Fetched> {
+    let mut done = fs::metadata(part).await.map(|meta| meta.len()).unwrap_or(0);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(part)
+        .await
+        .with_context(|| format!("Failed to create {}", part.display()))?;
+
+    match local_path(url) {
+        Some(source) => {
+            let mut input = fs::File::open(&source)
+                .await
+                .with_context(|| format!("Failed to open {}", source.display()))?;
+            let total = input.metadata().await?.len();
+            if done > total {
+                file.set_len(0).await?;
+                done = 0;
+            }
+            let resumed = done > 0;
+            input.seek(SeekFrom::Start(done)).await?;
+            let mut buf = vec![0u8; 1 << 20];
+            loop {
+                let n = input.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                file.write_all(&buf[..n]).await?;
+                done += n as u64;
+                progress(done, Some(total));
+            }
+            file.flush().await?;
+            Ok(if resumed { Fetched::Resumed } else { Fetched::Whole })
+        }
+        None => {
+            use reqwest::header::{ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+            let mut request = reqwest::Client::new().get(url);
+            // Without a validator there's no telling whether the bytes on
+            // disk belong to the file the server has now.
+            match fs::read_to_string(validator).await {
+                Ok(tag) if done > 0 => {
+                    request = request.header(RANGE, format!("bytes={}-", done)).header(IF_RANGE, tag.trim());
+                }
+                _ => {
+                    file.set_len(0).await?;
+                    done = 0;
+                }
+            }
+            let mut response = request.send().await.context("Failed to download model")?;
+            let status = response.status();
+            if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+                return Ok(Fetched::Refused);
+            }
+            ensure!(
+                status.is_success(),
+                "Download failed with status: {} ({})",
+                status.as_u16(),
+                status.canonical_reason().unwrap_or("Unknown error")
+            );
+            let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT && done > 0;
+            if !resumed {
+                // The range was ignored or the file changed: start over and
+                // remember what this copy is.
+                file.set_len(0).await?;
+                done = 0;
+                let headers = response.headers();
+                let strong_etag = headers.get(ETAG).filter(|tag| !tag.as_bytes().starts_with(b"W/"));
+                match strong_etag.or_else(|| headers.get(LAST_MODIFIED)).and_then(|tag| tag.to_str().ok()) {
+                    Some(tag) => fs::write(validator, tag).await?,
+                    None => {
+                        let _ = fs::remove_file(validator).await;
+                    }
+                }
+            }
+            let total = response.content_length().map(|len| len + done);
+            while let Some(chunk) = response.chunk().await.context("Download interrupted; run pull again to resume")? {
+                file.write_all(&chunk).await?;
+                done += chunk.len() as u64;
+                progress(done, total);
+            }
+            file.flush().await?;
+            Ok(if resumed { Fetched::Resumed } else { Fetched::Whole })
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use super::abstractions::*;
+use super::metadata::media_timestamp;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How an event picks its species from its frames' `dominant_species`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EventLabel {
+    /// The single most confident frame wins.
+    #[default]
+    MaxConfidence,
+    /// The label most frames agree on; ties go to the higher summed confidence.
+    Vote,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventConfig {
+    /// Frames further apart than this start a new event.
+    pub gap_s: u32,
+    pub label: EventLabel,
+}
+
+impl Default for EventConfig {
+    fn default() -> Self {
+        Self {
+            gap_s: 60,
+            label: EventLabel::MaxConfidence,
+        }
+    }
+}
+
+/// A burst of images from one camera, each within `gap_s` of the previous.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub camera: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    /// Indices into the `PredImg` slice the event was grouped from, in
+    /// capture order.
+    pub members: Vec<usize>,
+}
+
+/// What an event shows: its species and the most individuals seen in any
+/// one frame. `label` is `None` when no frame predicted anything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventSummary {
+    pub label: Option<String>,
+    pub class_id: Option<u32>,
+    pub prob: Option<f32>,
+    pub max_count: u32,
+}
+
+/// Folder the image sits in, plus the camera serial when EXIF has one, so two
+/// cameras dumped into the same folder don't merge their bursts.
+pub fn camera_key(pred: &PredImg) -> String {
+    let folder = pred
+        .file_path
+        .parent()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match pred.metadata.as_ref().and_then(|m| m.serial_number.as_deref()) {
+        Some(serial) => format!("{}#{}", folder, serial),
+        None => folder,
+    }
+}
+
+/// Groups `preds` into events, ordered by start time. Capture times come from
+/// EXIF, falling back to the file modification time; files with neither are
+/// left out.
+pub fn group_events(preds: &[PredImg], gap_s: u32) -> Vec<Event> {
+    let mut stamped: Vec<(String, DateTime<Local>, usize)> = preds
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| {
+            let ts = media_timestamp(&p.file_path, p.metadata.as_ref())?;
+            Some((camera_key(p), ts, i))
+        })
+        .collect();
+    stamped.sort();
+
+    let gap = chrono::Duration::seconds(gap_s as i64);
+    let mut events: Vec<Event> = Vec::new();
+    for (camera, ts, i) in stamped {
+        match events.last_mut() {
+            Some(event) if event.camera == camera && ts - event.end <= gap => {
+                event.end = ts;
+                event.members.push(i);
+            }
+            _ => events.push(Event {
+                camera,
+                start: ts,
+                end: ts,
+                members: vec![i],
+            }),
+        }
+    }
+    events.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.camera.cmp(&b.camera)));
+    events
+}
+
+/// Index of the event holding image `idx`.
+pub fn event_of(events: &[Event], idx: usize) -> Option<usize> {
+    events.iter().position(|e| e.members.contains(&idx))
+}
+
+/// How many boxes/points/segments in `output` carry `label`, boxes named as
+/// by [`XYXYc::species`]. Classification counts as one when its top label
+/// matches.
+fn label_count(output: &AIOutputs, label: &str) -> u32 {
+    let n = match output {
+        AIOutputs::ObjectDetection(boxes) => boxes.iter().filter(|b| b.species().1 == label).count(),
+        AIOutputs::Segmentation(segs) => segs.iter().filter(|s| s.bbox.species().1 == label).count(),
+        AIOutputs::PointDetection(points) => points.iter().filter(|p| p.label == label).count(),
+        AIOutputs::Classification(probs) => {
+            probs.top().is_some_and(|p| p.label == label) as usize
+        }
+        AIOutputs::AudioClassification(_) | AIOutputs::Embed(_) => 0,
+    };
+    n as u32
+}
+
+impl Event {
+    pub fn summarize(&self, preds: &[PredImg], mode: EventLabel) -> EventSummary {
+        let outputs: Vec<&AIOutputs> = self
+            .members
+            .iter()
+            .filter_map(|&i| preds.get(i))
+            .filter(|p| p.wasprocessed)
+            .filter_map(|p| p.aioutput.as_ref())
+            .collect();
+        let dominant: Vec<(u32, &str, f32)> =
+            outputs.iter().filter_map(|o| o.dominant_species()).collect();
+
+        let best = match mode {
+            EventLabel::MaxConfidence => dominant
+                .iter()
+                .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal))
+                .copied(),
+            EventLabel::Vote => {
+                let mut votes: HashMap<&str, (usize, f32, u32, f32)> = HashMap::new();
+                for &(class_id, label, prob) in &dominant {
+                    let entry = votes.entry(label).or_insert((0, 0.0, class_id, 0.0));
+                    entry.0 += 1;
+                    entry.1 += prob;
+                    entry.3 = entry.3.max(prob);
+                }
+                votes
+                    .into_iter()
+                    .max_by(|a, b| {
+                        (a.1.0, a.1.1)
+                            .partial_cmp(&(b.1.0, b.1.1))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map(|(label, (_, _, class_id, prob))| (class_id, label, prob))
+            }
+        };
+
+        match best {
+            Some((class_id, label, prob)) => EventSummary {
+                max_count: outputs.iter().map(|o| label_count(o, label)).max().unwrap_or(0),
+                label: Some(label.to_string()),
+                class_id: Some(class_id),
+                prob: Some(prob),
+            },
+            None => EventSummary::default(),
+        }
+    }
+}
+
+/// One CSV row per event, the event-level counterpart of `PredRow`.
+#[derive(Serialize)]
+struct EventRow<'a> {
+    event: usize,
+    camera: &'a str,
+    start: String,
+    end: String,
+    n_images: usize,
+    label: String,
+    class_id: Option<u32>,
+    prob: Option<f32>,
+    max_count: u32,
+    files: String,
+}
+
+/// Writes one row per event; `files` lists the member images separated by
+/// `;`. Returns the number of events written.
+pub fn write_events_csv(path: impl AsRef<Path>, preds: &[PredImg], config: &EventConfig) -> Result<usize> {
+    let path = path.as_ref();
+    let events = group_events(preds, config.gap_s);
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    for (n, event) in events.iter().enumerate() {
+        let summary = event.summarize(preds, config.label);
+        let files: Vec<String> = event
+            .members
+            .iter()
+            .map(|&i| preds[i].file_path.to_string_lossy().into_owned())
+            .collect();
+        writer.serialize(EventRow {
+            event: n + 1,
+            camera: &event.camera,
+            start: event.start.to_rfc3339_opts(SecondsFormat::Secs, false),
+            end: event.end.to_rfc3339_opts(SecondsFormat::Secs, false),
+            n_images: event.members.len(),
+            label: summary.label.unwrap_or_default(),
+            class_id: summary.class_id,
+            prob: summary.prob,
+            max_count: summary.max_count,
+            files: files.join(";"),
+        })?;
+    }
+    writer.flush()?;
+    Ok(events.len())
+}
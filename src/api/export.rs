@@ -36,3 +36,26 @@ pub fn prepare_export_video(path: &Path) -> PathBuf {
         .unwrap_or_else(|| "exported_video".to_string());
     PathBuf::from(format!("{}/exported_{}", EXPORT_DIR, name))
 }
+
+/// `export/<stem>_<timestamp>.<ext>` for whole-project exports (CSV, COCO…),
+/// so repeated exports don't clobber each other.
+pub fn prepare_export_file(stem: &str, ext: &str) -> PathBuf {
+    std::fs::create_dir_all(EXPORT_DIR).expect("Failed to create export directory");
+    PathBuf::from(format!(
+        "{}/{}_{}.{}",
+        EXPORT_DIR,
+        stem,
+        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+        ext
+    ))
+}
+
+/// `export/<stem>_<timestamp>/` for exports made of several files.
+pub fn prepare_export_dir(stem: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/{}_{}",
+        EXPORT_DIR,
+        stem,
+        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+    ))
+}
@@ -1,3 +1,6 @@
+use std::fs;
+use std::path::PathBuf;
+
 pub const IMAGE_FORMATS: [&'static str; 23] = [
     "bmp", "dib", "dds", "ff", "gif", "hdr", "ico", "cur", "jpg", "jpeg", "jpe", "jfif", "exr",
     "png", "pnm", "pbm", "pgm", "ppm", "qoi", "tga", "tiff", "tif", "webp",
@@ -12,4 +15,41 @@ pub const VIDEO_FORMATS: [&'static str; 34] = [
 pub const AUDIO_FORMATS: [&'static str; 18] = [
     "mp3", "wav", "flac", "ogg", "opus", "aac", "m4a", "wma", "aiff", "aif", "au", "snd", "amr",
     "ac3", "mid", "midi", "wv", "ape",
-];
\ No newline at end of file
+];
+
+/// Splits a directory listing into `(images, audios, videos)` by extension.
+/// Non-files and unknown extensions are dropped.
+pub fn partition_media_in_dir(
+    entries: fs::ReadDir,
+) -> (Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>) {
+    partition_media(entries.flatten().map(|entry| entry.path()))
+}
+
+/// Same as [`partition_media_in_dir`], for an arbitrary list of paths.
+pub fn partition_media(
+    paths: impl IntoIterator<Item = PathBuf>,
+) -> (Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>) {
+    let mut images = Vec::new();
+    let mut audios = Vec::new();
+    let mut videos = Vec::new();
+    for path in paths {
+        if !path.is_file() {
+            continue;
+        }
+        let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+            continue;
+        };
+        if has_ext(ext, IMAGE_FORMATS) {
+            images.push(path);
+        } else if has_ext(ext, AUDIO_FORMATS) {
+            audios.push(path);
+        } else if has_ext(ext, VIDEO_FORMATS) {
+            videos.push(path);
+        }
+    }
+    (images, audios, videos)
+}
+
+pub fn has_ext<const N: usize>(ext: &str, formats: [&str; N]) -> bool {
+    formats.iter().any(|f| ext.eq_ignore_ascii_case(f))
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// The subset of GeoJSON BoquilaHUB writes: points and polygons with free-form
+/// properties, as QGIS reads them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename = "FeatureCollection")]
+pub struct FeatureCollection {
+    /// Legacy (pre RFC 7946) CRS member. RFC 7946 files are always WGS 84;
+    /// QGIS still honours this for rasters in a projected CRS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crs: Option<Crs>,
+    pub features: Vec<Feature>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename = "Feature")]
+pub struct Feature {
+    pub geometry: Geometry,
+    pub properties: Map<String, Value>,
+}
+
+/// Coordinates are `[x, y]`: longitude and latitude, or easting and northing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum Geometry {
+    Point([f64; 2]),
+    /// Outer ring first; each ring is closed (last point equals the first).
+    Polygon(Vec<Vec<[f64; 2]>>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename = "name")]
+pub struct Crs {
+    pub properties: CrsName,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CrsName {
+    pub name: String,
+}
+
+impl Crs {
+    pub fn epsg(code: u32) -> Self {
+        Self {
+            properties: CrsName {
+                name: format!("urn:ogc:def:crs:EPSG::{}", code),
+            },
+        }
+    }
+}
+
+impl Feature {
+    pub fn new(geometry: Geometry, properties: Map<String, Value>) -> Self {
+        Self { geometry, properties }
+    }
+}
+
+pub fn write_geojson(path: impl AsRef<Path>, collection: &FeatureCollection) -> Result<()> {
+    let path = path.as_ref();
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    serde_json::to_writer(std::io::BufWriter::new(file), collection)?;
+    Ok(())
+}
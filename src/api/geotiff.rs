@@ -0,0 +1,279 @@
+use super::abstractions::*;
+use super::geojson::{Crs, Feature, FeatureCollection, Geometry};
+use super::pipeline::Pipeline;
+use super::processing::tiling::{merge_tiles, tile_grid};
+use anyhow::{bail, Context, Result};
+use image::{ImageBuffer, Rgb};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tiff::decoder::{ChunkType, Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+const GEO_KEY_GEOGRAPHIC_TYPE: u16 = 2048;
+const GEO_KEY_PROJECTED_CS_TYPE: u16 = 3072;
+const GEO_KEY_USER_DEFINED: u16 = 32767;
+
+/// Affine pixel → map transform, in GDAL order: `x = c[0] + c[1]·col + c[2]·row`,
+/// `y = c[3] + c[4]·col + c[5]·row`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeoTransform(pub [f64; 6]);
+
+impl GeoTransform {
+    /// Builds the transform from the GeoTIFF tags: `ModelTransformationTag`
+    /// when present, else the first tie point plus `ModelPixelScaleTag`.
+    pub fn from_tags(scale: Option<&[f64]>, tiepoint: Option<&[f64]>, transformation: Option<&[f64]>) -> Option<Self> {
+        if let Some(m) = transformation.filter(|m| m.len() >= 8) {
+            return Some(Self([m[3], m[0], m[1], m[7], m[4], m[5]]));
+        }
+        match (scale, tiepoint) {
+            (Some(s), Some(t)) if s.len() >= 2 && t.len() >= 6 => {
+                let (col, row, x, y) = (t[0], t[1], t[3], t[4]);
+                Some(Self([x - col * s[0], s[0], 0.0, y + row * s[1], 0.0, -s[1]]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Map coordinates of the pixel position `(col, row)`.
+    pub fn apply(&self, col: f64, row: f64) -> [f64; 2] {
+        let c = &self.0;
+        [c[0] + c[1] * col + c[2] * row, c[3] + c[4] * col + c[5] * row]
+    }
+}
+
+/// EPSG code from a `GeoKeyDirectoryTag`: the projected CRS, else the
+/// geographic one. `None` for user-defined or missing CRSs.
+pub fn epsg_from_geokeys(geokeys: &[u16]) -> Option<u32> {
+    let keys: HashMap<u16, u16> = geokeys
+        .get(4..)?
+        .chunks_exact(4)
+        .filter(|entry| entry[1] == 0) // value stored inline
+        .map(|entry| (entry[0], entry[3]))
+        .collect();
+    [GEO_KEY_PROJECTED_CS_TYPE, GEO_KEY_GEOGRAPHIC_TYPE]
+        .iter()
+        .filter_map(|key| keys.get(key))
+        .find(|&&code| code != 0 && code != GEO_KEY_USER_DEFINED)
+        .map(|&code| code as u32)
+}
+
+/// A georeferenced raster read window by window, so mosaics far larger than
+/// memory can be processed. Only the strips or tiles under a window are
+/// decoded; the last window's are kept for the next one. Band-interleaved
+/// files (`PlanarConfiguration` 2) store each band in its own chunks, which
+/// are read together and interleaved.
+pub struct GeoTiff {
+    decoder: Decoder<BufReader<File>>,
+    pub width: u32,
+    pub height: u32,
+    pub transform: GeoTransform,
+    pub epsg: Option<u32>,
+    samples: usize,
+    planar: bool,
+    chunks_per_plane: u32,
+    cache: HashMap<u32, Vec<u8>>,
+}
+
+impl GeoTiff {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut decoder = Decoder::new(BufReader::new(file))
+            .with_context(|| format!("Not a TIFF file: {}", path.display()))?
+            .with_limits(tiff::decoder::Limits::unlimited());
+        let (width, height) = decoder.dimensions()?;
+        let samples = match decoder.colortype()? {
+            ColorType::Gray(8 | 16) => 1,
+            ColorType::GrayA(8 | 16) => 2,
+            ColorType::RGB(8 | 16) => 3,
+            ColorType::RGBA(8 | 16) => 4,
+            other => bail!("Unsupported GeoTIFF color type {:?}", other),
+        };
+        let planar = match decoder.get_tag_u32(Tag::PlanarConfiguration).unwrap_or(1) {
+            1 => false,
+            2 => true,
+            other => bail!("Unsupported GeoTIFF planar configuration {}", other),
+        };
+        let (chunk_w, chunk_h) = decoder.chunk_dimensions();
+        let chunks_per_plane = match decoder.get_chunk_type() {
+            ChunkType::Strip => height.div_ceil(chunk_h),
+            ChunkType::Tile => width.div_ceil(chunk_w) * height.div_ceil(chunk_h),
+        };
+
+        let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag).ok();
+        let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag).ok();
+        let transformation = decoder.get_tag_f64_vec(Tag::ModelTransformationTag).ok();
+        let transform = GeoTransform::from_tags(scale.as_deref(), tiepoint.as_deref(), transformation.as_deref())
+            .with_context(|| format!("{} has no georeferencing tags", path.display()))?;
+        let epsg = decoder
+            .get_tag_u16_vec(Tag::GeoKeyDirectoryTag)
+            .ok()
+            .and_then(|keys| epsg_from_geokeys(&keys));
+
+        Ok(Self {
+            decoder,
+            width,
+            height,
+            transform,
+            epsg,
+            samples,
+            planar,
+            chunks_per_plane,
+            cache: HashMap::new(),
+        })
+    }
+
+    /// The pixels under `window`, clipped to the raster, as RGB.
+    pub fn read_window(&mut self, window: &XYXY) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let x0 = (window.x1.max(0.0) as u32).min(self.width);
+        let y0 = (window.y1.max(0.0) as u32).min(self.height);
+        let x1 = (window.x2.max(0.0) as u32).min(self.width);
+        let y1 = (window.y2.max(0.0) as u32).min(self.height);
+        let mut out = ImageBuffer::new(x1.saturating_sub(x0), y1.saturating_sub(y0));
+        if out.width() == 0 || out.height() == 0 {
+            return Ok(out);
+        }
+
+        let (chunk_w, chunk_h) = self.decoder.chunk_dimensions();
+        let chunks_across = match self.decoder.get_chunk_type() {
+            ChunkType::Strip => 1,
+            ChunkType::Tile => self.width.div_ceil(chunk_w),
+        };
+        let needed: Vec<(u32, u32, u32)> = (y0 / chunk_h..=(y1 - 1) / chunk_h)
+            .flat_map(|cy| (x0 / chunk_w..=(x1 - 1) / chunk_w).map(move |cx| (cy * chunks_across + cx, cx, cy)))
+            .collect();
+        self.cache.retain(|index, _| needed.iter().any(|&(i, _, _)| i == *index));
+
+        for (index, cx, cy) in needed {
+            if !self.cache.contains_key(&index) {
+                let rgb = self.read_rgb8(index)?;
+                self.cache.insert(index, rgb);
+            }
+            let rgb = &self.cache[&index];
+            let (data_w, data_h) = self.decoder.chunk_data_dimensions(index);
+            let (origin_x, origin_y) = (cx * chunk_w, cy * chunk_h);
+            for y in y0.max(origin_y)..y1.min(origin_y + data_h) {
+                for x in x0.max(origin_x)..x1.min(origin_x + data_w) {
+                    let i = (((y - origin_y) * data_w + (x - origin_x)) * 3) as usize;
+                    out.put_pixel(x - x0, y - y0, Rgb([rgb[i], rgb[i + 1], rgb[i + 2]]));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Chunk `index` of the first plane as packed RGB8. In planar files the
+    /// green and blue bands are the same chunk in the following planes.
+    fn read_rgb8(&mut self, index: u32) -> Result<Vec<u8>> {
+        if !self.planar {
+            let bytes = to_u8(self.decoder.read_chunk(index)?)?;
+            return Ok(bytes
+                .chunks_exact(self.samples)
+                .flat_map(|px| match self.samples {
+                    1 | 2 => [px[0], px[0], px[0]],
+                    _ => [px[0], px[1], px[2]],
+                })
+                .collect());
+        }
+        let bands = if self.samples >= 3 { 3 } else { 1 };
+        let planes = (0..bands)
+            .map(|plane| to_u8(self.decoder.read_chunk(plane * self.chunks_per_plane + index)?))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((0..planes[0].len())
+            .flat_map(|i| [0, 1, 2].map(|band: usize| planes[band.min(planes.len() - 1)][i]))
+            .collect())
+    }
+}
+
+/// Decoded chunk samples as bytes. 16-bit rasters keep their high byte.
+fn to_u8(chunk: DecodingResult) -> Result<Vec<u8>> {
+    match chunk {
+        DecodingResult::U8(data) => Ok(data),
+        DecodingResult::U16(data) => Ok(data.into_iter().map(|v| (v >> 8) as u8).collect()),
+        _ => bail!("Unsupported GeoTIFF sample format"),
+    }
+}
+
+/// Runs the active pipeline over `path` in `window`×`window` pieces that
+/// overlap by `overlap`, and merges the detections in raster pixel coordinates.
+/// `on_window(done, total)` is called after each window.
+pub fn process_geotiff(
+    path: impl AsRef<Path>,
+    window: u32,
+    overlap: f32,
+    mut on_window: impl FnMut(usize, usize),
+) -> Result<(GeoTiff, AIOutputs)> {
+    let pipeline = Pipeline::current().context("no model loaded")?;
+    let mut tif = GeoTiff::open(path)?;
+    let windows = tile_grid(
+        tif.width,
+        tif.height,
+        &TileConfig {
+            size: window,
+            overlap,
+            full_image: false,
+            ..Default::default()
+        },
+    );
+
+    let mut parts = Vec::with_capacity(windows.len());
+    for (i, win) in windows.iter().enumerate() {
+        let img = tif.read_window(win)?;
+        parts.push((*win, pipeline.run(&img)?));
+        on_window(i + 1, windows.len());
+    }
+    let config = pipeline.model().config();
+    let point_distance = config.tiling.unwrap_or_default().point_distance;
+    let merged = merge_tiles(parts, config.nms_threshold, point_distance);
+    Ok((tif, merged))
+}
+
+/// Detections in raster pixel coordinates as map-coordinate features: points
+/// for point detections, box outlines for boxes and segments.
+pub fn to_geojson(aioutput: &AIOutputs, transform: &GeoTransform, epsg: Option<u32>) -> FeatureCollection {
+    let polygon = |xyxy: &XYXY| {
+        let corners = [(xyxy.x1, xyxy.y1), (xyxy.x2, xyxy.y1), (xyxy.x2, xyxy.y2), (xyxy.x1, xyxy.y2), (xyxy.x1, xyxy.y1)];
+        Geometry::Polygon(vec![corners.iter().map(|&(x, y)| transform.apply(x as f64, y as f64)).collect()])
+    };
+    let features = match aioutput {
+        AIOutputs::ObjectDetection(dets) => dets.iter().map(|det| Feature::new(polygon(&det.xyxy), box_properties(det))).collect(),
+        AIOutputs::Segmentation(segs) => segs
+            .iter()
+            .map(|seg| Feature::new(polygon(&seg.bbox.xyxy), box_properties(&seg.bbox)))
+            .collect(),
+        AIOutputs::PointDetection(points) => points
+            .iter()
+            .map(|point| {
+                let geometry = Geometry::Point(transform.apply(point.xy.x as f64, point.xy.y as f64));
+                Feature::new(geometry, properties(&point.label, point.xy.prob, point.xy.class_id))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    FeatureCollection {
+        crs: epsg.map(Crs::epsg),
+        features,
+    }
+}
+
+fn properties(label: &str, score: f32, class_id: u32) -> Map<String, Value> {
+    let mut properties = Map::new();
+    properties.insert("label".into(), label.into());
+    properties.insert("score".into(), score.into());
+    properties.insert("class_id".into(), class_id.into());
+    properties
+}
+
+fn box_properties(det: &XYXYc) -> Map<String, Value> {
+    let mut properties = properties(&det.label, det.xyxy.prob, det.xyxy.class_id);
+    let top = det.extra_cls.iter().flatten().max_by(|a, b| a.prob.total_cmp(&b.prob));
+    if let Some(top) = top {
+        properties.insert("species".into(), top.label.as_str().into());
+        properties.insert("species_score".into(), top.prob.into());
+    }
+    properties
+}
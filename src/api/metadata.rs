@@ -0,0 +1,186 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use exif::{In, Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What the camera wrote into an image's EXIF: when, which camera, where,
+/// and for camera traps what fired the shot. Every field is optional since
+/// most files only carry some of them.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ImageMetadata {
+    /// `DateTimeOriginal` as `YYYY-MM-DDTHH:MM:SS`, with the UTC offset
+    /// appended when the camera recorded one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    /// "motion", "timelapse", "external" or the raw MakerNote code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
+    /// Position in the burst, 1-based, and the burst length.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<(u16, u16)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature_c: Option<i16>,
+}
+
+impl ImageMetadata {
+    /// Reads the EXIF block of `path`. `None` when the file has no EXIF or
+    /// nothing in it we use.
+    pub fn read(path: &Path) -> Option<Self> {
+        let file = std::fs::File::open(path).ok()?;
+        let exif = exif::Reader::new()
+            .read_from_container(&mut std::io::BufReader::new(file))
+            .ok()?;
+        let metadata = Self::from_exif(&exif);
+        (metadata != Self::default()).then_some(metadata)
+    }
+
+    pub fn from_exif(exif: &exif::Exif) -> Self {
+        let field = |tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+        let text = |tag| field(tag).and_then(ascii);
+
+        let captured_at = field(Tag::DateTimeOriginal)
+            .or_else(|| field(Tag::DateTime))
+            .and_then(|v| match v {
+                Value::Ascii(parts) => exif::DateTime::from_ascii(parts.first()?).ok(),
+                _ => None,
+            })
+            .map(|mut dt| {
+                let offset = field(Tag::OffsetTimeOriginal).and_then(|v| match v {
+                    Value::Ascii(parts) => parts.first(),
+                    _ => None,
+                });
+                if let Some(offset) = offset {
+                    let _ = dt.parse_offset(offset);
+                }
+                let mut s = format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                    dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second
+                );
+                if let Some(offset) = dt.offset {
+                    let sign = if offset < 0 { '-' } else { '+' };
+                    s.push_str(&format!("{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60));
+                }
+                s
+            });
+
+        let coordinate = |tag, ref_tag, negative: &str| {
+            let degrees = match field(tag)? {
+                Value::Rational(r) if r.len() >= 3 => {
+                    r[0].to_f64() + r[1].to_f64() / 60.0 + r[2].to_f64() / 3600.0
+                }
+                _ => return None,
+            };
+            let sign = if text(ref_tag).as_deref() == Some(negative) { -1.0 } else { 1.0 };
+            degrees.is_finite().then_some(sign * degrees)
+        };
+        let altitude = match field(Tag::GPSAltitude) {
+            Some(Value::Rational(r)) if !r.is_empty() => {
+                let below_sea = matches!(field(Tag::GPSAltitudeRef), Some(Value::Byte(b)) if b.first() == Some(&1));
+                let alt = r[0].to_f64();
+                alt.is_finite().then_some(if below_sea { -alt } else { alt })
+            }
+            _ => None,
+        };
+
+        let mut metadata = ImageMetadata {
+            captured_at,
+            make: text(Tag::Make),
+            model: text(Tag::Model),
+            serial_number: text(Tag::BodySerialNumber),
+            latitude: coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+            longitude: coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+            altitude,
+            ..Default::default()
+        };
+        if let Some(Value::Undefined(note, _)) = field(Tag::MakerNote) {
+            metadata.apply_maker_note(note);
+        }
+        metadata
+    }
+
+    /// Trap-specific fields from the MakerNote. Only Reconyx HyperFire has a
+    /// documented layout (little-endian 16-bit words, version 0xf101); other
+    /// brands' notes are left alone.
+    fn apply_maker_note(&mut self, note: &[u8]) {
+        if note.len() < 70 || note[..2] != [0x01, 0xf1] {
+            return;
+        }
+        let word = |i: usize| u16::from_le_bytes([note[i], note[i + 1]]);
+
+        self.trigger = Some(match note[12] {
+            b'M' => "motion".to_string(),
+            b'T' => "timelapse".to_string(),
+            b'E' => "external".to_string(),
+            c => (c as char).to_string(),
+        });
+        self.sequence = Some((word(14), word(16)));
+        self.event_number = Some(((word(18) as u32) << 16) | word(20) as u32);
+        self.temperature_c = Some(word(38) as i16);
+        if self.serial_number.is_none() {
+            let units: Vec<u16> = note[40..70]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&u| u != 0)
+                .collect();
+            let serial = String::from_utf16_lossy(&units).trim().to_string();
+            if !serial.is_empty() {
+                self.serial_number = Some(serial);
+            }
+        }
+    }
+
+    /// Capture time in local time. Times without an offset are taken to be
+    /// in this machine's timezone, which is what the camera clock usually
+    /// was set to.
+    pub fn capture_time(&self) -> Option<DateTime<Local>> {
+        let s = self.captured_at.as_deref()?;
+        if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            return Some(dt.with_timezone(&Local));
+        }
+        let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").ok()?;
+        Local.from_local_datetime(&naive).earliest()
+    }
+
+    /// "Make Model", without repeating the make when the model already
+    /// starts with it (as many vendors do).
+    pub fn camera(&self) -> Option<String> {
+        match (self.make.as_deref(), self.model.as_deref()) {
+            (Some(make), Some(model)) if model.starts_with(make) => Some(model.to_string()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.or(model).map(str::to_string),
+        }
+    }
+}
+
+/// When the media was captured: EXIF `DateTimeOriginal` when there is one,
+/// else the file modification time, the closest thing every file has.
+pub fn media_timestamp(path: &Path, metadata: Option<&ImageMetadata>) -> Option<DateTime<Local>> {
+    metadata
+        .and_then(|m| m.capture_time())
+        .or_else(|| std::fs::metadata(path).ok()?.modified().ok().map(DateTime::<Local>::from))
+}
+
+fn ascii(value: &Value) -> Option<String> {
+    match value {
+        Value::Ascii(parts) => {
+            let s = String::from_utf8_lossy(parts.first()?).trim().to_string();
+            (!s.is_empty()).then_some(s)
+        }
+        _ => None,
+    }
+}
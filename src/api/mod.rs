@@ -1,12 +1,27 @@
 pub mod abstractions;
+pub mod batch;
 pub mod bq;
+pub mod bqfile;
+pub mod bundle;
+pub mod camtrap;
+pub mod coco;
+pub mod deployment;
+pub mod download;
+pub mod events;
 pub mod export;
 pub mod formats;
+pub mod geojson;
+pub mod geotiff;
+pub mod metadata;
 pub mod models;
+pub mod pipeline;
 pub mod processing;
+pub mod registry;
 pub mod render;
 pub mod rest;
 pub mod stream;
+pub mod tabular;
+pub mod triage;
 pub mod utils;
 pub mod video_file;
 pub mod audio;
\ No newline at end of file
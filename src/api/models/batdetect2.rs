@@ -71,7 +71,7 @@ impl BatDetect2 {
             for i in 0..scores.shape()[1] {
                 let score = scores[[0, i]];
                 // Sorted by descending score, so the first sub-threshold row ends it.
-                if score < self.config.confidence_threshold {
+                if score < self.config.min_threshold() {
                     break;
                 }
                 let cid = class_id[[0, i]].max(0) as usize;
@@ -92,6 +92,7 @@ impl BatDetect2 {
                     ),
                     label: self.classes.get(cid).cloned().unwrap_or_default(),
                     extra_cls: Some(extra_cls),
+                    embedding: None,
                 });
             }
         }
@@ -1,10 +1,15 @@
 use crate::api::{
     abstractions::{AIOutputs, Embedding, ModelConfig},
     bq::AIMetadata,
-    processing::{inference::inference, pre::imgbuf_to_clip_input},
+    models::batched,
+    processing::{
+        inference::{has_dynamic_batch, inference},
+        pre::{imgbuf_to_clip_input, stack_batch},
+    },
 };
 use anyhow::{bail, Error, Result};
 use image::{ImageBuffer, Rgb};
+use ndarray::{Array, Axis, Ix4};
 use ort::{session::Session, value::ValueType};
 
 pub struct Clip {
@@ -15,6 +20,7 @@ pub struct Clip {
     pub model_name: String,
     pub session: Session,
     pub config: ModelConfig,
+    pub dynamic_batch: bool,
 }
 
 impl Clip {
@@ -45,20 +51,38 @@ impl Clip {
             input_name: session.inputs()[0].name().to_string(),
             output_name: session.outputs()[0].name().to_string(),
             model_name: metadata.name,
+            dynamic_batch: has_dynamic_batch(&session),
             session,
             config,
         })
     }
 
     pub fn run_image(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> AIOutputs {
-        let input = imgbuf_to_clip_input(self.input_height, self.input_width, img);
-        let outputs = inference(&self.session, &input, &self.input_name).unwrap();
+        self.run_images(std::slice::from_ref(img)).remove(0)
+    }
+
+    pub fn run_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Vec<AIOutputs> {
+        batched(
+            imgs,
+            self.dynamic_batch,
+            |img| imgbuf_to_clip_input(self.input_height, self.input_width, img),
+            |inputs| self.run_batch(inputs),
+        )
+    }
+
+    fn run_batch(&self, inputs: &[Array<f32, Ix4>]) -> Vec<AIOutputs> {
+        let batch = stack_batch(inputs.iter().map(|input| input.view()));
+        let outputs = inference(&self.session, &batch, &self.input_name).unwrap();
         let tensor = outputs[self.output_name.as_str()]
             .try_extract_array::<f32>()
             .unwrap()
             .into_owned();
-        let raw = tensor.as_slice().expect("non-contiguous embedding tensor");
-
-        AIOutputs::Embed(Embedding::from_raw(raw, self.model_name.clone()))
+        tensor
+            .axis_iter(Axis(0))
+            .map(|row| {
+                let raw: Vec<f32> = row.iter().copied().collect();
+                AIOutputs::Embed(Embedding::from_raw(&raw, self.model_name.clone()))
+            })
+            .collect()
     }
 }
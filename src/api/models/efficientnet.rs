@@ -1,18 +1,19 @@
 use crate::api::{
     abstractions::{AIOutputs, ModelConfig, Prob, ProbSugar},
     bq::AIMetadata,
-    models::Task,
+    models::{batched, Task},
     processing::{
-        inference::inference,
+        inference::{has_dynamic_batch, inference},
         post::{
-            apply_geofence_filter, apply_label_rollup, extract_output,
+            apply_geofence_filter, apply_label_rollup, batch_item, extract_output,
             process_class_output, PostProcessing,
         },
-        pre::{imgbuf_to_input_array, TensorFormat},
+        pre::{imgbuf_to_input_array, stack_batch, TensorFormat},
     },
 };
 use anyhow::{bail, Error, Result};
 use image::{ImageBuffer, Rgb};
+use ndarray::{Array, Ix4, IxDyn};
 use ort::{session::Session, value::ValueType};
 
 pub struct EfficientNetV2 {
@@ -30,6 +31,7 @@ pub struct EfficientNetV2 {
     pub session: Session,
     pub config: ModelConfig,
     pub input_format: TensorFormat,
+    pub dynamic_batch: bool,
 }
 
 impl EfficientNetV2 {
@@ -74,6 +76,7 @@ impl EfficientNetV2 {
         };
 
         let output_name: String = session.outputs()[0].name().to_string();
+        let dynamic_batch = has_dynamic_batch(&session);
 
         Ok(EfficientNetV2 {
             classes: metadata.classes,
@@ -90,24 +93,36 @@ impl EfficientNetV2 {
             session,
             config,
             input_format,
+            dynamic_batch,
         })
     }
 }
 
 impl EfficientNetV2 {
     pub fn run_image(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> AIOutputs {
-        let (input, _img_width, _img_height) = imgbuf_to_input_array(
-            1,
-            3,
-            self.input_height,
-            self.input_width,
-            img,
-            &self.input_format,
-        );
-        let outputs = inference(&self.session, &input, &self.input_name).unwrap();
+        self.run_images(std::slice::from_ref(img)).remove(0)
+    }
+
+    pub fn run_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Vec<AIOutputs> {
+        batched(
+            imgs,
+            self.dynamic_batch,
+            |img| imgbuf_to_input_array(1, 3, self.input_height, self.input_width, img, &self.input_format).0,
+            |inputs| self.run_batch(inputs),
+        )
+    }
+
+    fn run_batch(&self, inputs: &[Array<f32, Ix4>]) -> Vec<AIOutputs> {
+        let batch = stack_batch(inputs.iter().map(|input| input.view()));
+        let outputs = inference(&self.session, &batch, &self.input_name).unwrap();
         let output = extract_output(&outputs, &self.output_name);
+        (0..inputs.len())
+            .map(|i| self.postprocess(&batch_item(&output, i)))
+            .collect()
+    }
 
-        let mut probs: Vec<Prob> = process_class_output(None, &self.classes, &output);
+    fn postprocess(&self, output: &Array<f32, IxDyn>) -> AIOutputs {
+        let mut probs: Vec<Prob> = process_class_output(None, &self.classes, output);
         probs.logits_to_probs();
 
         if self.post_processing.contains(&PostProcessing::GeoFence) {
@@ -118,7 +133,7 @@ impl EfficientNetV2 {
             );
             apply_label_rollup(&mut probs, self.config.confidence_threshold);
         } else {
-            probs.retain(|p| p.prob >= self.config.confidence_threshold);
+            probs.retain(|p| p.prob >= self.config.min_threshold());
         }
 
         AIOutputs::Classification(probs)
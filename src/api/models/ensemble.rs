@@ -0,0 +1,49 @@
+use super::*;
+use crate::api::processing::ensemble::fuse_members;
+
+/// Several models run on the same input, with their outputs fused into one.
+/// Each member keeps its own session; `classes` is the union of theirs.
+pub struct Ensemble {
+    pub members: Vec<Model>,
+    pub classes: Vec<String>,
+    pub ensemble: EnsembleConfig,
+    pub config: ModelConfig,
+}
+
+impl Ensemble {
+    pub fn new(members: Vec<Model>, classes: Vec<String>, ensemble: EnsembleConfig, config: ModelConfig) -> Result<Self, Error> {
+        if members.is_empty() {
+            return Err(anyhow!("An ensemble needs at least one model"));
+        }
+        Ok(Self {
+            members,
+            classes,
+            ensemble,
+            config,
+        })
+    }
+
+    pub fn run(&self, input: &AIInput<'_>) -> AIOutputs {
+        let outputs = self.members.iter().map(|member| member.run(input)).collect();
+        fuse_members(outputs, &self.ensemble, &self.classes, self.config.nms_threshold)
+    }
+
+    /// Each member runs all of `imgs` in its own batches before fusing.
+    pub fn run_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Vec<AIOutputs> {
+        let mut per_member: Vec<_> = self.members.iter().map(|member| member.run_images(imgs).into_iter()).collect();
+        (0..imgs.len())
+            .map(|_| {
+                let outputs = per_member.iter_mut().filter_map(Iterator::next).collect();
+                fuse_members(outputs, &self.ensemble, &self.classes, self.config.nms_threshold)
+            })
+            .collect()
+    }
+
+    /// Sets the config of the ensemble and of every member.
+    pub fn set_config(&mut self, config: ModelConfig) {
+        for member in self.members.iter_mut() {
+            member.set_config(config.clone());
+        }
+        self.config = config;
+    }
+}
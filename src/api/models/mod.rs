@@ -2,6 +2,7 @@ pub mod batdetect2;
 pub mod clip;
 pub mod dinov3;
 pub mod efficientnet;
+pub mod ensemble;
 pub mod overhead;
 pub mod perch;
 pub mod resnet18;
@@ -9,14 +10,26 @@ pub mod yolo;
 use crate::api::models::batdetect2::BatDetect2;
 use crate::api::models::clip::Clip;
 use crate::api::models::dinov3::Dinov3;
+use crate::api::models::ensemble::Ensemble;
 use crate::api::models::overhead::Overhead;
 use crate::api::models::perch::PerchV2;
 use crate::api::models::resnet18::ResNet18;
-use super::{audio::AudioData, abstractions::*, bq::AIMetadata, processing::post::PostProcessing};
+use super::{
+    audio::AudioData,
+    abstractions::*,
+    bq::AIMetadata,
+    processing::{
+        post::PostProcessing,
+        pre::slice_image,
+        tiling::{merge_tiles, tile_grid},
+        tta::{augment, fuse, restore},
+    },
+};
 use anyhow::{anyhow, Error, Result};
 pub use efficientnet::EfficientNetV2;
 use image::{ImageBuffer, Rgb};
 use ort::session::Session;
+use rayon::prelude::*;
 pub use yolo::Yolo;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -50,6 +63,38 @@ impl From<&str> for Task {
     }
 }
 
+/// The model family an `architecture` name builds.
+#[derive(Clone, Copy)]
+enum Arch {
+    Yolo,
+    EfficientNetV2,
+    ResNet18,
+    PerchV2,
+    Clip,
+    Dinov3,
+    Overhead,
+    BatDetect2,
+}
+
+/// Every `architecture` name [`Model::new`] accepts, and what it builds.
+const ARCHITECTURES: &[(&str, Arch)] = &[
+    ("yolo", Arch::Yolo),
+    ("yolov10", Arch::Yolo),
+    ("yolov26", Arch::Yolo),
+    ("efficientnetv2", Arch::EfficientNetV2),
+    ("resnet18", Arch::ResNet18),
+    ("perch_v2", Arch::PerchV2),
+    ("perch", Arch::PerchV2),
+    ("perch2", Arch::PerchV2),
+    ("clip", Arch::Clip),
+    ("dinov3", Arch::Dinov3),
+    ("overhead", Arch::Overhead),
+    ("heatmap", Arch::Overhead),
+    ("owl", Arch::Overhead),
+    ("herdnet", Arch::Overhead),
+    ("batdetect2", Arch::BatDetect2),
+];
+
 // All supported architectures
 pub enum Model {
     EfficientNetV2(EfficientNetV2),
@@ -60,6 +105,7 @@ pub enum Model {
     Dinov3(Dinov3),
     Overhead(Overhead),
     BatDetect2(BatDetect2),
+    Ensemble(Ensemble),
 }
 
 pub enum AIInput<'a> {
@@ -68,6 +114,20 @@ pub enum AIInput<'a> {
 }
 
 impl Model {
+    pub fn config(&self) -> &ModelConfig {
+        match self {
+            Model::EfficientNetV2(inner) => &inner.config,
+            Model::Yolo(inner) => &inner.config,
+            Model::ResNet18(inner) => &inner.config,
+            Model::PerchV2(inner) => &inner.config,
+            Model::Clip(inner) => &inner.config,
+            Model::Dinov3(inner) => &inner.config,
+            Model::Overhead(inner) => &inner.config,
+            Model::BatDetect2(inner) => &inner.config,
+            Model::Ensemble(inner) => &inner.config,
+        }
+    }
+
     pub fn config_mut(&mut self) -> &mut ModelConfig {
         match self {
             Model::EfficientNetV2(inner) => &mut inner.config,
@@ -78,6 +138,15 @@ impl Model {
             Model::Dinov3(inner) => &mut inner.config,
             Model::Overhead(inner) => &mut inner.config,
             Model::BatDetect2(inner) => &mut inner.config,
+            Model::Ensemble(inner) => &mut inner.config,
+        }
+    }
+
+    /// Replaces the config; an ensemble passes it on to its members.
+    pub fn set_config(&mut self, config: ModelConfig) {
+        match self {
+            Model::Ensemble(inner) => inner.set_config(config),
+            _ => *self.config_mut() = config,
         }
     }
 }
@@ -89,22 +158,90 @@ impl Model {
         config: ModelConfig,
     ) -> Result<Self, Error> {
         let arch = metadata.architecture.to_lowercase();
-        match arch.as_str() {
-            "yolo" | "yolov10" | "yolov26" => {Ok(Model::Yolo(Yolo::new(metadata, &arch, session, config)?))}
-            "efficientnetv2" => Ok(Model::EfficientNetV2(EfficientNetV2::new(metadata, session, config)?)),
-            "resnet18" => Ok(Model::ResNet18(ResNet18::new(metadata, session, config)?)),
-            "perch_v2" | "perch" | "perch2" => Ok(Model::PerchV2(PerchV2::new(metadata, session, config)?)),
-            "clip" => Ok(Model::Clip(Clip::new(metadata, session, config)?)),
-            "dinov3" => Ok(Model::Dinov3(Dinov3::new(metadata, session, config)?)),
-            "overhead" | "heatmap" | "owl" | "herdnet" => {
-                Ok(Model::Overhead(Overhead::new(metadata, session, config)?))
-            }
-            "batdetect2" => Ok(Model::BatDetect2(BatDetect2::new(metadata, session, config)?)),
-            arch => Err(anyhow!("Unsupported model architecture: {}", arch)),
+        let Some(family) = Self::family(&arch) else {
+            return Err(anyhow!("Unsupported model architecture: {}", arch));
+        };
+        match family {
+            Arch::Yolo => Ok(Model::Yolo(Yolo::new(metadata, &arch, session, config)?)),
+            Arch::EfficientNetV2 => Ok(Model::EfficientNetV2(EfficientNetV2::new(metadata, session, config)?)),
+            Arch::ResNet18 => Ok(Model::ResNet18(ResNet18::new(metadata, session, config)?)),
+            Arch::PerchV2 => Ok(Model::PerchV2(PerchV2::new(metadata, session, config)?)),
+            Arch::Clip => Ok(Model::Clip(Clip::new(metadata, session, config)?)),
+            Arch::Dinov3 => Ok(Model::Dinov3(Dinov3::new(metadata, session, config)?)),
+            Arch::Overhead => Ok(Model::Overhead(Overhead::new(metadata, session, config)?)),
+            Arch::BatDetect2 => Ok(Model::BatDetect2(BatDetect2::new(metadata, session, config)?)),
+        }
+    }
+
+    fn family(arch: &str) -> Option<Arch> {
+        let arch = arch.to_lowercase();
+        ARCHITECTURES.iter().find(|(name, _)| *name == arch).map(|&(_, family)| family)
+    }
+
+    /// Whether [`Self::new`] accepts `arch`, in any case.
+    pub fn supports(arch: &str) -> bool {
+        Self::family(arch).is_some()
+    }
+
+    /// Every architecture name [`Self::new`] accepts.
+    pub fn architectures() -> impl Iterator<Item = &'static str> {
+        ARCHITECTURES.iter().map(|&(name, _)| name)
+    }
+
+    /// Runs `imgs` through the model, in batches when its batch dimension is
+    /// dynamic. Architectures without a batched path go one image at a time.
+    pub fn run_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Vec<AIOutputs> {
+        let mut outputs = match self.tiling() {
+            Some(tiling) => imgs.iter().map(|img| self.run_tiled(img, tiling)).collect(),
+            None => self.run_augmented_images(imgs),
+        };
+        for output in outputs.iter_mut() {
+            self.config().filter_output(output);
         }
+        outputs
     }
 
     pub fn run(&self, input: &AIInput<'_>) -> AIOutputs {
+        match input {
+            AIInput::Image(img) if self.tiling().is_some() || self.tta().is_some() => {
+                self.run_images(std::slice::from_ref(*img)).remove(0)
+            }
+            _ => {
+                let mut output = self.run_untiled(input);
+                self.config().filter_output(&mut output);
+                output
+            }
+        }
+    }
+
+    /// Like [`Self::run_untiled_images`], with test-time augmentation when
+    /// the config asks for it.
+    fn run_augmented_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Vec<AIOutputs> {
+        let Some(tta) = self.tta() else {
+            return self.run_untiled_images(imgs);
+        };
+        imgs.iter()
+            .map(|img| {
+                let (augs, copies): (Vec<_>, Vec<_>) = augment(img, tta).into_iter().unzip();
+                let outputs = self.run_untiled_images(&copies);
+                let restored = outputs.into_iter().zip(&augs).map(|(output, aug)| restore(output, aug)).collect();
+                fuse(restored, self.config().nms_threshold)
+            })
+            .collect()
+    }
+
+    fn run_untiled_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Vec<AIOutputs> {
+        match self {
+            Model::EfficientNetV2(m) => m.run_images(imgs),
+            Model::Yolo(m) => m.run_images(imgs),
+            Model::Clip(m) => m.run_images(imgs),
+            Model::Dinov3(m) => m.run_images(imgs),
+            Model::Ensemble(m) => m.run_images(imgs),
+            _ => imgs.iter().map(|img| self.run_untiled(&AIInput::Image(img))).collect(),
+        }
+    }
+
+    fn run_untiled(&self, input: &AIInput<'_>) -> AIOutputs {
         match (self, input) {
             (Model::EfficientNetV2(m), AIInput::Image(img)) => m.run_image(img),
             (Model::Yolo(m), AIInput::Image(img)) => m.run_image(img),
@@ -114,7 +251,56 @@ impl Model {
             (Model::Dinov3(m), AIInput::Image(img)) => m.run_image(img),
             (Model::Overhead(m), AIInput::Image(img)) => m.run_image(img),
             (Model::BatDetect2(m), AIInput::Audio(audio)) => m.run_audio(audio),
+            (Model::Ensemble(m), input) => m.run(input),
             _ => panic!("wrong input type for this model architecture"),
         }
     }
+
+    /// The tiling config, for models whose outputs can be merged across tiles.
+    fn tiling(&self) -> Option<&TileConfig> {
+        let tileable = match self {
+            Model::Yolo(m) => matches!(m.task, Task::Detect | Task::Segment),
+            Model::Overhead(_) => true,
+            _ => false,
+        };
+        self.config().tiling.as_ref().filter(|_| tileable)
+    }
+
+    /// The TTA config, for the architectures whose outputs [`fuse`] handles.
+    fn tta(&self) -> Option<&TtaConfig> {
+        let augmentable = match self {
+            Model::Yolo(m) => m.task != Task::Embed,
+            Model::EfficientNetV2(_) => true,
+            _ => false,
+        };
+        self.config().tta.as_ref().filter(|_| augmentable)
+    }
+
+    fn run_tiled(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>, tiling: &TileConfig) -> AIOutputs {
+        let (width, height) = img.dimensions();
+        let tiles = tile_grid(width, height, tiling);
+        if tiles.len() == 1 {
+            return self.run_augmented_images(std::slice::from_ref(img)).remove(0);
+        }
+        let crops: Vec<_> = tiles.par_iter().map(|tile| slice_image(img, tile)).collect();
+        let outputs = self.run_augmented_images(&crops);
+        merge_tiles(tiles.into_iter().zip(outputs), self.config().nms_threshold, tiling.point_distance)
+    }
+}
+
+/// Largest batch sent to a session in one call.
+pub const MAX_BATCH: usize = 16;
+
+/// Preprocesses `imgs` in parallel, then hands them to `run` in batches of
+/// up to [`MAX_BATCH`], or one by one when the batch dimension is fixed.
+/// `run` returns one output per input, in order.
+fn batched<I: Send>(
+    imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>],
+    dynamic_batch: bool,
+    preprocess: impl Fn(&ImageBuffer<Rgb<u8>, Vec<u8>>) -> I + Sync,
+    run: impl Fn(&[I]) -> Vec<AIOutputs>,
+) -> Vec<AIOutputs> {
+    let inputs: Vec<I> = imgs.par_iter().map(preprocess).collect();
+    let size = if dynamic_batch { MAX_BATCH } else { 1 };
+    inputs.chunks(size).flat_map(run).collect()
 }
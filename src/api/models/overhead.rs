@@ -80,7 +80,7 @@ impl Overhead {
 
         // Detection score is the heatmap value; the frontend confidence slider
         // filters on it via `config`.
-        let conf_thr = self.config.confidence_threshold;
+        let conf_thr = self.config.min_threshold();
 
         let scale_x = img_w as f32 / loc_w as f32;
         let scale_y = img_h as f32 / loc_h as f32;
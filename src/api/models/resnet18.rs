@@ -155,7 +155,7 @@ impl ResNet18 {
             let prediction = if self.post_processing.contains(&PostProcessing::BinaryClassification) {
                 let logit = output[[0, j]];
                 let p_pos = 1.0 / (1.0 + (-logit).exp());
-                let (class_id, prob) = if p_pos >= self.config.confidence_threshold {
+                let (class_id, prob) = if p_pos >= self.config.threshold(&self.classes[1]) {
                     (1u32, p_pos)
                 } else {
                     (0u32, 1.0 - p_pos)
@@ -2,14 +2,14 @@ use super::*;
 use crate::api::{
     abstractions::{AIOutputs, XYXY},
     processing::{
-        inference::inference,
+        inference::{has_dynamic_batch, inference},
         post::*,
-        pre::{imgbuf_to_input_array, TensorFormat},
+        pre::{imgbuf_to_input_array, stack_batch, TensorFormat},
     },
 };
 use anyhow::{bail, Error, Result};
 use image::{ImageBuffer, Rgb};
-use ndarray::{s, Array, Array2, Axis, IxDyn};
+use ndarray::{s, Array, Array2, Axis, Ix4, IxDyn};
 use ort::{session::Session, value::ValueType};
 
 enum YoloType {
@@ -32,6 +32,7 @@ pub struct Yolo {
     pub post_processing: Vec<PostProcessing>,
     pub session: Session,
     pub config: ModelConfig,
+    pub dynamic_batch: bool,
     yolotype: YoloType,
 }
 
@@ -56,7 +57,7 @@ impl Yolo {
                     .map(|(index, &value)| (index, value))
                     .reduce(|a, b| if b.1 > a.1 { b } else { a })?;
 
-                if prob < self.config.confidence_threshold {
+                if prob < self.config.min_threshold() {
                     return None;
                 }
 
@@ -74,10 +75,10 @@ impl Yolo {
             })
             .collect();
 
-        for technique in &self.post_processing {
-            if matches!(technique, PostProcessing::NMS) {
-                let indices = nms_indices(&boxes, self.config.nms_threshold, true);
-                boxes = indices.iter().map(|&idx| boxes[idx]).collect();
+        for &technique in &self.post_processing {
+            let merged = merge_boxes(&boxes, technique, self.config.nms_threshold, self.config.min_threshold(), true);
+            if let Some(merged) = merged {
+                boxes = merged.into_iter().map(|(_, xyxy)| xyxy).collect();
             }
         }
 
@@ -98,7 +99,7 @@ impl Yolo {
             .filter_map(|row| {
                 let row: Vec<f32> = row.iter().copied().collect();
                 let prob = row[4 as usize];
-                if prob < self.config.confidence_threshold {
+                if prob < self.config.min_threshold() {
                     return None;
                 }
                 let fields: Vec<f32> = row[5 as usize..].to_vec();
@@ -124,10 +125,10 @@ impl Yolo {
             })
             .collect();
 
-        for technique in &self.post_processing {
-            if matches!(technique, PostProcessing::NMS) {
-                let indices = nms_indices(&boxes, self.config.nms_threshold, false);
-                boxes = indices.iter().map(|&idx| boxes[idx]).collect();
+        for &technique in &self.post_processing {
+            let merged = merge_boxes(&boxes, technique, self.config.nms_threshold, self.config.min_threshold(), false);
+            if let Some(merged) = merged {
+                boxes = merged.into_iter().map(|(_, xyxy)| xyxy).collect();
             }
         }
 
@@ -148,7 +149,7 @@ impl Yolo {
             .axis_iter(Axis(1))
             .filter_map(|row| {
                 let prob = row[4 as usize];
-                if prob < self.config.confidence_threshold {
+                if prob < self.config.min_threshold() {
                     return None;
                 }
                 let x1 = row[0 as usize] * x_scale;
@@ -203,7 +204,7 @@ impl Yolo {
         let y_scale = img_height as f32 / self.input_height as f32;
 
         // Process all detections with iterator chain
-        let (mut segmentations, bounding_boxes): (Vec<SEGc>, Vec<XYXY>) = bbox_and_scores
+        let (mut segmentations, mut bounding_boxes): (Vec<SEGc>, Vec<XYXY>) = bbox_and_scores
             .axis_iter(Axis(0))
             .enumerate()
             .filter_map(|(index, row)| {
@@ -216,7 +217,7 @@ impl Yolo {
                     .map(|(i, &v)| (i, v))
                     .reduce(|acc, val| if val.1 > acc.1 { val } else { acc })
                     .unwrap();
-                if score < self.config.confidence_threshold {
+                if score < self.config.min_threshold() {
                     return None;
                 }
 
@@ -252,14 +253,19 @@ impl Yolo {
             })
             .unzip();
 
-        for technique in &self.post_processing {
-            if matches!(technique, PostProcessing::NMS) {
-                let keep_indices: Vec<usize> =
-                    nms_indices(&bounding_boxes, self.config.nms_threshold, true);
-                segmentations = keep_indices
+        for &technique in &self.post_processing {
+            let merged = merge_boxes(&bounding_boxes, technique, self.config.nms_threshold, self.config.min_threshold(), true);
+            if let Some(merged) = merged {
+                // Fused boxes keep their strongest member's mask, stretched to the new box.
+                segmentations = merged
                     .iter()
-                    .map(|&i| segmentations[i].clone()) // use `.clone()` if needed, depending on the type
+                    .map(|&(i, xyxy)| {
+                        let mut seg = segmentations[i].clone();
+                        seg.bbox.xyxy = xyxy;
+                        seg
+                    })
                     .collect();
+                bounding_boxes = merged.into_iter().map(|(_, xyxy)| xyxy).collect();
             }
         }
 
@@ -326,6 +332,8 @@ impl Yolo {
             (0, 0, 0)
         };
 
+        let dynamic_batch = has_dynamic_batch(&session);
+
         Ok(Yolo {
             classes: metadata.classes,
             input_width,
@@ -339,6 +347,7 @@ impl Yolo {
             post_processing: metadata.post_processing,
             session,
             config,
+            dynamic_batch,
             yolotype,
         })
     }
@@ -346,38 +355,50 @@ impl Yolo {
 
 impl Yolo {
     pub fn run_image(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> AIOutputs {
-        let (input, img_width, img_height) = imgbuf_to_input_array(
-            1,
-            3,
-            self.input_height,
-            self.input_width,
-            img,
-            &TensorFormat::NCHW,
-        );
-        let outputs = inference(&self.session, &input, "images").unwrap();
-        match self.task {
-            Task::Detect => {
-                let output = extract_output(&outputs, "output0");
-                let boxes = match self.yolotype {
-                    YoloType::Yolov8plus => self.process_detect_output(&output, img_width, img_height),
-                    YoloType::Yolov5 => self.process_detect_output_yolov5(&output, img_width, img_height),
-                    YoloType::Yolov10 | YoloType::Yolov26 => {self.process_detect_output_end2end(&output, img_width, img_height)}
-                };
-                return AIOutputs::ObjectDetection(boxes);
-            }
-            Task::Classify => {
-                let output = extract_output(&outputs, "output0");
-                let probs =
-                    process_class_output(Some(self.config.confidence_threshold), &self.classes, &output);
-                return AIOutputs::Classification(probs);
-            }
-            Task::Segment => {
-                let output0 = extract_output(&outputs, "output0");
-                let output1 = extract_output(&outputs, "output1");
-                let segc_vec = self.process_seg_output((output0, output1), img_width, img_height);
-                return AIOutputs::Segmentation(segc_vec);
-            }
-            Task::Embed => unreachable!("Yolo does not support Task::Embed"),
-        }
+        self.run_images(std::slice::from_ref(img)).remove(0)
+    }
+
+    pub fn run_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Vec<AIOutputs> {
+        batched(
+            imgs,
+            self.dynamic_batch,
+            |img| imgbuf_to_input_array(1, 3, self.input_height, self.input_width, img, &TensorFormat::NCHW),
+            |inputs| self.run_batch(inputs),
+        )
+    }
+
+    fn run_batch(&self, inputs: &[(Array<f32, Ix4>, u32, u32)]) -> Vec<AIOutputs> {
+        let batch = stack_batch(inputs.iter().map(|(input, _, _)| input.view()));
+        let outputs = inference(&self.session, &batch, "images").unwrap();
+        let output0 = extract_output(&outputs, "output0");
+        let output1 = (self.task == Task::Segment).then(|| extract_output(&outputs, "output1"));
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, img_width, img_height))| {
+                let output = batch_item(&output0, i);
+                match self.task {
+                    Task::Detect => {
+                        let boxes = match self.yolotype {
+                            YoloType::Yolov8plus => self.process_detect_output(&output, img_width, img_height),
+                            YoloType::Yolov5 => self.process_detect_output_yolov5(&output, img_width, img_height),
+                            YoloType::Yolov10 | YoloType::Yolov26 => {self.process_detect_output_end2end(&output, img_width, img_height)}
+                        };
+                        AIOutputs::ObjectDetection(boxes)
+                    }
+                    Task::Classify => {
+                        let probs =
+                            process_class_output(Some(self.config.min_threshold()), &self.classes, &output);
+                        AIOutputs::Classification(probs)
+                    }
+                    Task::Segment => {
+                        let protos = batch_item(output1.as_ref().expect("segment models have output1"), i);
+                        let segc_vec = self.process_seg_output((output, protos), img_width, img_height);
+                        AIOutputs::Segmentation(segc_vec)
+                    }
+                    Task::Embed => unreachable!("Yolo does not support Task::Embed"),
+                }
+            })
+            .collect()
     }
 }
@@ -0,0 +1,345 @@
+use super::abstractions::*;
+use super::bq::{Ep, GlobalBQ};
+use super::processing::pre::slice_image;
+use super::registry::{ModelHandle, ModelRegistry};
+use anyhow::{bail, Context, Result};
+use image::{ImageBuffer, Rgb};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// A pipeline file, TOML or JSON: an ordered list of stages.
+///
+/// ```toml
+/// [[stages]]
+/// model = "MDV6-yolov9-c"
+/// confidence_threshold = 0.3
+/// exclude_classes = ["person"]
+/// class_thresholds = { vehicle = 0.6 }
+///
+/// [stages.tiling]
+/// size = 1024
+///
+/// [[stages]]
+/// model = "speciesnet"
+/// labels = ["animal"]
+/// padding = 0.1
+/// min_size = 32
+///
+/// [stages.tta]
+/// scales = [1.0, 1.2]
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PipelineConfig {
+    pub stages: Vec<StageConfig>,
+}
+
+/// One model in a pipeline. The first stage sees the whole image and ignores
+/// the crop options; later stages run on each box or segment it found:
+/// classifiers fill `extra_cls`, embedders fill `embedding`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct StageConfig {
+    /// Name in `models/`, or a path to a `.bq` relative to the pipeline file.
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence_threshold: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nms_threshold: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_fence: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub class_thresholds: BTreeMap<String, f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include_classes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude_classes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tiling: Option<TileConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tta: Option<TtaConfig>,
+    /// Run these models together instead; `model` then only names the stage:
+    ///
+    /// ```toml
+    /// [[stages]]
+    /// model = "regional"
+    /// ensemble = { models = ["amazonia", "andes"], fusion = "vote" }
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ensemble: Option<EnsembleConfig>,
+    #[serde(flatten)]
+    pub crop: CropOptions,
+}
+
+/// Which detections a later stage runs on, and how their crops are cut.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct CropOptions {
+    /// Only crops whose label is listed; empty means every crop.
+    pub labels: Vec<String>,
+    /// Margin added on each side of a crop, as a fraction of its size.
+    pub padding: f32,
+    /// Crops narrower or shorter than this, in pixels, are skipped.
+    pub min_size: u32,
+}
+
+impl CropOptions {
+    pub fn accepts(&self, det: &XYXYc) -> bool {
+        let size = self.min_size.max(1) as f32;
+        (self.labels.is_empty() || self.labels.contains(&det.label))
+            && det.xyxy.x2 - det.xyxy.x1 >= size
+            && det.xyxy.y2 - det.xyxy.y1 >= size
+    }
+
+    /// `xyxy` grown by `padding` on every side, kept inside `width`×`height`.
+    pub fn crop_box(&self, xyxy: &XYXY, width: u32, height: u32) -> XYXY {
+        let pad_x = (xyxy.x2 - xyxy.x1) * self.padding;
+        let pad_y = (xyxy.y2 - xyxy.y1) * self.padding;
+        XYXY::new(
+            (xyxy.x1 - pad_x).max(0.0),
+            (xyxy.y1 - pad_y).max(0.0),
+            (xyxy.x2 + pad_x).min(width as f32),
+            (xyxy.y2 + pad_y).min(height as f32),
+            xyxy.prob,
+            xyxy.class_id,
+        )
+    }
+}
+
+impl StageConfig {
+    pub fn model_config(&self) -> ModelConfig {
+        let default = ModelConfig::default();
+        ModelConfig {
+            confidence_threshold: self.confidence_threshold.unwrap_or(default.confidence_threshold),
+            nms_threshold: self.nms_threshold.unwrap_or(default.nms_threshold),
+            geo_fence: self.geo_fence.clone().unwrap_or(default.geo_fence),
+            tiling: self.tiling.clone(),
+            tta: self.tta.clone(),
+            class_thresholds: self.class_thresholds.clone(),
+            include_classes: self.include_classes.clone(),
+            exclude_classes: self.exclude_classes.clone(),
+        }
+    }
+
+    /// Loads the stage's model, or its ensemble, into the registry as `name`.
+    fn load(&self, name: &str, base_dir: &Path, ep: Ep) -> Result<ModelHandle> {
+        match &self.ensemble {
+            Some(ensemble) => {
+                let paths: Vec<PathBuf> = ensemble.models.iter().map(|model| model_path(model, base_dir)).collect();
+                ModelRegistry::load_ensemble(name, &paths, ensemble.clone(), ep, Some(self.model_config()))
+            }
+            None => {
+                let path = model_path(&self.model, base_dir);
+                ModelRegistry::load(name, &path, ep, Some(self.model_config()))
+                    .with_context(|| format!("failed to load {}", path.display()))
+            }
+        }
+    }
+}
+
+/// `.bq` file for `model`, looked up next to the pipeline file first.
+fn model_path(model: &str, base_dir: &Path) -> PathBuf {
+    let local = base_dir.join(model);
+    if model.ends_with(".bq") && local.is_file() {
+        return local;
+    }
+    let name = model.strip_suffix(".bq").unwrap_or(model);
+    PathBuf::from(format!("models/{}.bq", name))
+}
+
+impl PipelineConfig {
+    /// True for paths a pipeline is read from, so a CLI model argument can
+    /// name either a model or a pipeline.
+    pub fn is_pipeline_path(path: impl AsRef<Path>) -> bool {
+        matches!(
+            path.as_ref().extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref(),
+            Some("toml" | "json")
+        )
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        toml::from_str::<Self>(text)?.validated()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        serde_json::from_str::<Self>(text)?.validated()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let is_toml = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("toml"));
+        let config = if is_toml { Self::from_toml(&text) } else { Self::from_json(&text) };
+        config.with_context(|| format!("Invalid pipeline {}", path.display()))
+    }
+
+    fn validated(self) -> Result<Self> {
+        if self.stages.is_empty() {
+            bail!("A pipeline needs at least one stage");
+        }
+        if let Some(i) = self.stages.iter().position(|s| s.model.trim().is_empty()) {
+            bail!("Stage {} has no model", i + 1);
+        }
+        if let Some(i) = self.stages.iter().position(|s| s.ensemble.as_ref().is_some_and(|e| e.models.is_empty())) {
+            bail!("Stage {} has an empty ensemble", i + 1);
+        }
+        Ok(self)
+    }
+}
+
+impl ModelStamp {
+    /// The stamp of a stage that crops with `crop`. Default options are left
+    /// out, so the classic model pair stamps as it always has.
+    pub fn with_crop(mut self, crop: &CropOptions) -> Self {
+        self.crop = (*crop != CropOptions::default()).then(|| crop.clone());
+        self
+    }
+}
+
+/// A loaded stage: its model plus the crop options from [`StageConfig`].
+#[derive(Clone)]
+pub struct Stage {
+    pub model: ModelHandle,
+    pub crop: CropOptions,
+}
+
+impl Stage {
+    pub fn new(model: ModelHandle) -> Self {
+        Self {
+            model,
+            crop: CropOptions::default(),
+        }
+    }
+}
+
+/// The boxes later stages crop from: detections, or the bounding boxes of
+/// segments.
+fn detections_mut(outputs: &mut AIOutputs) -> Vec<&mut XYXYc> {
+    match outputs {
+        AIOutputs::ObjectDetection(dets) => dets.iter_mut().collect(),
+        AIOutputs::Segmentation(segs) => segs.iter_mut().map(|segc| &mut segc.bbox).collect(),
+        _ => Vec::new(),
+    }
+}
+
+static ACTIVE: RwLock<Option<Pipeline>> = RwLock::new(None);
+
+/// Stages run in order on one image. Always holds at least one stage.
+#[derive(Clone)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// The classic pair: `model` on the image, `cls` on every crop.
+    pub fn from_models(model: ModelHandle, cls: Option<ModelHandle>) -> Self {
+        let mut stages = vec![Stage::new(model)];
+        stages.extend(cls.map(Stage::new));
+        Self { stages }
+    }
+
+    /// Loads every stage's model into the registry as `pipeline<N>/<model>`.
+    pub fn load(config: &PipelineConfig, base_dir: &Path, ep: Ep) -> Result<Self> {
+        let stages = config
+            .stages
+            .iter()
+            .enumerate()
+            .map(|(i, stage)| {
+                let name = format!("pipeline{}/{}", i + 1, stage.model);
+                let model = stage
+                    .load(&name, base_dir, ep)
+                    .with_context(|| format!("Stage {}", i + 1))?;
+                Ok(Stage {
+                    model,
+                    crop: stage.crop.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { stages })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, ep: Ep) -> Result<Self> {
+        let path = path.as_ref();
+        let config = PipelineConfig::from_file(path)?;
+        Self::load(&config, path.parent().unwrap_or(Path::new(".")), ep)
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// The first stage's model, the one that sees the whole input.
+    pub fn model(&self) -> &ModelHandle {
+        &self.stages[0].model
+    }
+
+    pub fn run(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Result<AIOutputs> {
+        let mut outputs = self.run_images(std::slice::from_ref(img))?;
+        outputs.pop().context("model returned no output")
+    }
+
+    /// Like [`run`](Self::run) on each image. The first stage runs the images
+    /// as a batch; each later stage gets the crops of all of them at once.
+    pub fn run_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Result<Vec<AIOutputs>> {
+        let mut outputs = self.model().run_images(imgs)?;
+        for stage in &self.stages[1..] {
+            let mut targets: Vec<&mut XYXYc> = Vec::new();
+            let mut crops = Vec::new();
+            for (img, output) in imgs.iter().zip(outputs.iter_mut()) {
+                let (width, height) = img.dimensions();
+                for det in detections_mut(output).into_iter().filter(|det| stage.crop.accepts(det)) {
+                    crops.push(slice_image(img, &stage.crop.crop_box(&det.xyxy, width, height)));
+                    targets.push(det);
+                }
+            }
+            if crops.is_empty() {
+                continue;
+            }
+            for (det, output) in targets.into_iter().zip(stage.model.run_images(&crops)?) {
+                match output {
+                    AIOutputs::Classification(probs) => det.extra_cls = Some(probs),
+                    AIOutputs::Embed(embedding) => det.embedding = Some(embedding),
+                    _ => {}
+                }
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Provenance of the first stage, with each later stage chained through
+    /// `model_cls`.
+    pub fn provenance(&self) -> Provenance {
+        let mut provenance = self.model().provenance();
+        provenance.stamp.model_cls = self.stages[1..].iter().rev().fold(None, |next, stage| {
+            let mut stamp = stage.model.stamp().with_crop(&stage.crop);
+            stamp.model_cls = next;
+            Some(Box::new(stamp))
+        });
+        provenance
+    }
+
+    /// Makes `pipeline` the one [`process_imgbuf`](super::bq::process_imgbuf)
+    /// uses instead of the `GlobalBQ` pair. Models of the pipeline it replaces
+    /// are unloaded.
+    pub fn set_active(pipeline: Option<Pipeline>) {
+        let mut active = ACTIVE.write().unwrap();
+        let previous = std::mem::replace(&mut *active, pipeline);
+        let kept: Vec<&ModelHandle> = active.iter().flat_map(|p| &p.stages).map(|s| &s.model).collect();
+        for stage in previous.iter().flat_map(|p| &p.stages) {
+            if !kept.iter().any(|model| model.same(&stage.model)) {
+                ModelRegistry::unload_handle(&stage.model);
+            }
+        }
+    }
+
+    pub fn active() -> Option<Pipeline> {
+        ACTIVE.read().ok()?.clone()
+    }
+
+    /// The active pipeline, else the `GlobalBQ` pair.
+    pub fn current() -> Option<Pipeline> {
+        Self::active().or_else(|| Some(Self::from_models(GlobalBQ::First.handle()?, GlobalBQ::Second.handle())))
+    }
+}
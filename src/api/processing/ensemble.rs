@@ -0,0 +1,154 @@
+use crate::api::abstractions::{AIOutputs, AudioProb, EnsembleConfig, Fusion, Prob, XYXY};
+use crate::api::processing::post::{nms_indices, weighted_box_fusion};
+use std::collections::HashMap;
+
+/// Combines one output per ensemble member into one. Class ids are first
+/// mapped to positions in `classes`, the ensemble's merged class list, so
+/// members trained on different lists agree on them.
+pub fn fuse_members(mut outputs: Vec<AIOutputs>, config: &EnsembleConfig, classes: &[String], nms_threshold: f32) -> AIOutputs {
+    outputs.iter_mut().for_each(|output| unify_class_ids(output, classes));
+    let n = outputs.len();
+    match outputs.first() {
+        Some(AIOutputs::Classification(_)) => {
+            let members: Vec<Vec<Prob>> = outputs
+                .into_iter()
+                .filter_map(|output| match output {
+                    AIOutputs::Classification(probs) => Some(probs),
+                    _ => None,
+                })
+                .collect();
+            AIOutputs::Classification(fuse_probs(&members, config.fusion))
+        }
+        Some(AIOutputs::AudioClassification(_)) => {
+            let members: Vec<Vec<AudioProb>> = outputs
+                .into_iter()
+                .filter_map(|output| match output {
+                    AIOutputs::AudioClassification(windows) => Some(windows),
+                    _ => None,
+                })
+                .collect();
+            AIOutputs::AudioClassification(fuse_audio(&members, config.fusion, config.window))
+        }
+        Some(AIOutputs::ObjectDetection(_)) => {
+            let dets: Vec<_> = outputs
+                .into_iter()
+                .flat_map(|output| match output {
+                    AIOutputs::ObjectDetection(dets) => dets,
+                    _ => Vec::new(),
+                })
+                .collect();
+            let boxes: Vec<XYXY> = dets.iter().map(|det| det.xyxy).collect();
+            let fused = weighted_box_fusion(&boxes, nms_threshold, n);
+            AIOutputs::ObjectDetection(
+                fused
+                    .into_iter()
+                    .map(|(i, xyxy)| {
+                        let mut det = dets[i].clone();
+                        det.xyxy = xyxy;
+                        det
+                    })
+                    .collect(),
+            )
+        }
+        Some(AIOutputs::Segmentation(_)) => {
+            let segs: Vec<_> = outputs
+                .into_iter()
+                .flat_map(|output| match output {
+                    AIOutputs::Segmentation(segs) => segs,
+                    _ => Vec::new(),
+                })
+                .collect();
+            let boxes: Vec<XYXY> = segs.iter().map(|seg| seg.bbox.xyxy).collect();
+            let keep = nms_indices(&boxes, nms_threshold, true);
+            AIOutputs::Segmentation(keep.into_iter().map(|i| segs[i].clone()).collect())
+        }
+        // Points and embeddings can't be combined; the first member's stand.
+        _ => outputs.into_iter().next().unwrap_or(AIOutputs::Classification(Vec::new())),
+    }
+}
+
+fn unify_class_ids(output: &mut AIOutputs, classes: &[String]) {
+    let id = |label: &str, fallback: u32| classes.iter().position(|c| c == label).map_or(fallback, |i| i as u32);
+    match output {
+        AIOutputs::Classification(probs) => probs.iter_mut().for_each(|p| p.class_id = id(&p.label, p.class_id)),
+        AIOutputs::AudioClassification(windows) => windows
+            .iter_mut()
+            .for_each(|w| w.prediction.class_id = id(&w.prediction.label, w.prediction.class_id)),
+        AIOutputs::ObjectDetection(dets) => dets.iter_mut().for_each(|det| det.xyxy.class_id = id(&det.label, det.xyxy.class_id)),
+        AIOutputs::Segmentation(segs) => segs
+            .iter_mut()
+            .for_each(|seg| seg.bbox.xyxy.class_id = id(&seg.bbox.label, seg.bbox.xyxy.class_id)),
+        AIOutputs::PointDetection(points) => points.iter_mut().for_each(|p| p.xy.class_id = id(&p.label, p.xy.class_id)),
+        AIOutputs::Embed(_) => {}
+    }
+}
+
+/// One probability per class any member reported, highest first.
+pub fn fuse_probs(members: &[Vec<Prob>], fusion: Fusion) -> Vec<Prob> {
+    let n = members.len().max(1) as f32;
+    let mut fused: HashMap<&str, (u32, f32)> = HashMap::new();
+    for probs in members {
+        match fusion {
+            Fusion::Mean => probs
+                .iter()
+                .for_each(|p| fused.entry(&p.label).or_insert((p.class_id, 0.0)).1 += p.prob / n),
+            Fusion::Max => probs.iter().for_each(|p| {
+                let entry = fused.entry(&p.label).or_insert((p.class_id, 0.0));
+                entry.1 = entry.1.max(p.prob);
+            }),
+            Fusion::Vote => {
+                if let Some(top) = probs.iter().max_by(|a, b| a.prob.total_cmp(&b.prob)) {
+                    fused.entry(&top.label).or_insert((top.class_id, 0.0)).1 += 1.0 / n;
+                }
+            }
+        }
+    }
+    let mut fused: Vec<Prob> = fused
+        .into_iter()
+        .map(|(label, (class_id, prob))| Prob::new(label.to_owned(), prob, class_id))
+        .collect();
+    fused.sort_by(|a, b| b.prob.total_cmp(&a.prob).then_with(|| a.class_id.cmp(&b.class_id)));
+    fused
+}
+
+/// Members' audio predictions, likely on different window lengths, put on a
+/// common grid of `window`-second windows. In each, a member's probability
+/// for a class is the overlap-weighted mean over its windows there (windows
+/// that predicted another class count as 0); the members are then fused as
+/// in [`fuse_probs`] and the top class kept.
+pub fn fuse_audio(members: &[Vec<AudioProb>], fusion: Fusion, window: f32) -> Vec<AudioProb> {
+    let window = if window > 0.0 { window } else { 3.0 };
+    let end = members.iter().flatten().map(|w| w.end).fold(0.0f32, f32::max);
+    let n_windows = (end / window).ceil() as usize;
+
+    (0..n_windows)
+        .filter_map(|k| {
+            let (start, stop) = (k as f32 * window, ((k + 1) as f32 * window).min(end));
+            let per_member: Vec<Vec<Prob>> = members
+                .iter()
+                .map(|windows| {
+                    let overlapping: Vec<(f32, &Prob)> = windows
+                        .iter()
+                        .map(|w| (w.end.min(stop) - w.start.max(start), &w.prediction))
+                        .filter(|(overlap, _)| *overlap > 0.0)
+                        .collect();
+                    let total: f32 = overlapping.iter().map(|(overlap, _)| overlap).sum();
+                    let mut probs: HashMap<&str, (u32, f32)> = HashMap::new();
+                    for (overlap, p) in overlapping {
+                        probs.entry(&p.label).or_insert((p.class_id, 0.0)).1 += p.prob * overlap / total;
+                    }
+                    probs
+                        .into_iter()
+                        .map(|(label, (class_id, prob))| Prob::new(label.to_owned(), prob, class_id))
+                        .collect()
+                })
+                .collect();
+            let top = fuse_probs(&per_member, fusion).into_iter().next()?;
+            Some(AudioProb {
+                start,
+                end: stop,
+                prediction: top,
+            })
+        })
+        .collect()
+}
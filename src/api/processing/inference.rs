@@ -11,3 +11,11 @@ pub fn inference<'a>(
     let input = ort::value::TensorRef::from_array_view(input.view()).map_err(ort_err)?;
     Ok(session.run(ort::inputs![input_name => input]).map_err(ort_err)?)
 }
+
+/// True when the session's first input accepts any batch size.
+pub fn has_dynamic_batch(session: &ort::session::Session) -> bool {
+    match session.inputs()[0].dtype() {
+        ort::value::ValueType::Tensor { shape, .. } => shape.first().is_some_and(|&d| d < 0),
+        _ => false,
+    }
+}
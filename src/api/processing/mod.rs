@@ -1,3 +1,6 @@
+pub mod ensemble;
 pub mod inference;
 pub mod post;
 pub mod pre;
+pub mod tiling;
+pub mod tta;
@@ -1,6 +1,6 @@
-use crate::api::abstractions::{BitMatrix, Prob, XYXY};
+use crate::api::abstractions::{BitMatrix, Prob, XY, XYXY};
 use bitvec::vec::BitVec;
-use ndarray::{Array, Array2, ArrayBase, Dim, IxDyn, IxDynImpl, OwnedRepr};
+use ndarray::{Array, Array2, ArrayBase, Axis, Dim, IxDyn, IxDynImpl, OwnedRepr};
 use ort::session::SessionOutputs;
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -8,6 +8,8 @@ use std::collections::HashMap;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PostProcessing {
     NMS,
+    SoftNMS(SoftNmsMethod),
+    WBF,
     GeoFence,
     Rollup,
     Ensemble,
@@ -21,6 +23,9 @@ impl From<&str> for PostProcessing {
     fn from(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "nms" => PostProcessing::NMS,
+            "soft_nms" | "soft-nms" | "softnms" | "soft_nms_linear" => PostProcessing::SoftNMS(SoftNmsMethod::Linear),
+            "soft_nms_gaussian" | "soft-nms-gaussian" => PostProcessing::SoftNMS(SoftNmsMethod::Gaussian),
+            "wbf" | "weighted_box_fusion" => PostProcessing::WBF,
             "rollup" => PostProcessing::Rollup,
             "geofence" | "geo_fence" | "geo-fence" => PostProcessing::GeoFence,
             "ensemble" | "ensemble_classification" => PostProcessing::Ensemble,
@@ -32,6 +37,44 @@ impl From<&str> for PostProcessing {
     }
 }
 
+impl PostProcessing {
+    /// Whether this technique thins out overlapping boxes, i.e. uses the
+    /// overlap threshold.
+    pub fn merges_boxes(&self) -> bool {
+        matches!(self, PostProcessing::NMS | PostProcessing::SoftNMS(_) | PostProcessing::WBF)
+    }
+}
+
+/// How Soft-NMS lowers the score of a box overlapping a stronger one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftNmsMethod {
+    /// `score · (1 − IoU)`, only above the IoU threshold.
+    Linear,
+    /// `score · exp(−IoU² / σ)`, for every overlap.
+    Gaussian,
+}
+
+/// σ of [`SoftNmsMethod::Gaussian`].
+pub const SOFT_NMS_SIGMA: f32 = 0.5;
+
+/// Applies one box-merging `technique` (see [`PostProcessing::merges_boxes`])
+/// and returns, per surviving box, the index of the box it came from and its
+/// new coordinates and score. `None` for other techniques.
+pub fn merge_boxes(
+    boxes: &[XYXY],
+    technique: PostProcessing,
+    iou_threshold: f32,
+    score_threshold: f32,
+    per_class: bool,
+) -> Option<Vec<(usize, XYXY)>> {
+    match technique {
+        PostProcessing::NMS => Some(nms_indices(boxes, iou_threshold, per_class).into_iter().map(|i| (i, boxes[i])).collect()),
+        PostProcessing::SoftNMS(method) => Some(soft_nms(boxes, iou_threshold, score_threshold, method, per_class)),
+        PostProcessing::WBF => Some(weighted_box_fusion(boxes, iou_threshold, 1)),
+        _ => None,
+    }
+}
+
 pub fn nms_indices(boxes: &[XYXY], iou_threshold: f32, per_class: bool) -> Vec<usize> {
     let mut indices: Vec<usize> = (0..boxes.len()).collect();
     indices.sort_by(|&a, &b| {
@@ -63,6 +106,131 @@ pub fn nms_indices(boxes: &[XYXY], iou_threshold: f32, per_class: bool) -> Vec<u
     keep
 }
 
+/// Soft-NMS: instead of dropping boxes that overlap a stronger one, lowers
+/// their score and drops them only once it falls below `score_threshold`, so
+/// animals standing close together survive. Returns the kept boxes'
+/// indices with their decayed scores, strongest first.
+pub fn soft_nms(
+    boxes: &[XYXY],
+    iou_threshold: f32,
+    score_threshold: f32,
+    method: SoftNmsMethod,
+    per_class: bool,
+) -> Vec<(usize, XYXY)> {
+    let mut pending: Vec<(usize, XYXY)> = boxes.iter().copied().enumerate().collect();
+    let mut keep = Vec::new();
+
+    while let Some(best) = pending
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.1.prob.total_cmp(&b.1.prob))
+        .map(|(pos, _)| pos)
+    {
+        let (idx, current) = pending.swap_remove(best);
+        keep.push((idx, current));
+
+        for (_, other) in pending.iter_mut() {
+            if per_class && other.class_id != current.class_id {
+                continue;
+            }
+            let iou = other.iou(&current);
+            other.prob *= match method {
+                SoftNmsMethod::Linear if iou > iou_threshold => 1.0 - iou,
+                SoftNmsMethod::Linear => 1.0,
+                SoftNmsMethod::Gaussian => (-iou * iou / SOFT_NMS_SIGMA).exp(),
+            };
+        }
+        pending.retain(|(_, other)| other.prob >= score_threshold);
+    }
+
+    keep
+}
+
+/// Weighted box fusion: overlapping boxes of one class (IoU above
+/// `iou_threshold` with a cluster's fused box) are averaged, weighted by
+/// their scores, instead of all but one being dropped. The fused score is the
+/// cluster's mean, scaled down when fewer than `n_sources` predictions (models
+/// or augmentations) agree on it. Returns the strongest member of each cluster
+/// with its fused box, strongest cluster first.
+pub fn weighted_box_fusion(boxes: &[XYXY], iou_threshold: f32, n_sources: usize) -> Vec<(usize, XYXY)> {
+    let mut indices: Vec<usize> = (0..boxes.len()).collect();
+    indices.sort_by(|&a, &b| {
+        boxes[b]
+            .prob
+            .partial_cmp(&boxes[a].prob)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    // (strongest member, fused box, members)
+    let mut clusters: Vec<(usize, XYXY, Vec<usize>)> = Vec::new();
+    for idx in indices {
+        let b = &boxes[idx];
+        let matched = clusters
+            .iter_mut()
+            .filter(|(_, fused, _)| fused.class_id == b.class_id)
+            .map(|cluster| (cluster.1.iou(b), cluster))
+            .filter(|(iou, _)| *iou > iou_threshold)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        match matched {
+            Some((_, cluster)) => {
+                cluster.2.push(idx);
+                cluster.1 = fuse_boxes(boxes, &cluster.2);
+            }
+            None => clusters.push((idx, *b, vec![idx])),
+        }
+    }
+
+    let n_sources = n_sources.max(1);
+    let mut fused: Vec<(usize, XYXY)> = clusters
+        .into_iter()
+        .map(|(best, mut fused, members)| {
+            fused.prob *= members.len().min(n_sources) as f32 / n_sources as f32;
+            (best, fused)
+        })
+        .collect();
+    fused.sort_by(|a, b| b.1.prob.total_cmp(&a.1.prob));
+    fused
+}
+
+fn fuse_boxes(boxes: &[XYXY], members: &[usize]) -> XYXY {
+    let total: f32 = members.iter().map(|&i| boxes[i].prob).sum::<f32>().max(f32::EPSILON);
+    let weighted = |coord: fn(&XYXY) -> f32| members.iter().map(|&i| coord(&boxes[i]) * boxes[i].prob).sum::<f32>() / total;
+    XYXY::new(
+        weighted(|b| b.x1),
+        weighted(|b| b.y1),
+        weighted(|b| b.x2),
+        weighted(|b| b.y2),
+        total / members.len() as f32,
+        boxes[members[0]].class_id,
+    )
+}
+
+/// NMS for points: keeps the most confident point and drops every other one
+/// closer than `min_distance` to it, then repeats.
+pub fn point_suppression_indices(points: &[XY], min_distance: f32, per_class: bool) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..points.len()).collect();
+    indices.sort_by(|&a, &b| {
+        points[b]
+            .prob
+            .partial_cmp(&points[a].prob)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut keep: Vec<usize> = Vec::new();
+    for idx in indices {
+        let p = &points[idx];
+        let suppressed = keep.iter().any(|&k| {
+            let q = &points[k];
+            (!per_class || p.class_id == q.class_id)
+                && (p.x - q.x).hypot(p.y - q.y) < min_distance
+        });
+        if !suppressed {
+            keep.push(idx);
+        }
+    }
+    keep
+}
+
 pub fn process_mask(
     mask: Array2<f32>,
     bbox: &XYXY,
@@ -119,6 +287,14 @@ pub fn extract_output(
         .into_owned()
 }
 
+/// Item `i` of a batched output from [`extract_output`], whose axes are
+/// reversed so the batch is last. Keeps a batch axis of 1, so code written
+/// for single images slices it unchanged.
+pub fn batch_item(output: &Array<f32, IxDyn>, i: usize) -> Array<f32, IxDyn> {
+    let batch_axis = Axis(output.ndim() - 1);
+    output.index_axis(batch_axis, i).insert_axis(batch_axis).to_owned()
+}
+
 pub fn process_class_output(
     conf: Option<f32>,
     classes: &[String],
@@ -213,6 +389,28 @@ impl<'a> SpeciesRecord<'a> {
         }
     }
 
+    /// Most specific taxon as `(scientific name, rank)`, capitalised the way
+    /// GBIF expects ("Leopardus pardalis", "species"). `None` for records with
+    /// no taxonomy at all, like blank or vehicle.
+    pub fn scientific_name(&self) -> Option<(String, &'static str)> {
+        match (&self.genus, &self.species) {
+            (Some(genus), Some(species)) => {
+                return Some((format!("{} {}", capitalize(genus), species.to_lowercase()), "species"));
+            }
+            (Some(genus), None) => return Some((capitalize(genus), "genus")),
+            _ => {}
+        }
+        if let Some(family) = &self.family {
+            Some((capitalize(family), "family"))
+        } else if let Some(order) = &self.order {
+            Some((capitalize(order), "order"))
+        } else if !self.class.is_empty() {
+            Some((capitalize(&self.class), "class"))
+        } else {
+            None
+        }
+    }
+
     pub fn to_taxonomic_string(&self) -> String {
         format!(
             "{};{};{};{};{}",
@@ -225,6 +423,14 @@ impl<'a> SpeciesRecord<'a> {
     }
 }
 
+pub(crate) fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
 pub fn apply_geofence_filter(
     probs: &mut Vec<Prob>,
     geofence_data: &HashMap<String, Vec<String>>,
@@ -2,7 +2,7 @@ use crate::api::abstractions::XYXY;
 use crate::api::audio::AudioData;
 use fast_image_resize::{self as fir};
 use image::{ImageBuffer, Rgb};
-use ndarray::{s, Array, Array2, Ix4};
+use ndarray::{s, Array, Array2, ArrayView4, Axis, Ix4};
 use realfft::RealFftPlanner;
 
 const SCALE: f32 = 1.0 / 255.0;
@@ -88,6 +88,12 @@ pub fn imgbuf_to_input_array(
     (input, img_width, img_height)
 }
 
+/// Stacks per-image `[1, …]` tensors into one `[N, …]` batch.
+pub fn stack_batch<'a>(inputs: impl IntoIterator<Item = ArrayView4<'a, f32>>) -> Array<f32, Ix4> {
+    let views: Vec<ArrayView4<f32>> = inputs.into_iter().collect();
+    ndarray::concatenate(Axis(0), &views).expect("batch inputs share one shape")
+}
+
 const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
 const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
 
@@ -0,0 +1,117 @@
+use crate::api::abstractions::{AIOutputs, TileConfig, XYXY};
+use crate::api::processing::post::{nms_indices, point_suppression_indices};
+
+/// Tiles covering a `width`×`height` image, in image coordinates. Edge tiles
+/// are shifted inwards so every tile is full size; an image no larger than a
+/// tile gets a single tile. With `full_image` the whole image comes first.
+pub fn tile_grid(width: u32, height: u32, config: &TileConfig) -> Vec<XYXY> {
+    let size = config.size.max(1);
+    let stride = ((size as f32 * (1.0 - config.overlap.clamp(0.0, 0.95))).round() as u32).max(1);
+    let full = XYXY::new(0.0, 0.0, width as f32, height as f32, 1.0, 0);
+    if width <= size && height <= size {
+        return vec![full];
+    }
+
+    let mut tiles = Vec::new();
+    if config.full_image {
+        tiles.push(full);
+    }
+    for y in tile_starts(height, size, stride) {
+        for x in tile_starts(width, size, stride) {
+            tiles.push(XYXY::new(
+                x as f32,
+                y as f32,
+                (x + size).min(width) as f32,
+                (y + size).min(height) as f32,
+                1.0,
+                0,
+            ));
+        }
+    }
+    tiles
+}
+
+fn tile_starts(len: u32, size: u32, stride: u32) -> Vec<u32> {
+    if len <= size {
+        return vec![0];
+    }
+    let mut starts: Vec<u32> = (0..len - size).step_by(stride as usize).collect();
+    starts.push(len - size);
+    starts
+}
+
+/// The kind of output a tiled model gives, kept so an image where no tile
+/// found anything still gets an (empty) output of that kind.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Boxes,
+    Segments,
+    Points,
+}
+
+/// Shifts each tile's detections to image coordinates and merges them into
+/// one output, of the same kind as the tiles'. Outputs other than boxes,
+/// segments and points are dropped.
+pub fn merge_tiles(
+    tiles: impl IntoIterator<Item = (XYXY, AIOutputs)>,
+    nms_threshold: f32,
+    point_distance: f32,
+) -> AIOutputs {
+    let mut dets = Vec::new();
+    let mut segs = Vec::new();
+    let mut points = Vec::new();
+    let mut kind = None;
+    for (tile, output) in tiles {
+        let (dx, dy) = (tile.x1, tile.y1);
+        match output {
+            AIOutputs::ObjectDetection(tile_dets) => {
+                kind.get_or_insert(Kind::Boxes);
+                dets.extend(tile_dets.into_iter().map(|mut det| {
+                    offset_xyxy(&mut det.xyxy, dx, dy);
+                    det
+                }))
+            }
+            AIOutputs::Segmentation(tile_segs) => {
+                kind.get_or_insert(Kind::Segments);
+                segs.extend(tile_segs.into_iter().map(|mut seg| {
+                    offset_xyxy(&mut seg.bbox.xyxy, dx, dy);
+                    seg
+                }))
+            }
+            AIOutputs::PointDetection(tile_points) => {
+                kind.get_or_insert(Kind::Points);
+                points.extend(tile_points.into_iter().map(|mut point| {
+                    point.xy.x += dx;
+                    point.xy.y += dy;
+                    point
+                }))
+            }
+            _ => {}
+        }
+    }
+
+    match kind.unwrap_or(Kind::Boxes) {
+        Kind::Segments => {
+            let boxes: Vec<XYXY> = segs.iter().map(|seg| seg.bbox.xyxy).collect();
+            let keep = nms_indices(&boxes, nms_threshold, true);
+            AIOutputs::Segmentation(keep.into_iter().map(|i| segs[i].clone()).collect())
+        }
+        Kind::Points => {
+            let xys: Vec<_> = points.iter().map(|point| point.xy).collect();
+            let keep = point_suppression_indices(&xys, point_distance, true);
+            AIOutputs::PointDetection(keep.into_iter().map(|i| points[i].clone()).collect())
+        }
+        Kind::Boxes => {
+            let boxes: Vec<XYXY> = dets.iter().map(|det| det.xyxy).collect();
+            let keep = nms_indices(&boxes, nms_threshold, true);
+            AIOutputs::ObjectDetection(keep.into_iter().map(|i| dets[i].clone()).collect())
+        }
+    }
+}
+
+fn offset_xyxy(xyxy: &mut XYXY, dx: f32, dy: f32) {
+    xyxy.x1 += dx;
+    xyxy.y1 += dy;
+    xyxy.x2 += dx;
+    xyxy.y2 += dy;
+}
@@ -0,0 +1,151 @@
+use crate::api::abstractions::{AIOutputs, BitMatrix, Prob, TtaConfig, XYXY};
+use crate::api::processing::post::{nms_indices, weighted_box_fusion};
+use image::imageops::{crop_imm, flip_horizontal, replace};
+use image::{ImageBuffer, Rgb, RgbImage};
+use std::collections::HashMap;
+
+/// How an augmented copy was made from the original image: its `(0, 0)`
+/// sits at `offset` in the original, and it is `width` pixels wide.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Augmentation {
+    pub flip: bool,
+    pub offset: (f32, f32),
+    pub width: f32,
+}
+
+/// The original image plus the copies `config` asks for. The original is
+/// always first.
+pub fn augment(img: &RgbImage, config: &TtaConfig) -> Vec<(Augmentation, RgbImage)> {
+    let mut scales = vec![1.0];
+    scales.extend(config.scales.iter().copied().filter(|&s| s > 0.0 && s != 1.0));
+
+    let mut variants = Vec::new();
+    for scale in scales {
+        let (aug, scaled) = rescale(img, scale);
+        if config.flip {
+            let flipped = Augmentation { flip: true, ..aug };
+            variants.push((aug, scaled.clone()));
+            variants.push((flipped, flip_horizontal(&scaled)));
+        } else {
+            variants.push((aug, scaled));
+        }
+    }
+    variants
+}
+
+/// Zooms in by cropping the centre (`scale > 1`) or out by padding with grey
+/// (`scale < 1`), keeping the image's aspect ratio.
+fn rescale(img: &RgbImage, scale: f32) -> (Augmentation, RgbImage) {
+    let (width, height) = img.dimensions();
+    let new_w = ((width as f32 / scale).round() as u32).max(1);
+    let new_h = ((height as f32 / scale).round() as u32).max(1);
+    if (new_w, new_h) == (width, height) {
+        let aug = Augmentation { flip: false, offset: (0.0, 0.0), width: width as f32 };
+        return (aug, img.clone());
+    }
+
+    let dx = (width as i64 - new_w as i64) / 2;
+    let dy = (height as i64 - new_h as i64) / 2;
+    let aug = Augmentation { flip: false, offset: (dx as f32, dy as f32), width: new_w as f32 };
+    if scale > 1.0 {
+        (aug, crop_imm(img, dx as u32, dy as u32, new_w, new_h).to_image())
+    } else {
+        let mut canvas = ImageBuffer::from_pixel(new_w, new_h, Rgb([114, 114, 114]));
+        replace(&mut canvas, img, -dx, -dy);
+        (aug, canvas)
+    }
+}
+
+/// Maps an augmented copy's detections back onto the original image.
+/// Classifications need no mapping and are returned as they are.
+pub fn restore(output: AIOutputs, aug: &Augmentation) -> AIOutputs {
+    let restore_xyxy = |xyxy: &mut XYXY| {
+        if aug.flip {
+            (xyxy.x1, xyxy.x2) = (aug.width - xyxy.x2, aug.width - xyxy.x1);
+        }
+        xyxy.x1 += aug.offset.0;
+        xyxy.x2 += aug.offset.0;
+        xyxy.y1 += aug.offset.1;
+        xyxy.y2 += aug.offset.1;
+    };
+    match output {
+        AIOutputs::ObjectDetection(mut dets) => {
+            dets.iter_mut().for_each(|det| restore_xyxy(&mut det.xyxy));
+            AIOutputs::ObjectDetection(dets)
+        }
+        AIOutputs::Segmentation(mut segs) => {
+            for seg in segs.iter_mut() {
+                restore_xyxy(&mut seg.bbox.xyxy);
+                if aug.flip {
+                    flip_mask(&mut seg.mask);
+                }
+            }
+            AIOutputs::Segmentation(segs)
+        }
+        other => other,
+    }
+}
+
+fn flip_mask(mask: &mut BitMatrix) {
+    for y in 0..mask.height {
+        let row = &mut mask.data[y * mask.width..(y + 1) * mask.width];
+        row.reverse();
+    }
+}
+
+/// Fuses the restored outputs of all copies of one image: class
+/// probabilities are averaged (a class a copy did not report counts as 0),
+/// boxes are merged with weighted box fusion, and segments, whose masks
+/// can't be averaged, go through NMS.
+pub fn fuse(outputs: Vec<AIOutputs>, nms_threshold: f32) -> AIOutputs {
+    let n = outputs.len();
+    let (classified, segmented) = match outputs.first() {
+        Some(AIOutputs::Classification(_)) => (true, false),
+        Some(AIOutputs::Segmentation(_)) => (false, true),
+        _ => (false, false),
+    };
+    let mut dets = Vec::new();
+    let mut segs = Vec::new();
+    let mut probs: HashMap<String, (u32, f32)> = HashMap::new();
+    let mut other = None;
+    for output in outputs {
+        match output {
+            AIOutputs::ObjectDetection(d) => dets.extend(d),
+            AIOutputs::Segmentation(s) => segs.extend(s),
+            AIOutputs::Classification(ps) => {
+                for p in ps {
+                    probs.entry(p.label).or_insert((p.class_id, 0.0)).1 += p.prob;
+                }
+            }
+            o => other = other.or(Some(o)),
+        }
+    }
+
+    if classified {
+        let mut averaged: Vec<Prob> = probs
+            .into_iter()
+            .map(|(label, (class_id, sum))| Prob::new(label, sum / n as f32, class_id))
+            .collect();
+        averaged.sort_by(|a, b| b.prob.total_cmp(&a.prob));
+        AIOutputs::Classification(averaged)
+    } else if segmented {
+        let boxes: Vec<XYXY> = segs.iter().map(|seg| seg.bbox.xyxy).collect();
+        let keep = nms_indices(&boxes, nms_threshold, true);
+        AIOutputs::Segmentation(keep.into_iter().map(|i| segs[i].clone()).collect())
+    } else if let Some(other) = other {
+        other
+    } else {
+        let boxes: Vec<XYXY> = dets.iter().map(|det| det.xyxy).collect();
+        let fused = weighted_box_fusion(&boxes, nms_threshold, n);
+        AIOutputs::ObjectDetection(
+            fused
+                .into_iter()
+                .map(|(i, xyxy)| {
+                    let mut det = dets[i].clone();
+                    det.xyxy = xyxy;
+                    det
+                })
+                .collect(),
+        )
+    }
+}
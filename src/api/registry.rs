@@ -0,0 +1,216 @@
+use super::abstractions::*;
+use super::bq::{AIMetadata, BQModel, Ep};
+use super::models::{ensemble::Ensemble, AIInput, Model};
+use anyhow::{bail, Context, Result};
+use image::{ImageBuffer, Rgb};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+static MODELS: RwLock<BTreeMap<String, ModelHandle>> = RwLock::new(BTreeMap::new());
+
+/// Any number of models loaded side by side, each under its own name.
+/// Callers hold a [`ModelHandle`] and pick the model per call instead of
+/// going through a global slot.
+pub struct ModelRegistry;
+
+impl ModelRegistry {
+    /// Loads the `.bq` at `path` under `name`, replacing whatever was loaded
+    /// under that name before. Without a `config`, the one saved next to the
+    /// model (see [`BQModel::save_config`]) is used, else the default.
+    pub fn load(
+        name: &str,
+        path: impl AsRef<Path>,
+        ep: Ep,
+        config: Option<ModelConfig>,
+    ) -> Result<ModelHandle> {
+        let config = config.or_else(|| BQModel::load_config(&path)).unwrap_or_default();
+        let (model, metadata, bq_sha256, bytes) = Self::open(path, ep, config)?;
+        Ok(Self::insert(name, model, metadata, bq_sha256, ep, bytes))
+    }
+
+    /// Loads the `.bq` files at `paths` as one [`Ensemble`] under `name`.
+    /// Its metadata is the first model's, with every member's classes, and
+    /// its hash combines theirs.
+    pub fn load_ensemble(
+        name: &str,
+        paths: &[PathBuf],
+        ensemble: EnsembleConfig,
+        ep: Ep,
+        config: Option<ModelConfig>,
+    ) -> Result<ModelHandle> {
+        let config = config.unwrap_or_default();
+        let mut members = Vec::with_capacity(paths.len());
+        let mut metadata: Option<AIMetadata> = None;
+        let mut hashes = Vec::with_capacity(paths.len());
+        let mut bytes = 0;
+        for path in paths {
+            let (model, member, sha256, size) = Self::open(path, ep, config.clone())
+                .with_context(|| format!("Ensemble member {}", path.display()))?;
+            if let Some(first) = metadata.as_mut() {
+                if first.task != member.task || first.modality != member.modality {
+                    bail!("{} does not have the same task and modality as {}", member.name, first.name);
+                }
+                for class in member.classes {
+                    if !first.classes.contains(&class) {
+                        first.classes.push(class);
+                    }
+                }
+                for technique in member.post_processing {
+                    if !first.post_processing.contains(&technique) {
+                        first.post_processing.push(technique);
+                    }
+                }
+            } else {
+                metadata = Some(member);
+            }
+            members.push(model);
+            hashes.push(sha256);
+            bytes += size;
+        }
+        let mut metadata = metadata.context("An ensemble needs at least one model")?;
+        metadata.name = name.to_owned();
+        metadata.architecture = "ensemble".to_owned();
+
+        let model = Model::Ensemble(Ensemble::new(members, metadata.classes.clone(), ensemble, config)?);
+        Ok(Self::insert(name, model, metadata, hashes.join("+"), ep, bytes))
+    }
+
+    fn open(path: impl AsRef<Path>, ep: Ep, config: ModelConfig) -> Result<(Model, AIMetadata, String, usize)> {
+        let (metadata, payload) = BQModel::import_data(&path)?;
+        let bq_sha256 = payload.identity(&metadata.info)?;
+        let session = BQModel::session_from_payload(&payload, ep)?;
+        let model = Model::new(metadata.clone(), session, config)?;
+        Ok((model, metadata, bq_sha256, payload.len()))
+    }
+
+    fn insert(name: &str, model: Model, metadata: AIMetadata, bq_sha256: String, ep: Ep, bytes: usize) -> ModelHandle {
+        let handle = ModelHandle(Arc::new(Loaded {
+            name: name.to_owned(),
+            model: RwLock::new(model),
+            metadata,
+            bq_sha256,
+            ep,
+            bytes,
+        }));
+        MODELS.write().unwrap().insert(name.to_owned(), handle.clone());
+        handle
+    }
+
+    pub fn get(name: &str) -> Option<ModelHandle> {
+        MODELS.read().ok()?.get(name).cloned()
+    }
+
+    /// Drops the registry's reference to `name`. The model is freed once
+    /// in-flight calls holding a handle to it finish. False when nothing was
+    /// loaded under that name.
+    pub fn unload(name: &str) -> bool {
+        MODELS.write().unwrap().remove(name).is_some()
+    }
+
+    /// Unloads `handle` if it is still the model registered under its name,
+    /// leaving a newer model loaded under the same name alone.
+    pub fn unload_handle(handle: &ModelHandle) -> bool {
+        let mut models = MODELS.write().unwrap();
+        if models.get(handle.name()).is_some_and(|m| m.same(handle)) {
+            models.remove(handle.name());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Every loaded model, sorted by name.
+    pub fn list() -> Vec<ModelHandle> {
+        MODELS
+            .read()
+            .map(|models| models.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Sum of [`ModelHandle::memory_bytes`] over the loaded models.
+    pub fn memory_bytes() -> usize {
+        Self::list().iter().map(ModelHandle::memory_bytes).sum()
+    }
+}
+
+struct Loaded {
+    name: String,
+    model: RwLock<Model>,
+    metadata: AIMetadata,
+    bq_sha256: String,
+    ep: Ep,
+    bytes: usize,
+}
+
+/// A model in the registry. Cheap to clone; it keeps the model alive even
+/// after [`ModelRegistry::unload`].
+#[derive(Clone)]
+pub struct ModelHandle(Arc<Loaded>);
+
+impl ModelHandle {
+    /// True when both handles point at the same loaded model.
+    pub fn same(&self, other: &ModelHandle) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    pub fn metadata(&self) -> &AIMetadata {
+        &self.0.metadata
+    }
+
+    pub fn ep(&self) -> Ep {
+        self.0.ep
+    }
+
+    /// Size of the ONNX weights the session was built from. The runtime's
+    /// own buffers come on top, so treat it as a lower bound.
+    pub fn memory_bytes(&self) -> usize {
+        self.0.bytes
+    }
+
+    pub fn config(&self) -> ModelConfig {
+        self.0.model.read().map(|m| m.config().clone()).unwrap_or_default()
+    }
+
+    pub fn update_config(&self, config: ModelConfig) {
+        self.0.model.write().unwrap().set_config(config);
+    }
+
+    pub fn run(&self, input: &AIInput) -> Result<AIOutputs> {
+        let model = self.0.model.read().ok().context("model lock poisoned")?;
+        Ok(model.run(input))
+    }
+
+    /// Runs `imgs` in as few session calls as the model allows.
+    pub fn run_images(&self, imgs: &[ImageBuffer<Rgb<u8>, Vec<u8>>]) -> Result<Vec<AIOutputs>> {
+        let model = self.0.model.read().ok().context("model lock poisoned")?;
+        Ok(model.run_images(imgs))
+    }
+
+    /// Identity of the model and its current config.
+    pub fn stamp(&self) -> ModelStamp {
+        ModelStamp {
+            model: self.0.metadata.name.clone(),
+            bq_sha256: self.0.bq_sha256.clone(),
+            config: self.config(),
+            crop: None,
+            model_cls: None,
+        }
+    }
+
+    /// Sidecar provenance for an output of this model, made now.
+    pub fn provenance(&self) -> Provenance {
+        Provenance {
+            stamp: self.stamp(),
+            architecture: self.0.metadata.architecture.clone(),
+            classes: self.0.metadata.classes.clone(),
+            ep: self.0.ep.name().to_owned(),
+            created_at: chrono::Local::now().to_rfc3339(),
+            boquilahub_version: env!("CARGO_PKG_VERSION").to_owned(),
+        }
+    }
+}
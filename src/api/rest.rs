@@ -1,13 +1,67 @@
 use super::abstractions::AIOutputs;
 use super::audio::AudioData;
 use super::bq::*;
-use axum::{extract::Multipart, http::StatusCode, routing::{get, post}, Router};
+use super::pipeline::Pipeline;
+use super::registry::{ModelHandle, ModelRegistry};
+use axum::{extract::{Multipart, Query}, http::StatusCode, routing::{get, post}, Router};
 use image::codecs::jpeg::JpegEncoder;
 use image::{ColorType, ImageBuffer, ImageEncoder, Rgb};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use std::process::Command;
 
-async fn upload(mut multipart: Multipart) -> Result<String, StatusCode> {
+/// `/upload?model=NAME&cls=NAME` picks registry models for one request.
+/// Without either, the server's pipeline or deployed pair is used.
+#[derive(Deserialize, Default)]
+struct ModelQuery {
+    model: Option<String>,
+    cls: Option<String>,
+}
+
+impl ModelQuery {
+    fn resolve(&self) -> Result<Pipeline, StatusCode> {
+        let get = |name: &str| ModelRegistry::get(name).ok_or(StatusCode::NOT_FOUND);
+        let cls = self.cls.as_deref().map(get).transpose()?;
+        match (&self.model, cls) {
+            (Some(name), cls) => Ok(Pipeline::from_models(get(name)?, cls)),
+            (None, Some(cls)) => {
+                let model = GlobalBQ::First.handle().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+                Ok(Pipeline::from_models(model, Some(cls)))
+            }
+            (None, None) => Pipeline::current().ok_or(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+}
+
+/// One entry of `GET /models`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoadedModelInfo {
+    pub name: String,
+    pub model: String,
+    pub task: String,
+    pub ep: String,
+    pub memory_bytes: usize,
+}
+
+impl From<&ModelHandle> for LoadedModelInfo {
+    fn from(handle: &ModelHandle) -> Self {
+        Self {
+            name: handle.name().to_owned(),
+            model: handle.metadata().name.clone(),
+            task: handle.metadata().task.name().to_owned(),
+            ep: handle.ep().name().to_owned(),
+            memory_bytes: handle.memory_bytes(),
+        }
+    }
+}
+
+async fn models() -> Result<String, StatusCode> {
+    let models: Vec<LoadedModelInfo> = ModelRegistry::list().iter().map(LoadedModelInfo::from).collect();
+    serde_json::to_string(&models).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn upload(Query(query): Query<ModelQuery>, mut multipart: Multipart) -> Result<String, StatusCode> {
+    let pipeline = query.resolve()?;
     let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? else {
         return Err(StatusCode::BAD_REQUEST);
     };
@@ -18,12 +72,12 @@ async fn upload(mut multipart: Multipart) -> Result<String, StatusCode> {
         let audio = AudioData::from_bytes(&data)
             .map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?
             .to_mono();
-        process_audio(&audio)
+        process_audio_with(pipeline.model(), &audio)
     } else {
         let imgbuf = image::load_from_memory(&data)
             .map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?
             .into_rgb8();
-        process_imgbuf(&imgbuf)
+        pipeline.run(&imgbuf)
     };
 
     let result = result.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -49,6 +103,7 @@ impl<'a> From<&'a ImageBuffer<Rgb<u8>, Vec<u8>>> for Payload<'a> {
 #[derive(Clone)]
 pub struct Rest {
     client: Client,
+    base_url: String,
     upload_url: String,
 }
 
@@ -62,6 +117,7 @@ impl Rest {
 
         Some(Self {
             client: Client::new(),
+            base_url: base_url.to_owned(),
             upload_url: format!("{}/upload", base_url),
         })
     }
@@ -70,6 +126,7 @@ impl Rest {
         let app: Router = Router::new()
             .route("/", get(root))
             .route("/upload", post(upload))
+            .route("/models", get(models))
             .layer(axum::extract::DefaultBodyLimit::max(50 * 1024 * 1024)); // 50MB limit;
 
         let addr = format!("0.0.0.0:{}", port);
@@ -79,21 +136,41 @@ impl Rest {
     }
 
     pub async fn detect<'a>(&self, payload: impl Into<Payload<'a>>) -> anyhow::Result<AIOutputs> {
+        self.detect_with(payload, None).await
+    }
+
+    /// Models loaded on the server, for [`Rest::detect_with`].
+    pub async fn models(&self) -> anyhow::Result<Vec<LoadedModelInfo>> {
+        let response = self.client.get(format!("{}/models", self.base_url)).send().await?;
+        Ok(serde_json::from_str(&response.text().await?)?)
+    }
+
+    /// Like [`Rest::detect`], on the server model registered as `model`.
+    pub async fn detect_with<'a>(
+        &self,
+        payload: impl Into<Payload<'a>>,
+        model: Option<&str>,
+    ) -> anyhow::Result<AIOutputs> {
         let (buffer, mime) = match payload.into() {
             Payload::RawImageBytes(bytes) => (bytes, "image/*"),
             Payload::RawAudioBytes(bytes) => (bytes, "audio/*"),
             Payload::RgbImage(img) => (rgb_image_to_jpeg_buffer(img, 95), "image/jpeg"),
         };
 
+        let url = match model {
+            Some(model) => reqwest::Url::parse_with_params(&self.upload_url, &[("model", model)])?,
+            None => reqwest::Url::parse(&self.upload_url)?,
+        };
         let response = self
             .client
-            .post(&self.upload_url)
+            .post(url)
             .multipart(reqwest::multipart::Form::new().part(
                 "file",
                 reqwest::multipart::Part::bytes(buffer).mime_str(mime)?,
             ))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         let response_text = response.text().await?;
         let deserialized: AIOutputs = serde_json::from_str(&response_text)?;
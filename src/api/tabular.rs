@@ -0,0 +1,193 @@
+use super::abstractions::*;
+use super::metadata::ImageMetadata;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// One CSV row: a single detection, point, classification or audio window.
+/// Files that were processed but produced nothing get one row with an empty
+/// label, so spreadsheets still count them.
+///
+/// `frame` is set for video, `start_s`/`end_s` for audio windows, audio
+/// detections and video frames. For audio detections the box is in seconds
+/// (x) and Hz (y); for points only `x1`/`y1` are filled. The trailing
+/// capture columns come from image EXIF and stay empty for audio and video.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PredRow {
+    pub file: String,
+    pub frame: Option<u64>,
+    pub start_s: Option<f32>,
+    pub end_s: Option<f32>,
+    pub label: String,
+    pub class_id: Option<u32>,
+    pub prob: Option<f32>,
+    pub x1: Option<f32>,
+    pub y1: Option<f32>,
+    pub x2: Option<f32>,
+    pub y2: Option<f32>,
+    pub cls_label: Option<String>,
+    pub cls_prob: Option<f32>,
+    pub captured_at: Option<String>,
+    pub camera: Option<String>,
+    pub camera_serial: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub trigger: Option<String>,
+}
+
+impl PredRow {
+    fn new(file: &Path) -> Self {
+        Self {
+            file: file.to_string_lossy().into_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn with_box(mut self, xyxyc: &XYXYc) -> Self {
+        let b = &xyxyc.xyxy;
+        self.label = xyxyc.label.clone();
+        self.class_id = Some(b.class_id);
+        self.prob = Some(b.prob);
+        (self.x1, self.y1, self.x2, self.y2) = (Some(b.x1), Some(b.y1), Some(b.x2), Some(b.y2));
+        if let Some(top) = xyxyc.extra_cls.as_ref().and_then(|p| p.top()) {
+            self.cls_label = Some(top.label.clone());
+            self.cls_prob = Some(top.prob);
+        }
+        self
+    }
+
+    fn with_metadata(mut self, metadata: Option<&ImageMetadata>) -> Self {
+        if let Some(m) = metadata {
+            self.captured_at = m.captured_at.clone();
+            self.camera = m.camera();
+            self.camera_serial = m.serial_number.clone();
+            (self.latitude, self.longitude) = (m.latitude, m.longitude);
+            self.trigger = m.trigger.clone();
+        }
+        self
+    }
+}
+
+/// Flattens one `AIOutputs` into rows; `base` carries the file/time columns.
+/// Classification keeps the top-1 only; embeddings have no tabular form.
+pub fn output_rows(base: &PredRow, output: &AIOutputs, is_audio: bool) -> Vec<PredRow> {
+    let mut rows: Vec<PredRow> = match output {
+        AIOutputs::ObjectDetection(boxes) => boxes
+            .iter()
+            .map(|b| {
+                let mut row = base.clone().with_box(b);
+                if is_audio {
+                    (row.start_s, row.end_s) = (Some(b.xyxy.x1), Some(b.xyxy.x2));
+                }
+                row
+            })
+            .collect(),
+        AIOutputs::Segmentation(segs) => segs.iter().map(|s| base.clone().with_box(&s.bbox)).collect(),
+        AIOutputs::PointDetection(points) => points
+            .iter()
+            .map(|p| PredRow {
+                label: p.label.clone(),
+                class_id: Some(p.xy.class_id),
+                prob: Some(p.xy.prob),
+                x1: Some(p.xy.x),
+                y1: Some(p.xy.y),
+                ..base.clone()
+            })
+            .collect(),
+        AIOutputs::Classification(probs) => probs
+            .top()
+            .map(|p| PredRow {
+                label: p.label.clone(),
+                class_id: Some(p.class_id),
+                prob: Some(p.prob),
+                ..base.clone()
+            })
+            .into_iter()
+            .collect(),
+        AIOutputs::AudioClassification(windows) => windows
+            .iter()
+            .map(|w| PredRow {
+                start_s: Some(w.start),
+                end_s: Some(w.end),
+                label: w.prediction.label.clone(),
+                class_id: Some(w.prediction.class_id),
+                prob: Some(w.prediction.prob),
+                ..base.clone()
+            })
+            .collect(),
+        AIOutputs::Embed(_) => Vec::new(),
+    };
+    if rows.is_empty() {
+        rows.push(base.clone());
+    }
+    rows
+}
+
+/// CSV rows for one media file. Unprocessed files yield none.
+pub trait ToRows {
+    fn to_rows(&self) -> Vec<PredRow>;
+}
+
+impl ToRows for PredImg {
+    fn to_rows(&self) -> Vec<PredRow> {
+        match (self.wasprocessed, self.aioutput.as_ref()) {
+            (true, Some(output)) => {
+                let base = PredRow::new(&self.file_path).with_metadata(self.metadata.as_ref());
+                output_rows(&base, output, false)
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl ToRows for PredAudio {
+    fn to_rows(&self) -> Vec<PredRow> {
+        match (self.wasprocessed, self.aioutput.as_ref()) {
+            (true, Some(output)) => output_rows(&PredRow::new(&self.file_path), output, true),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl ToRows for PredVideo {
+    fn to_rows(&self) -> Vec<PredRow> {
+        self.frames
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| Some((i as u64, f.as_ref()?)))
+            .flat_map(|(frame_idx, output)| {
+                let mut base = PredRow::new(&self.file_path);
+                base.frame = Some(frame_idx);
+                if self.fps > 0.0 {
+                    let start = frame_idx as f64 / self.fps;
+                    base.start_s = Some(start as f32);
+                    base.end_s = Some((start + self.step.max(1) as f64 / self.fps) as f32);
+                }
+                output_rows(&base, output, false)
+            })
+            .collect()
+    }
+}
+
+pub fn write_csv<'a>(
+    path: impl AsRef<Path>,
+    preds: impl IntoIterator<Item = &'a dyn ToRows>,
+) -> Result<usize> {
+    let path = path.as_ref();
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut n_rows = 0;
+    for pred in preds {
+        for row in pred.to_rows() {
+            writer.serialize(row)?;
+            n_rows += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(n_rows)
+}
+
+/// Same as [`write_csv`] for a homogeneous list, the common GUI case.
+pub fn write_csv_list<T: ToRows>(path: impl AsRef<Path>, preds: &[T]) -> Result<usize> {
+    write_csv(path, preds.iter().map(|p| p as &dyn ToRows))
+}
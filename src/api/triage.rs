@@ -0,0 +1,222 @@
+use super::abstractions::*;
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// What to do with each file once its folder is known.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TriageMode {
+    #[default]
+    Copy,
+    Move,
+    Symlink,
+    /// Plan only; nothing on disk changes.
+    DryRun,
+}
+
+impl TriageMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "copy" => Some(TriageMode::Copy),
+            "move" => Some(TriageMode::Move),
+            "symlink" | "link" => Some(TriageMode::Symlink),
+            "dry-run" | "dryrun" => Some(TriageMode::DryRun),
+            _ => None,
+        }
+    }
+}
+
+pub const EMPTY_FOLDER: &str = "empty";
+/// Folder the label folders go in by default, inside the folder being sorted.
+pub const OUTPUT_FOLDER: &str = "triage";
+
+/// Per-label confidence thresholds. A file whose dominant prediction is
+/// under its label's threshold goes to `empty/`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriageConfig {
+    pub default_threshold: f32,
+    pub thresholds: HashMap<String, f32>,
+}
+
+impl Default for TriageConfig {
+    fn default() -> Self {
+        Self {
+            default_threshold: 0.5,
+            thresholds: HashMap::new(),
+        }
+    }
+}
+
+impl TriageConfig {
+    pub fn threshold(&self, label: &str) -> f32 {
+        self.thresholds.get(label).copied().unwrap_or(self.default_threshold)
+    }
+
+    /// Parses `label=0.7`, as given on the command line.
+    pub fn parse_threshold(spec: &str) -> Result<(String, f32)> {
+        let (label, value) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("Expected LABEL=THRESHOLD, got '{}'", spec))?;
+        let value: f32 = value
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid threshold in '{}'", spec))?;
+        Ok((label.trim().to_string(), value))
+    }
+
+    /// Folder a processed image belongs in, `None` when it hasn't been
+    /// analysed yet.
+    pub fn folder_for(&self, pred: &PredImg) -> Option<String> {
+        if !pred.wasprocessed {
+            return None;
+        }
+        let output = pred.aioutput.as_ref()?;
+        if output.is_empty() {
+            return Some(EMPTY_FOLDER.to_string());
+        }
+        match output.dominant_prob() {
+            Some((_, label, prob)) if prob >= self.threshold(label) => Some(folder_name(label)),
+            _ => Some(EMPTY_FOLDER.to_string()),
+        }
+    }
+}
+
+/// Labels become folder names: path separators and characters Windows
+/// rejects are replaced, and an empty label falls back to `empty`.
+fn folder_name(label: &str) -> String {
+    let name: String = label
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | ';' => '_',
+            c => c,
+        })
+        .collect();
+    match name.trim_matches('.') {
+        "" => EMPTY_FOLDER.to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// One planned file operation. Sidecars travel with their image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriageMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub folder: String,
+}
+
+/// Where every processed image in `preds` would go under `out_dir`.
+/// Unprocessed images are left out; name clashes, of the image or of its
+/// sidecar, get a `_1`, `_2`… suffix.
+pub fn plan(preds: &[PredImg], out_dir: &Path, config: &TriageConfig) -> Vec<TriageMove> {
+    let mut taken: HashSet<PathBuf> = HashSet::new();
+    preds
+        .iter()
+        .filter_map(|pred| {
+            let folder = config.folder_for(pred)?;
+            let to = unique_target(&out_dir.join(&folder), &pred.file_path, &mut taken)?;
+            Some(TriageMove {
+                from: pred.file_path.clone(),
+                to,
+                folder,
+            })
+        })
+        .collect()
+}
+
+fn unique_target(dir: &Path, from: &Path, taken: &mut HashSet<PathBuf>) -> Option<PathBuf> {
+    let name = from.file_name()?;
+    let stem = from.file_stem()?.to_string_lossy().into_owned();
+    let ext = from.extension().map(|e| e.to_string_lossy().into_owned());
+    // `img.jpg` and `img.png` share `img_predictions.json`, so a free image
+    // name isn't enough.
+    let in_use = |path: &Path| taken.contains(path) || path.exists();
+    let mut candidate = dir.join(name);
+    let mut sidecar = sidecar_predictions_path(&candidate).ok()?;
+    let mut n = 1;
+    while candidate != from && (in_use(&candidate) || in_use(&sidecar)) {
+        candidate = dir.join(match &ext {
+            Some(ext) => format!("{}_{}.{}", stem, n, ext),
+            None => format!("{}_{}", stem, n),
+        });
+        sidecar = sidecar_predictions_path(&candidate).ok()?;
+        n += 1;
+    }
+    taken.insert(candidate.clone());
+    taken.insert(sidecar);
+    Some(candidate)
+}
+
+/// Whether `path`, found under `root`, is in `out_dir`: output of an earlier
+/// run, which sorting again would duplicate. Never when `out_dir` holds
+/// `root` itself, as when sorting in place.
+pub fn in_output(path: &Path, root: &Path, out_dir: &Path) -> bool {
+    !root.starts_with(out_dir) && path.starts_with(out_dir)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+fn transfer(from: &Path, to: &Path, mode: TriageMode) -> std::io::Result<()> {
+    match mode {
+        TriageMode::Copy => std::fs::copy(from, to).map(|_| ()),
+        TriageMode::Move => std::fs::rename(from, to).or_else(|_| {
+            // Different filesystem: fall back to copy + delete.
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }),
+        TriageMode::Symlink => symlink(&std::fs::canonicalize(from)?, to),
+        TriageMode::DryRun => Ok(()),
+    }
+}
+
+/// Carries out `moves`. Each image's `_predictions.json` goes along under the
+/// matching name. `on_done` gets every move whose image was transferred, so
+/// callers can follow moved files even when a later one fails. Stops at the
+/// first failure; the error says how many files were done before it.
+/// Returns the number of files transferred.
+pub fn apply(moves: &[TriageMove], mode: TriageMode, mut on_done: impl FnMut(&TriageMove)) -> Result<usize> {
+    if mode == TriageMode::DryRun {
+        return Ok(0);
+    }
+    let mut done = 0;
+    for m in moves {
+        if m.from == m.to {
+            continue;
+        }
+        let fail = |e: std::io::Error| {
+            anyhow!("{} -> {}: {} ({} files done)", m.from.display(), m.to.display(), e, done)
+        };
+        if let Some(dir) = m.to.parent() {
+            std::fs::create_dir_all(dir).map_err(fail)?;
+        }
+        transfer(&m.from, &m.to, mode).map_err(fail)?;
+        on_done(m);
+        let sidecar = sidecar_predictions_path(&m.from).map_err(fail)?;
+        if sidecar.exists() {
+            let target = sidecar_predictions_path(&m.to).map_err(fail)?;
+            transfer(&sidecar, &target, mode).map_err(fail)?;
+        }
+        done += 1;
+    }
+    Ok(done)
+}
+
+/// Files per folder, sorted by folder name, for summaries.
+pub fn counts(moves: &[TriageMove]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for m in moves {
+        *counts.entry(m.folder.as_str()).or_default() += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+    counts.sort();
+    counts
+}
@@ -1,5 +1,6 @@
 use ffmpeg_next as ffmpeg;
 use image::{ImageBuffer, Rgb};
+use std::path::{Path, PathBuf};
 
 pub struct SendScaler(pub ffmpeg::software::scaling::Context);
 unsafe impl Send for SendScaler {}
@@ -59,3 +60,15 @@ pub fn rgb_frame_to_imgbuf(frame: &ffmpeg::frame::Video) -> ImageBuffer<Rgb<u8>,
     }
 }
 
+/// Deepest directory containing every path; the natural `root` for a picked
+/// set of images.
+pub fn common_root<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Option<PathBuf> {
+    let mut paths = paths.into_iter();
+    let mut root = paths.next()?.parent()?.to_path_buf();
+    for path in paths {
+        while !path.starts_with(&root) {
+            root = root.parent()?.to_path_buf();
+        }
+    }
+    Some(root)
+}
@@ -1,15 +1,25 @@
 use crate::api::{
-    bq::{AIMetadata, BQModel, Ep, GlobalBQ},
+    abstractions::{AvailableModel, ModelConfig, ModelOrigin, Pred, PredAudio, PredImg, PredVideo, TileConfig, TtaConfig},
+    batch, bqfile::Signer, bundle, camtrap, coco, export,
+    deployment::{self, DeploymentLocation},
+    download,
+    bq::{run_provenance, AIMetadata, BQModel, Ep, GlobalBQ, Modality},
+    events::{self, EventConfig, EventLabel},
+    geojson, geotiff,
+    models::MAX_BATCH,
+    pipeline::{Pipeline, PipelineConfig},
+    registry::ModelRegistry,
     rest::{get_ipv4_address, Rest},
+    tabular::{self, ToRows},
+    triage::{self, TriageConfig, TriageMode},
 };
 use clap::{Args, Parser, Subcommand};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::fs as tokio_fs;
-use tokio::io::AsyncWriteExt;
 
 #[derive(Args)]
 pub struct ServeArgs {
-    /// Model name to deploy
+    /// Model name to deploy, or a pipeline file (.toml/.json)
     #[arg(value_name = "MODEL_PATH", required = true)]
     pub model: String,
 
@@ -17,29 +27,318 @@ pub struct ServeArgs {
     #[arg(long, value_name = "MODEL_CLS_PATH", required = false)]
     pub model_cls: Option<String>,
 
+    /// Extra models to keep loaded, picked per request with `/upload?model=NAME`
+    #[arg(long = "also", value_name = "MODEL")]
+    pub also: Vec<String>,
+
     /// Port number for the server
     #[arg(long, value_name = "PORT", default_value = "8791")]
     pub port: u16,
 }
 
+#[derive(Args)]
+pub struct ProcessArgs {
+    /// Model name to run, or a pipeline file (.toml/.json)
+    #[arg(value_name = "MODEL_PATH", required = true)]
+    pub model: String,
+
+    /// File or folder with images, audio or video
+    #[arg(value_name = "PATH", required = true)]
+    pub path: String,
+
+    /// Model name to run, complementary classification model
+    #[arg(long, value_name = "MODEL_CLS_PATH", required = false)]
+    pub model_cls: Option<String>,
+
+    /// Execution provider (cpu, gpu, cuda, webgpu)
+    #[arg(long, value_name = "EP", default_value = "gpu")]
+    pub ep: String,
+
+    /// Also process files in subfolders
+    #[arg(long, short)]
+    pub recursive: bool,
+
+    /// Analyse one video frame every STEP frames
+    #[arg(long, value_name = "STEP", default_value = "3")]
+    pub step: u32,
+
+    /// Re-process files even if their sidecar is up to date
+    #[arg(long)]
+    pub force: bool,
+
+    /// Run the detector on SIZE×SIZE tiles, for large aerial images
+    #[arg(long, value_name = "SIZE")]
+    pub tile: Option<u32>,
+
+    /// Fraction of a tile shared with its neighbours
+    #[arg(long, value_name = "FRACTION", default_value = "0.2", requires = "tile")]
+    pub tile_overlap: f32,
+
+    /// Also run flipped and rescaled copies of each image and fuse the
+    /// results (slower, catches more rare animals)
+    #[arg(long)]
+    pub tta: bool,
+}
+
+impl ProcessArgs {
+    /// The settings saved for the model at `model_path`, with the tiling and
+    /// TTA flags on top.
+    fn model_config(&self, model_path: &str) -> Option<ModelConfig> {
+        let saved = BQModel::load_config(model_path);
+        if self.tile.is_none() && !self.tta {
+            return saved;
+        }
+        Some(ModelConfig {
+            tiling: self.tile.map(|size| TileConfig {
+                size,
+                overlap: self.tile_overlap,
+                ..Default::default()
+            }),
+            tta: self.tta.then(TtaConfig::default),
+            ..saved.unwrap_or_default()
+        })
+    }
+}
+
+#[derive(Args)]
+pub struct GeotiffArgs {
+    /// Model name to run, or a pipeline file (.toml/.json)
+    #[arg(value_name = "MODEL_PATH", required = true)]
+    pub model: String,
+
+    /// Georeferenced GeoTIFF mosaic
+    #[arg(value_name = "TIFF", required = true)]
+    pub path: String,
+
+    /// Execution provider (cpu, gpu, cuda, webgpu)
+    #[arg(long, value_name = "EP", default_value = "gpu")]
+    pub ep: String,
+
+    /// Side of the square windows read from the raster, in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 4096)]
+    pub window: u32,
+
+    /// Run the detector on SIZE×SIZE tiles within each window
+    #[arg(long, value_name = "SIZE", default_value_t = 640)]
+    pub tile: u32,
+
+    /// Fraction of a window or tile shared with its neighbours
+    #[arg(long, value_name = "FRACTION", default_value = "0.2")]
+    pub overlap: f32,
+
+    /// Output GeoJSON file (defaults to a timestamped name in the export folder)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: Option<String>,
+}
+
+#[derive(Args)]
+pub struct LocateArgs {
+    /// Deployment folder; its subfolders share the location
+    #[arg(value_name = "FOLDER", required = true)]
+    pub path: String,
+
+    /// Latitude in decimal degrees
+    #[arg(long, allow_hyphen_values = true)]
+    pub lat: f64,
+
+    /// Longitude in decimal degrees
+    #[arg(long, allow_hyphen_values = true)]
+    pub lon: f64,
+
+    /// Deployment name shown on the map
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
 #[derive(Args)]
 pub struct PullArgs {
     /// Model name to pull
-    #[arg(value_name = "MODEL_NAME", required = true)]
-    pub model: String,
+    #[arg(value_name = "MODEL_NAME", required_unless_present = "update")]
+    pub model: Option<String>,
+
+    /// Replace local models that are older than the index's; alone, checks every local model
+    #[arg(long)]
+    pub update: bool,
+
+    /// Model index URL or path (file:// or plain paths for offline mirrors); defaults to $BOQUILAHUB_REGISTRY or boquila.org
+    #[arg(long, value_name = "URL")]
+    pub registry: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// File or folder whose `_predictions.json` sidecars are exported
+    #[arg(value_name = "PATH", required = true)]
+    pub path: String,
+
+    /// Also read sidecars in subfolders
+    #[arg(long, short)]
+    pub recursive: bool,
+
+    /// Output file, or folder for Camtrap DP (defaults to a timestamped name in the export folder)
+    #[arg(long, short, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Group images into events (camera-trap bursts) for CSV and Camtrap DP
+    #[arg(long)]
+    pub events: bool,
+
+    /// Seconds between images that still belong to the same event
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    pub event_gap: u32,
+
+    /// Label events by majority vote instead of the most confident image
+    #[arg(long)]
+    pub vote: bool,
+}
+
+impl ExportArgs {
+    fn event_config(&self) -> Option<EventConfig> {
+        self.events.then(|| EventConfig {
+            gap_s: self.event_gap,
+            label: if self.vote { EventLabel::Vote } else { EventLabel::MaxConfidence },
+        })
+    }
+}
+
+#[derive(Args)]
+pub struct TriageArgs {
+    /// File or folder with images and their `_predictions.json` sidecars
+    #[arg(value_name = "PATH", required = true)]
+    pub path: String,
+
+    /// Also triage images in subfolders
+    #[arg(long, short)]
+    pub recursive: bool,
+
+    /// Folder the label subfolders are created in (defaults to PATH/triage)
+    #[arg(long, short, value_name = "DIR")]
+    pub output: Option<String>,
+
+    /// What to do with each file: copy, move or symlink
+    #[arg(long, value_name = "MODE", default_value = "copy")]
+    pub mode: String,
+
+    /// Only print where each file would go
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Confidence a prediction needs; below it the image counts as empty
+    #[arg(long, value_name = "PROB", default_value = "0.5")]
+    pub threshold: f32,
+
+    /// Threshold for one label, e.g. `--label-threshold person=0.3` (repeatable)
+    #[arg(long = "label-threshold", value_name = "LABEL=PROB")]
+    pub label_thresholds: Vec<String>,
+}
+
+#[derive(Subcommand)]
+pub enum ExportCommands {
+    /// One CSV row per detection, point, classification or audio window
+    Csv(ExportArgs),
+
+    /// COCO instances.json with image detections and segmentation masks
+    Coco(ExportArgs),
+
+    /// Camtrap DP package (deployments, media, observations, datapackage.json)
+    CamtrapDp(ExportArgs),
+
+    /// GeoJSON points per deployment with species counts and detections
+    Geojson(ExportArgs),
+
+    /// KML placemarks per deployment with species counts
+    Kml(ExportArgs),
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Annotation file to import
+    #[arg(value_name = "FILE", required = true)]
+    pub file: String,
+
+    /// Folder with the annotated media; matched files get new sidecars
+    #[arg(value_name = "PATH", required = true)]
+    pub path: String,
+
+    /// Also match files in subfolders
+    #[arg(long, short)]
+    pub recursive: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ImportCommands {
+    /// COCO instances.json (boxes, RLE or polygon masks)
+    Coco(ImportArgs),
+}
+
+#[derive(Subcommand)]
+pub enum BundleCommands {
+    /// Pack models, their index entries and the geofence data into one .zip for offline stations
+    Create {
+        /// Archive to write
+        #[arg(value_name = "ARCHIVE")]
+        out: PathBuf,
+        /// Models to pack, by name or path
+        #[arg(value_name = "MODEL", required = true)]
+        models: Vec<String>,
+        /// Model index to take descriptions from; see `pull --registry`
+        #[arg(long, value_name = "URL")]
+        registry: Option<String>,
+    },
+
+    /// Install a bundle into models/, without internet
+    Install {
+        #[arg(value_name = "ARCHIVE")]
+        archive: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum BqCommands {
     /// Create a new .bq model.
     /// Pass "name" to use "name.json" and "name.onnx" and create "name.bq"
-    New { name: String },
+    New {
+        name: String,
+        /// External data file of the ONNX model; "name.onnx.data" is picked up on its own
+        #[arg(long)]
+        data: Vec<PathBuf>,
+        /// Sign the model with a key made by `bq keygen`
+        #[arg(long)]
+        sign: Option<PathBuf>,
+    },
+
+    /// Create a signing key pair: "name.key" (keep private) and "name.pub"
+    Keygen { name: String },
 
     /// Returns the shape of a .bq model
     Shape { name: String },
 
     /// Returns the JSON part of a .bq model
     Json { name: String },
+
+    /// Check a .bq model: metadata, architecture, audio_config and class count against the outputs
+    Validate { name: String },
+
+    /// Write the .json, .onnx and external data of a .bq model back out
+    Extract {
+        name: String,
+        /// Folder to write to, instead of next to the model
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Change metadata fields without touching the ONNX: field=value, value as JSON or text, null removes
+    SetMeta {
+        name: String,
+        #[arg(required = true)]
+        fields: Vec<String>,
+        /// Re-sign the model; the old signature no longer holds
+        #[arg(long)]
+        sign: Option<PathBuf>,
+    },
+
+    /// Compare metadata, classes and tensor shapes of two .bq models
+    Diff { old: String, new: String },
 }
 
 #[derive(Subcommand)]
@@ -47,12 +346,42 @@ pub enum Commands {
     /// Deploy and serve a model
     Serve(ServeArgs),
 
+    /// Run a model over a file or folder and write `_predictions.json` sidecars
+    Process(ProcessArgs),
+
+    /// Export predictions from `_predictions.json` sidecars
+    Export {
+        #[command(subcommand)]
+        command: ExportCommands,
+    },
+
+    /// Run a model over a GeoTIFF mosaic and write detections as GeoJSON
+    Geotiff(GeotiffArgs),
+
+    /// Pin a deployment folder to a latitude/longitude for map exports
+    Locate(LocateArgs),
+
+    /// Sort images into label subfolders (animal/, person/, empty/…) by prediction
+    Triage(TriageArgs),
+
+    /// Import annotations into `_predictions.json` sidecars
+    Import {
+        #[command(subcommand)]
+        command: ImportCommands,
+    },
+
     /// Download a model
     Pull(PullArgs),
 
     /// Print list of models
     List,
 
+    /// Offline model bundles for stations without internet
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommands,
+    },
+
     /// Start the GUI, while keeping the terminal
     Gui,
 
@@ -86,19 +415,41 @@ impl Cli {
         match self.command.expect("Could not run CLI") {
             Commands::Serve(args) => {
                 let ais: Vec<AIMetadata> = BQModel::get_list();
-                let model = resolve_model(&args.model, &ais);
+                let deployed = if PipelineConfig::is_pipeline_path(&args.model) {
+                    match Pipeline::from_file(&args.model, Ep::gpu()) {
+                        Ok(pipeline) => Pipeline::set_active(Some(pipeline)),
+                        Err(e) => {
+                            eprintln!("❌ {:#}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                    args.model.clone()
+                } else {
+                    let model = resolve_model(&args.model, &ais);
 
-                if let Some(cls_name) = &args.model_cls {
-                    let cls = resolve_model(cls_name, &ais);
-                    let _ = GlobalBQ::Second.set_model(&cls.get_path(), Ep::gpu(), None);
-                }
+                    if let Some(cls_name) = &args.model_cls {
+                        let cls = resolve_model(cls_name, &ais);
+                        let _ = GlobalBQ::Second.set_model(&cls.get_path(), Ep::gpu(), None);
+                    }
 
-                let _ = GlobalBQ::First.set_model(&model.get_path(), Ep::gpu(), None);
+                    let _ = GlobalBQ::First.set_model(&model.get_path(), Ep::gpu(), None);
+                    model.name.clone()
+                };
+
+                for name in &args.also {
+                    let extra = resolve_model(name, &ais);
+                    if let Err(e) = ModelRegistry::load(&extra.name, extra.get_path(), Ep::gpu(), None) {
+                        eprintln!("Could not load {}: {}", extra.name, e);
+                    }
+                }
 
                 println!("\x1b[38;2;51;218;114m{ASCII_ART}\x1b[0m");
                 match &args.model_cls {
-                    Some(cls) => println!("Model deployed: {} with {}", model.name, cls),
-                    None => println!("Model deployed: {}", model.name),
+                    Some(cls) => println!("Model deployed: {} with {}", deployed, cls),
+                    None => println!("Model deployed: {}", deployed),
+                }
+                if !args.also.is_empty() {
+                    println!("Also loaded: {}", args.also.join(", "));
                 }
                 println!("IP Address: http://{}:{}", get_ipv4_address().unwrap(),args.port);
 
@@ -106,14 +457,110 @@ impl Cli {
                     eprintln!("Error running API: {}", e);
                 }
             }
+            Commands::Process(args) => {
+                if let Err(e) = process(&args) {
+                    eprintln!("❌ Failed to process {}: {}", &args.path, e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Export { command } => match command {
+                ExportCommands::Csv(args) => match export_csv(&args) {
+                    Ok((path, n)) => {
+                        let noun = if args.events { "events" } else { "rows" };
+                        println!("Wrote {} {} to {}", n, noun, path.display())
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Failed to export {}: {}", &args.path, e);
+                        std::process::exit(1);
+                    }
+                },
+                ExportCommands::Coco(args) => match export_coco(&args) {
+                    Ok(path) => println!("Saved to {}", path.display()),
+                    Err(e) => {
+                        eprintln!("❌ Failed to export {}: {}", &args.path, e);
+                        std::process::exit(1);
+                    }
+                },
+                ExportCommands::CamtrapDp(args) => match export_camtrap_dp(&args) {
+                    Ok(path) => println!("Saved to {}", path.display()),
+                    Err(e) => {
+                        eprintln!("❌ Failed to export {}: {}", &args.path, e);
+                        std::process::exit(1);
+                    }
+                },
+                ExportCommands::Geojson(args) => match export_map(&args, MapFormat::GeoJson) {
+                    Ok((path, n)) => println!("Wrote {} deployments to {}", n, path.display()),
+                    Err(e) => {
+                        eprintln!("❌ Failed to export {}: {}", &args.path, e);
+                        std::process::exit(1);
+                    }
+                },
+                ExportCommands::Kml(args) => match export_map(&args, MapFormat::Kml) {
+                    Ok((path, n)) => println!("Wrote {} deployments to {}", n, path.display()),
+                    Err(e) => {
+                        eprintln!("❌ Failed to export {}: {}", &args.path, e);
+                        std::process::exit(1);
+                    }
+                },
+            },
+            Commands::Locate(args) => match locate(&args) {
+                Ok(path) => println!("Saved to {}", path.display()),
+                Err(e) => {
+                    eprintln!("❌ Failed to locate {}: {}", &args.path, e);
+                    std::process::exit(1);
+                }
+            },
+            Commands::Geotiff(args) => match process_geotiff(&args) {
+                Ok((path, n)) => println!("Wrote {} detections to {}", n, path.display()),
+                Err(e) => {
+                    eprintln!("❌ Failed to process {}: {}", &args.path, e);
+                    std::process::exit(1);
+                }
+            },
+            Commands::Triage(args) => {
+                if let Err(e) = triage(&args) {
+                    eprintln!("❌ Failed to triage {}: {}", &args.path, e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Import { command } => match command {
+                ImportCommands::Coco(args) => match import_coco(&args) {
+                    Ok(n) => println!("Imported annotations for {} images", n),
+                    Err(e) => {
+                        eprintln!("❌ Failed to import {}: {}", &args.file, e);
+                        std::process::exit(1);
+                    }
+                },
+            },
             Commands::List => {
                 let ais: Vec<AIMetadata> = BQModel::get_list();
                 print_ais_table(&ais);
                 std::process::exit(0);
             }
-            Commands::Pull(args) => match pull(&args.model).await {
+            Commands::Bundle { command } => match command {
+                BundleCommands::Create { out, models, registry } => match bundle_create(&out, &models, registry.as_deref()).await {
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("❌ Failed to create bundle {}: {}", out.display(), e);
+                        std::process::exit(1);
+                    }
+                },
+                BundleCommands::Install { archive } => match bundle::install(&archive, Path::new("models")) {
+                    Ok(installed) => {
+                        for (model, outcome) in installed {
+                            let version = model.version.as_deref().map(|v| format!(" {}", v)).unwrap_or_default();
+                            println!("✅ {}{}: {:?}", model.name, version, outcome);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Failed to install bundle {}: {:#}", archive.display(), e);
+                        std::process::exit(1);
+                    }
+                },
+            },
+            Commands::Pull(args) => match pull(&args).await {
                 Ok(_) => {}
-                Err(e) => eprintln!("❌ Failed to pull model {}: {}", &args.model, e),
+                Err(e) => eprintln!("❌ Failed to pull model {}: {}", args.model.as_deref().unwrap_or("updates"), e),
             },
             Commands::Gui => {
                 let _ = crate::gui::Gui::run();
@@ -127,7 +574,11 @@ impl Cli {
                     Ok(_) => {}
                     Err(e) => eprintln!("{}", e),
                 },
-                BqCommands::New { name } => match BQModel::create_bq_file(name) {
+                BqCommands::New { name, data, sign } => match BQModel::create_bq_file(name, &data, sign.as_deref()) {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("{}", e),
+                }
+                BqCommands::Keygen { name } => match BQModel::create_signing_key(&name) {
                     Ok(_) => {}
                     Err(e) => eprintln!("{}", e),
                 }
@@ -135,6 +586,36 @@ impl Cli {
                     Ok(buf) => {println!("{}", String::from_utf8_lossy(&buf));}
                     Err(e) => eprintln!("{}", e),
                 }
+                BqCommands::Validate { name } => match BQModel::validate(&name) {
+                    Ok(problems) => {
+                        if let Ok(signer) = BQModel::signer(&name) {
+                            println!("Signer: {}", signer_label(&signer));
+                        }
+                        if problems.is_empty() {
+                            println!("✅ {} is valid", name);
+                        } else {
+                            problems.iter().for_each(|problem| eprintln!("❌ {}", problem));
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("❌ {:#}", e);
+                        std::process::exit(1);
+                    }
+                },
+                BqCommands::Extract { name, out } => match BQModel::extract(&name, out.as_deref()) {
+                    Ok(paths) => paths.iter().for_each(|path| println!("{}", path.display())),
+                    Err(e) => eprintln!("{}", e),
+                },
+                BqCommands::SetMeta { name, fields, sign } => match BQModel::set_metadata(&name, &fields, sign.as_deref()) {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("{:#}", e),
+                },
+                BqCommands::Diff { old, new } => match BQModel::diff(&old, &new) {
+                    Ok(changes) if changes.is_empty() => println!("No differences"),
+                    Ok(changes) => changes.iter().for_each(|change| println!("{}", change)),
+                    Err(e) => eprintln!("{}", e),
+                },
             },
         }
     }
@@ -157,9 +638,13 @@ const ASCII_ART: &'static str = r#"
 "#;
 
 fn resolve_model<'a>(name: &str, ais: &'a [AIMetadata]) -> &'a AIMetadata {
+    find_model(name, ais).unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn find_model<'a>(name: &str, ais: &'a [AIMetadata]) -> Result<&'a AIMetadata, String> {
     let clean = name.strip_suffix(".bq").unwrap_or(name);
-    ais.iter().find(|ai| ai.name == clean).unwrap_or_else(|| {
-        panic!(
+    ais.iter().find(|ai| ai.name == clean).ok_or_else(|| {
+        format!(
             "Model '{0}' (or '{0}.bq') was not found in the 'models/' directory",
             clean
         )
@@ -172,6 +657,16 @@ pub fn print_ais_table(ais: &[AIMetadata]) {
         return;
     }
 
+    let installed: Vec<(String, String, String)> = ais
+        .iter()
+        .map(|ai| {
+            let version = ai.info.model_version.clone().unwrap_or_else(|| "-".to_owned());
+            let origin = BQModel::load_origin(ai.get_path()).map_or_else(|| "local".to_owned(), |origin| origin.source);
+            let signer = BQModel::signer(ai.get_path()).map_or_else(|_| "invalid".to_owned(), |signer| signer_label(&signer));
+            (version, origin, signer)
+        })
+        .collect();
+
     // Calculate column widths
     let name_width = std::cmp::max(4, ais.iter().map(|ai| ai.name.len()).max().unwrap_or(0));
     let task_width = std::cmp::max(4, ais.iter().map(|ai| ai.task.name().len()).max().unwrap_or(0));
@@ -180,8 +675,11 @@ pub fn print_ais_table(ais: &[AIMetadata]) {
         ais.iter().map(|ai| ai.architecture.len()).max().unwrap_or(0),
     );
     let classes_width = 8;
+    let version_width = std::cmp::max(7, installed.iter().map(|(v, _, _)| v.chars().count()).max().unwrap_or(0));
+    let origin_width = std::cmp::max(6, installed.iter().map(|(_, o, _)| o.chars().count()).max().unwrap_or(0));
+    let signer_width = std::cmp::max(6, installed.iter().map(|(_, _, s)| s.chars().count()).max().unwrap_or(0));
 
-    let widths = [name_width, task_width, arch_width, classes_width];
+    let widths = [name_width, task_width, arch_width, classes_width, version_width, origin_width, signer_width];
 
     // Helper to print borders
     let print_border = |left: &str, mid: &str, right: &str| {
@@ -196,86 +694,485 @@ pub fn print_ais_table(ais: &[AIMetadata]) {
     // Table
     print_border("┌", "┬", "┐");
     println!(
-        "│ {:^name_width$} │ {:^task_width$} │ {:^arch_width$} │ {:^classes_width$} │",
-        "Name", "Task", "Architecture", "Classes"
+        "│ {:^name_width$} │ {:^task_width$} │ {:^arch_width$} │ {:^classes_width$} │ {:^version_width$} │ {:^origin_width$} │ {:^signer_width$} │",
+        "Name", "Task", "Architecture", "Classes", "Version", "Origin", "Signer"
     );
     print_border("├", "┼", "┤");
 
-    for ai in ais {
+    for (ai, (version, origin, signer)) in ais.iter().zip(&installed) {
         println!(
-            "│ {:name_width$} │ {:task_width$} │ {:arch_width$} │ {:>classes_width$} │",
+            "│ {:name_width$} │ {:task_width$} │ {:arch_width$} │ {:>classes_width$} │ {:version_width$} │ {:origin_width$} │ {:signer_width$} │",
             ai.name,
             ai.task.name(),
             ai.architecture,
-            ai.classes.len()
+            ai.classes.len(),
+            version,
+            origin,
+            signer
         );
     }
 
     print_border("└", "┴", "┘");
 }
 
-async fn pull(model_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Searching for model '{}'...", model_name);
+/// The signer's key fingerprint, marked by whether it is in `models/trusted_keys`.
+fn signer_label(signer: &Signer) -> String {
+    match signer {
+        Signer::Unsigned => "unsigned".to_owned(),
+        Signer::Trusted(sig) => format!("{} (trusted)", sig.fingerprint()),
+        Signer::Unknown(sig) => format!("{} (unknown)", sig.fingerprint()),
+    }
+}
 
-    // Fetch the JSON index
-    let models = BQModel::get_list_from_api()
+async fn pull(args: &PullArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let index = download::index_url(args.registry.as_deref());
+    println!("Reading model index {}...", index);
+    let models = download::fetch_index(&index)
         .await
         .map_err(|e| format!("Failed to fetch model index: {}", e))?;
 
-    // Find the requested model
-    let model = models
-        .into_iter()
-        .find(|m| m.name == model_name)
-        .ok_or_else(|| format!("Model '{}' not found in the registry", model_name))?;
-
-    println!("Model found, starting download...");
-    println!("Downloading from: {}", model.download_link);
-
-    // Ensure models/ directory exists
     tokio_fs::create_dir_all("models")
         .await
         .map_err(|e| format!("Failed to create models directory: {}", e))?;
 
-    // Extract filename from URL
-    let filename = Path::new(&model.download_link)
-        .file_name()
-        .ok_or("Invalid download URL: cannot extract filename")?
-        .to_string_lossy();
-    let file_path = format!("models/{}", filename);
+    let wanted: Vec<&AvailableModel> = match &args.model {
+        Some(name) => {
+            let name = name.strip_suffix(".bq").unwrap_or(name);
+            let model = models
+                .iter()
+                .find(|m| m.name == name)
+                .ok_or_else(|| format!("Model '{}' not found in the registry", name))?;
+            vec![model]
+        }
+        // `pull --update`: every model from the index we already have.
+        None => models
+            .iter()
+            .filter(|m| download::file_name(m).is_some_and(|file| Path::new("models").join(file).exists()))
+            .collect(),
+    };
 
-    // Download the file
-    println!("Downloading to '{}'...", file_path);
-    let response = reqwest::get(&model.download_link)
+    for model in wanted {
+        let file_name = download::file_name(model).ok_or("Invalid download URL: cannot extract filename")?;
+        let file_path = Path::new("models").join(file_name);
+        if file_path.exists() {
+            if !download::is_outdated(&file_path, model)? {
+                println!("✅ {} is up to date", model.name);
+                continue;
+            }
+            if !args.update {
+                println!("{} has a newer version; run `pull {} --update` to replace it", model.name, model.name);
+                continue;
+            }
+        }
+
+        println!("Downloading {} to '{}'...", model.download_link, file_path.display());
+        let mut shown = None;
+        download::download(&model.download_link, &file_path, model.sha256.as_deref(), |done, total| {
+            let line = progress_line(done, total);
+            if shown.as_ref() != Some(&line) {
+                eprint!("\r{}", line);
+                shown = Some(line);
+            }
+        })
         .await
-        .map_err(|e| format!("Failed to download model: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!(
-            "Download failed with status: {} ({})",
-            response.status().as_u16(),
-            response
-                .status()
-                .canonical_reason()
-                .unwrap_or("Unknown error")
-        )
-        .into());
+        .map_err(|e| format!("\nFailed to download {}: {}", model.name, e))?;
+        eprintln!();
+        let origin = ModelOrigin {
+            source: index.clone(),
+            sha256: model.sha256.clone(),
+        };
+        BQModel::save_origin(&file_path, &origin)?;
+        if model.sha256.is_some() {
+            println!("✅ {} downloaded and verified", model.name);
+        } else {
+            println!("✅ {} downloaded (the index has no checksum to verify it against)", model.name);
+        }
     }
+    Ok(())
+}
 
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read downloaded content: {}", e))?;
+async fn bundle_create(out: &Path, models: &[String], registry: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let ais = BQModel::get_list();
+    let paths = models
+        .iter()
+        .map(|model| {
+            let path = PathBuf::from(model);
+            if path.exists() {
+                Ok(path)
+            } else {
+                find_model(model, &ais).map(|ai| PathBuf::from(ai.get_path()))
+            }
+        })
+        .collect::<Result<Vec<PathBuf>, String>>()?;
 
-    // Save the file
-    let mut file = tokio_fs::File::create(&file_path)
-        .await
-        .map_err(|e| format!("Failed to create file '{}': {}", file_path, e))?;
+    // Entries from the index carry descriptions; without internet the
+    // bundle is still made, from the files alone.
+    let index = download::index_url(registry);
+    let entries = match download::fetch_index(&index).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Could not read model index {} ({}); packing without descriptions", index, e);
+            Vec::new()
+        }
+    };
 
-    file.write_all(&bytes)
-        .await
-        .map_err(|e| format!("Failed to write to file '{}': {}", file_path, e))?;
+    let geofence = serde_json::to_vec(&*crate::api::bq::GEOFENCE_DATA)?;
+    let packed = bundle::create(out, &paths, &entries, &geofence)?;
+    for model in &packed {
+        println!("📦 {} {}", model.name, model.version.as_deref().unwrap_or(""));
+    }
+    println!("Bundle: {} ({:.2} MB)", out.display(), std::fs::metadata(out)?.len() as f64 / 1_048_576.0);
+    Ok(())
+}
+
+/// `[#######-------------]  35% 120.4/344.0 MB`, or just the size when the
+/// total is unknown. Changes at most once per percent or tenth of a MB.
+fn progress_line(done: u64, total: Option<u64>) -> String {
+    let mb = |bytes: u64| bytes as f64 / 1_048_576.0;
+    match total {
+        Some(total) if total > 0 => {
+            let fraction = (done as f64 / total as f64).min(1.0);
+            let filled = (fraction * 20.0) as usize;
+            format!(
+                "[{}{}] {:>3}% {:.1}/{:.1} MB",
+                "#".repeat(filled),
+                "-".repeat(20 - filled),
+                (fraction * 100.0) as u32,
+                mb(done),
+                mb(total)
+            )
+        }
+        _ => format!("{:.1} MB", mb(done)),
+    }
+}
 
-    println!("File size: {:.2} MB", bytes.len() as f64 / 1_048_576.0);
+fn process(args: &ProcessArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let ep = Ep::from_name(&args.ep)
+        .ok_or_else(|| format!("Unknown execution provider '{}'", args.ep))?;
 
+    let ais: Vec<AIMetadata> = BQModel::get_list();
+    let (name, modality) = if PipelineConfig::is_pipeline_path(&args.model) {
+        // A pipeline sets its stages, their tiling and TTA itself.
+        let flags = [(args.model_cls.is_some(), "--model-cls"), (args.tile.is_some(), "--tile"), (args.tta, "--tta")];
+        let set: Vec<&str> = flags.iter().filter(|(set, _)| *set).map(|&(_, flag)| flag).collect();
+        if !set.is_empty() {
+            return Err(format!("{} can't be combined with a pipeline; set it in {} instead", set.join(", "), args.model).into());
+        }
+        let pipeline = Pipeline::from_file(&args.model, ep)?;
+        let modality = pipeline.model().metadata().modality;
+        Pipeline::set_active(Some(pipeline));
+        (args.model.clone(), modality)
+    } else {
+        let model = resolve_model(&args.model, &ais);
+        GlobalBQ::First.set_model(&model.get_path(), ep, args.model_config(&model.get_path()))?;
+        if let Some(cls_name) = &args.model_cls {
+            let cls = resolve_model(cls_name, &ais);
+            GlobalBQ::Second.set_model(&cls.get_path(), ep, None)?;
+        }
+        (model.name.clone(), model.modality)
+    };
+    let stamp = run_provenance().ok_or("No model loaded")?.stamp;
+
+    let media = batch::collect_media(&args.path, args.recursive)?;
+    let (images, audios, videos) = match modality {
+        Modality::Image => (media.images, Vec::new(), media.videos),
+        Modality::Audio => (Vec::new(), media.audios, Vec::new()),
+    };
+    let total = images.len() + audios.len() + videos.len();
+    if total == 0 {
+        println!("No files supported by {} found in '{}'", name, args.path);
+        return Ok(());
+    }
+    println!("Processing {} files with {}...", total, name);
+
+    let mut done = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+    let mut report = |path: &Path, result: Option<Result<(), String>>| {
+        done += 1;
+        match result {
+            None => {
+                skipped += 1;
+                println!("[{}/{}] {} (up to date)", done, total, path.display());
+            }
+            Some(Ok(())) => println!("[{}/{}] {}", done, total, path.display()),
+            Some(Err(e)) => {
+                failed += 1;
+                eprintln!("[{}/{}] {}: {}", done, total, path.display(), e);
+            }
+        }
+    };
+
+    // Sidecars are written as each file finishes, so an interrupted run
+    // picks up where it stopped; stale ones (other model/weights/config) are redone.
+    let mut pending = Vec::new();
+    for path in &images {
+        if !args.force && PredImg::new_simple(path.clone()).is_up_to_date(&stamp) {
+            report(path, None);
+        } else {
+            pending.push(path.clone());
+        }
+    }
+    // Images go through the model in batches; each batch's sidecars are
+    // written before the next one starts.
+    for chunk in pending.chunks(MAX_BATCH) {
+        for (path, pred) in chunk.iter().zip(batch::process_image_files(chunk)) {
+            let result = pred
+                .map_err(|e| e.to_string())
+                .and_then(|pred| pred.write_predictions().map_err(|e| e.to_string()));
+            report(path, Some(result));
+        }
+    }
+    for path in &audios {
+        if !args.force && PredAudio::new_simple(path.clone()).is_up_to_date(&stamp) {
+            report(path, None);
+            continue;
+        }
+        let result = batch::process_audio_file(path)
+            .map_err(|e| e.to_string())
+            .and_then(|pred| pred.write_predictions().map_err(|e| e.to_string()));
+        report(path, Some(result));
+    }
+    for path in &videos {
+        let cached = PredVideo::new_simple(path.clone());
+        if !args.force && cached.is_up_to_date(&stamp) && cached.step == args.step.max(1) {
+            report(path, None);
+            continue;
+        }
+        let result = batch::process_video_file(path, args.step)
+            .map_err(|e| e.to_string())
+            .and_then(|pred| pred.write_predictions().map_err(|e| e.to_string()));
+        report(path, Some(result));
+    }
+
+    println!(
+        "Done: {} processed, {} up to date, {} failed",
+        total - skipped - failed,
+        skipped,
+        failed
+    );
+    Ok(())
+}
+
+/// Loads every sidecar under `args.path` into the same `Pred*` lists the GUI
+/// holds, so exporters don't care where the predictions came from.
+fn load_preds(
+    args: &ExportArgs,
+) -> Result<(Vec<PredImg>, Vec<PredAudio>, Vec<PredVideo>), Box<dyn std::error::Error>> {
+    let media = batch::collect_media(&args.path, args.recursive)?;
+    Ok((
+        media
+            .images
+            .into_iter()
+            .map(|path| {
+                let mut img = PredImg::new_simple(path);
+                img.load_metadata();
+                img
+            })
+            .collect(),
+        media.audios.into_iter().map(PredAudio::new_simple).collect(),
+        media.videos.into_iter().map(PredVideo::new_simple).collect(),
+    ))
+}
+
+/// Returns the output path and how many rows (or events) were written.
+fn export_csv(args: &ExportArgs) -> Result<(PathBuf, usize), Box<dyn std::error::Error>> {
+    let (imgs, audios, videos) = load_preds(args)?;
+    if let Some(config) = args.event_config() {
+        let path = args
+            .output
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| export::prepare_export_file("events", "csv"));
+        let n_events = events::write_events_csv(&path, &imgs, &config)?;
+        return Ok((path, n_events));
+    }
+    let path = args
+        .output
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| export::prepare_export_file("predictions", "csv"));
+    let preds = imgs
+        .iter()
+        .map(|p| p as &dyn ToRows)
+        .chain(audios.iter().map(|p| p as &dyn ToRows))
+        .chain(videos.iter().map(|p| p as &dyn ToRows));
+    let n_rows = tabular::write_csv(&path, preds)?;
+    Ok((path, n_rows))
+}
+
+/// Directory the exported/imported paths are relative to.
+fn media_root(path: &str) -> Option<&Path> {
+    let path = Path::new(path);
+    if path.is_dir() { Some(path) } else { path.parent() }
+}
+
+fn export_coco(args: &ExportArgs) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let (imgs, _, _) = load_preds(args)?;
+    let dataset = coco::to_coco(&imgs, media_root(&args.path))?;
+    let path = args
+        .output
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| export::prepare_export_file("instances", "json"));
+    coco::write_coco(&path, &dataset)?;
+    Ok(path)
+}
+
+fn export_camtrap_dp(args: &ExportArgs) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let (imgs, _, videos) = load_preds(args)?;
+    let dir = args
+        .output
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| export::prepare_export_dir("camtrap_dp"));
+    camtrap::write_camtrap_dp(
+        &dir,
+        &imgs,
+        &videos,
+        media_root(&args.path),
+        args.event_config().as_ref(),
+    )?;
+    Ok(dir)
+}
+
+enum MapFormat {
+    GeoJson,
+    Kml,
+}
+
+/// Returns the output path and how many deployments have a position.
+fn export_map(args: &ExportArgs, format: MapFormat) -> Result<(PathBuf, usize), Box<dyn std::error::Error>> {
+    let (imgs, _, videos) = load_preds(args)?;
+    let deployments = deployment::summarize(&imgs, &videos, media_root(&args.path));
+    let located = deployments.iter().filter(|d| d.latitude.is_some() && d.longitude.is_some()).count();
+    if located < deployments.len() {
+        println!(
+            "{} of {} deployments have no location; set one with `boquilahub locate`",
+            deployments.len() - located,
+            deployments.len()
+        );
+    }
+    let ext = match format {
+        MapFormat::GeoJson => "geojson",
+        MapFormat::Kml => "kml",
+    };
+    let path = args
+        .output
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| export::prepare_export_file("deployments", ext));
+    match format {
+        MapFormat::GeoJson => geojson::write_geojson(&path, &deployment::to_geojson(&deployments))?,
+        MapFormat::Kml => deployment::write_kml(&path, &deployments)?,
+    }
+    Ok((path, located))
+}
+
+fn locate(args: &LocateArgs) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = Path::new(&args.path);
+    if !dir.is_dir() {
+        return Err(format!("'{}' is not a folder", args.path).into());
+    }
+    if !(-90.0..=90.0).contains(&args.lat) || !(-180.0..=180.0).contains(&args.lon) {
+        return Err("Latitude must be within ±90 and longitude within ±180".into());
+    }
+    let location = DeploymentLocation {
+        name: args.name.clone(),
+        latitude: args.lat,
+        longitude: args.lon,
+    };
+    Ok(location.write(dir)?)
+}
+
+/// Returns the GeoJSON path and how many detections it holds.
+fn process_geotiff(args: &GeotiffArgs) -> Result<(PathBuf, usize), Box<dyn std::error::Error>> {
+    let ep = Ep::from_name(&args.ep)
+        .ok_or_else(|| format!("Unknown execution provider '{}'", args.ep))?;
+    if PipelineConfig::is_pipeline_path(&args.model) {
+        Pipeline::set_active(Some(Pipeline::from_file(&args.model, ep)?));
+    } else {
+        let ais: Vec<AIMetadata> = BQModel::get_list();
+        let model = resolve_model(&args.model, &ais);
+        let config = ModelConfig {
+            tiling: Some(TileConfig {
+                size: args.tile,
+                overlap: args.overlap,
+                ..Default::default()
+            }),
+            ..BQModel::load_config(model.get_path()).unwrap_or_default()
+        };
+        GlobalBQ::First.set_model(&model.get_path(), ep, Some(config))?;
+    }
+
+    let (tif, aioutput) = geotiff::process_geotiff(&args.path, args.window, args.overlap, |done, total| {
+        println!("[{}/{}] windows", done, total);
+    })?;
+    let collection = geotiff::to_geojson(&aioutput, &tif.transform, tif.epsg);
+    let path = args
+        .output
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| export::prepare_export_file("detections", "geojson"));
+    geojson::write_geojson(&path, &collection)?;
+    Ok((path, collection.features.len()))
+}
+
+fn triage(args: &TriageArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mode = if args.dry_run {
+        TriageMode::DryRun
+    } else {
+        TriageMode::from_name(&args.mode).ok_or_else(|| format!("Unknown mode '{}'", args.mode))?
+    };
+    let mut config = TriageConfig {
+        default_threshold: args.threshold,
+        ..Default::default()
+    };
+    for spec in &args.label_thresholds {
+        let (label, threshold) = TriageConfig::parse_threshold(spec)?;
+        config.thresholds.insert(label, threshold);
+    }
+
+    let root = media_root(&args.path).map(Path::to_path_buf).unwrap_or_default();
+    let out_dir = match &args.output {
+        Some(dir) => PathBuf::from(dir),
+        None => root.join(triage::OUTPUT_FOLDER),
+    };
+    // A recursive walk would otherwise pick up what an earlier run sorted.
+    let media = batch::collect_media(&args.path, args.recursive)?;
+    let imgs: Vec<PredImg> = media
+        .images
+        .into_iter()
+        .filter(|path| !triage::in_output(path, &root, &out_dir))
+        .map(PredImg::new_simple)
+        .collect();
+    let moves = triage::plan(&imgs, &out_dir, &config);
+    if mode == TriageMode::DryRun {
+        for m in &moves {
+            println!("{} -> {}", m.from.display(), m.to.display());
+        }
+    }
+    let n_done = triage::apply(&moves, mode, |_| {})?;
+
+    for (folder, n) in triage::counts(&moves) {
+        println!("{:>8}  {}", n, folder);
+    }
+    let skipped = imgs.len() - moves.len();
+    if mode == TriageMode::DryRun {
+        println!("Dry run: {} files planned, {} without predictions", moves.len(), skipped);
+    } else {
+        println!("Done: {} files triaged, {} without predictions", n_done, skipped);
+    }
     Ok(())
 }
+
+fn import_coco(args: &ImportArgs) -> Result<usize, Box<dyn std::error::Error>> {
+    let dataset = coco::read_coco(&args.file)?;
+    let media = batch::collect_media(&args.path, args.recursive)?;
+    let mut imgs: Vec<PredImg> = media.images.into_iter().map(PredImg::new_simple).collect();
+    let updated = coco::apply_coco(&mut imgs, &dataset, media_root(&args.path))?;
+    for &i in &updated {
+        imgs[i].write_predictions()?;
+    }
+    Ok(updated.len())
+}
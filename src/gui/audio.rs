@@ -1,9 +1,11 @@
-use super::{imgbuf_to_texture, Gui, OpenDialog};
+use super::{imgbuf_to_texture, Gui, OpenDialog, RunModels};
 use crate::api::abstractions::*;
 use crate::api::audio::AudioData;
-use crate::api::bq::{process_audio, Modality};
+use crate::api::bq::Modality;
 use crate::api::processing::pre::compute_mel;
 use crate::api::render::*;
+use crate::api::export;
+use crate::api::tabular;
 use crate::api::rest::Payload;
 use crate::localization::*;
 use image::{ImageBuffer, Rgba};
@@ -104,6 +106,7 @@ impl Gui {
     pub fn audio_export_dialog(&mut self, ui: &egui::Ui) {
         let mut close = false;
         let mut export = false;
+        let mut export_csv = false;
         egui::Window::new(self.t(Key::export))
             .collapsible(false)
             .resizable(false)
@@ -112,6 +115,10 @@ impl Gui {
                     export = true;
                     close = true;
                 }
+                if ui.button(self.t(Key::export_csv)).clicked() {
+                    export_csv = true;
+                    close = true;
+                }
                 if ui.button(self.t(Key::cancel)).clicked() {
                     close = true;
                 }
@@ -135,6 +142,11 @@ impl Gui {
                 self.push_toast(super::Message::ok(msg));
             }
         }
+        if export_csv {
+            let path = export::prepare_export_file("predictions", "csv");
+            let result = tabular::write_csv_list(&path, &self.selected_audios);
+            self.export_done(&path, result);
+        }
         if close {
             self.dialog = OpenDialog::None;
         }
@@ -152,6 +164,7 @@ impl Gui {
 
         let rest_client = self.rest_client.clone();
         let is_remote = !self.ep_selected.is_local();
+        let models = RunModels::current();
         tokio::spawn(async move {
             if cancel_rx.try_recv().is_ok() {
                 return;
@@ -162,7 +175,7 @@ impl Gui {
             } else {
                 tokio::task::spawn_blocking(move || {
                     let audio = AudioData::from_file(&path).ok()?.to_mono();
-                    process_audio(&audio).ok()
+                    models.audio(&audio).ok()
                 })
                 .await
                 .ok()
@@ -183,6 +196,7 @@ impl Gui {
 
         let rest_client = self.rest_client.clone();
         let is_remote = !self.ep_selected.is_local();
+        let models = RunModels::current();
         tokio::spawn(async move {
             for (i, pred) in copy_preds.iter().enumerate() {
                 if pred.wasprocessed {
@@ -196,9 +210,10 @@ impl Gui {
                     let buffer = fs::read(&path).unwrap();
                     rest_client.as_ref().unwrap().detect(Payload::RawAudioBytes(buffer)).await.ok()
                 } else {
+                    let models = models.clone();
                     tokio::task::spawn_blocking(move || {
                         let audio = AudioData::from_file(&path).ok()?.to_mono();
-                        process_audio(&audio).ok()
+                        models.audio(&audio).ok()
                     })
                     .await
                     .ok()
@@ -297,12 +312,14 @@ impl Gui {
         let (updates, closed) = self.audio_state.drain();
         let current_idx = self.audio_texture_n.saturating_sub(1);
         let mut touched_current = false;
+        let provenance = self.current_provenance();
         for (i, result) in updates {
             match result {
                 Some(aio @ (AIOutputs::AudioClassification(_) | AIOutputs::ObjectDetection(_))) => {
                     if let Some(slot) = self.selected_audios.get_mut(i) {
                         slot.aioutput = Some(aio);
                         slot.wasprocessed = true;
+                        slot.provenance = provenance.clone();
                         if i == current_idx {
                             touched_current = true;
                         }
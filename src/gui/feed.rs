@@ -1,6 +1,5 @@
-use super::{imgbuf_to_texture, Gui, Mode, OpenDialog};
+use super::{imgbuf_to_texture, Gui, Mode, OpenDialog, RunModels};
 use crate::api::abstractions::*;
-use crate::api::bq::process_imgbuf;
 use crate::api::render::*;
 use crate::api::rest::rgb_image_to_jpeg_buffer;
 use crate::api::stream;
@@ -115,6 +114,7 @@ impl Gui {
 
         let rest_client = self.rest_client.clone();
         let is_remote = !self.ep_selected.is_local();
+        let models = RunModels::current();
         let has_ai = run_ai && self.can_run_image_ai();
         let step = self.feed_step_frame.max(1);
 
@@ -137,8 +137,9 @@ impl Gui {
                             Err(_) => break,
                         }
                     } else {
+                        let models = models.clone();
                         match tokio::task::spawn_blocking(move || {
-                            process_imgbuf(&img).map(|result| (img, result))
+                            models.imgbuf(&img).map(|result| (img, result))
                         })
                         .await
                         {
@@ -1,19 +1,35 @@
-use super::{imgbuf_to_texture, Gui, Mode, OpenDialog};
+use super::{imgbuf_to_texture, Gui, Mode, OpenDialog, RunModels};
 use crate::api::abstractions::*;
-use crate::api::bq::process_imgbuf;
+use crate::api::camtrap;
+use crate::api::coco;
+use crate::api::deployment;
+use crate::api::events::{self, EventLabel};
 use crate::api::export;
+use crate::api::geojson;
+use crate::api::metadata::ImageMetadata;
+use crate::api::models::MAX_BATCH;
+use crate::api::tabular;
+use crate::api::triage::{self, TriageMode};
 use crate::api::render::*;
 use crate::api::rest::Payload;
+use crate::api::utils;
 use crate::localization::*;
 use std::fs;
+use std::path::PathBuf;
 
 const MIN_PREVIEW_H: f32 = 240.0;
 
 impl Gui {
     // ---------- texture loading ----------
 
+    /// EXIF for every image, which exports and events need.
+    fn load_img_metadata(&mut self) {
+        self.selected_imgs.iter_mut().for_each(PredImg::load_metadata);
+    }
+
     pub(super) fn paint(&mut self, ui: &egui::Ui, i: usize) {
-        let Some(predimg) = self.selected_imgs.get(i) else { return; };
+        let Some(predimg) = self.selected_imgs.get_mut(i) else { return; };
+        predimg.load_metadata();
         let Ok(loaded) = image::open(&predimg.file_path) else { return; };
         let img = loaded.into_rgba8();
         self.img_state.texture = imgbuf_to_texture(&img, ui);
@@ -32,6 +48,7 @@ impl Gui {
 
         let rest_client = self.rest_client.clone();
         let is_remote = !self.ep_selected.is_local();
+        let models = RunModels::current();
         tokio::spawn(async move {
             if cancel_rx.try_recv().is_ok() {
                 return;
@@ -44,7 +61,7 @@ impl Gui {
                 }
             } else {
                 let img = image::open(&predimg.file_path).unwrap().into_rgb8();
-                match tokio::task::spawn_blocking(move || process_imgbuf(&img)).await {
+                match tokio::task::spawn_blocking(move || models.imgbuf(&img)).await {
                     Ok(Ok(result)) => Some(result),
                     _ => None,
                 }
@@ -65,30 +82,39 @@ impl Gui {
 
         let rest_client = self.rest_client.clone();
         let is_remote = !self.ep_selected.is_local();
+        let models = RunModels::current();
         tokio::spawn(async move {
-            for (i, predimg) in copy_predigms.iter().enumerate() {
-                if predimg.wasprocessed {
-                    continue;
-                }
+            let pending: Vec<(usize, PathBuf)> = copy_predigms
+                .iter()
+                .enumerate()
+                .filter(|(_, predimg)| !predimg.wasprocessed)
+                .map(|(i, predimg)| (i, predimg.file_path.clone()))
+                .collect();
+            // Local models take a batch per call; the server one image per request.
+            let chunk_size = if is_remote { 1 } else { MAX_BATCH };
+            for chunk in pending.chunks(chunk_size) {
                 if cancel_rx.try_recv().is_ok() {
                     break;
                 }
-                let result = if is_remote {
-                    let buffer = fs::read(&predimg.file_path).unwrap();
+                let results = if is_remote {
+                    let buffer = fs::read(&chunk[0].1).unwrap();
                     match rest_client.as_ref().unwrap().detect(Payload::RawImageBytes(buffer)).await {
-                        Ok(result) => Some(result),
-                        Err(_) => None,
+                        Ok(result) => vec![Some(result)],
+                        Err(_) => vec![None],
                     }
                 } else {
-                    let img = image::open(&predimg.file_path).unwrap().into_rgb8();
-                    match tokio::task::spawn_blocking(move || process_imgbuf(&img)).await {
-                        Ok(Ok(result)) => Some(result),
-                        _ => None,
+                    let paths: Vec<PathBuf> = chunk.iter().map(|(_, path)| path.clone()).collect();
+                    let models = models.clone();
+                    match tokio::task::spawn_blocking(move || models.image_files(&paths)).await {
+                        Ok(results) => results,
+                        _ => vec![None; chunk.len()],
                     }
                 };
 
-                if tx.send((i, result)).is_err() {
-                    break;
+                for (&(i, _), result) in chunk.iter().zip(results) {
+                    if tx.send((i, result)).is_err() {
+                        return;
+                    }
                 }
             }
         });
@@ -96,11 +122,13 @@ impl Gui {
 
     pub(super) fn img_handle_results(&mut self, ui: &egui::Ui) {
         let (updates, closed) = self.img_state.drain();
+        let provenance = self.current_provenance();
         for (i, result) in updates {
             match result {
                 Some(aio) => {
                     self.selected_imgs[i].aioutput = Some(aio);
                     self.selected_imgs[i].wasprocessed = true;
+                    self.selected_imgs[i].provenance = provenance.clone();
                     if i == self.image_texture_n - 1 {
                         self.paint(ui, i);
                     }
@@ -164,14 +192,148 @@ impl Gui {
                     .add_sized([85.0, 40.0], egui::Button::new(self.t(Key::export)))
                     .clicked()
                 {
+                    self.load_img_metadata();
                     self.dialog = OpenDialog::Export;
                 }
+                if ui
+                    .add_enabled(
+                        self.selected_imgs.count_processed() > 0,
+                        egui::Button::new(self.t(Key::triage)).min_size(egui::vec2(85.0, 40.0)),
+                    )
+                    .clicked()
+                {
+                    self.triage_preview = None;
+                    self.dialog = OpenDialog::Triage;
+                }
             });
         }
 
         self.process_all_dialog(ui, |gui, process_all| gui.process_all_imgs = process_all, Gui::start_img_analysis);
     }
 
+    pub fn img_triage_dialog(&mut self, ui: &mut egui::Ui) {
+        // Labels the current predictions would sort into, for per-label thresholds.
+        let mut labels: Vec<String> = self
+            .selected_imgs
+            .iter()
+            .filter(|p| p.wasprocessed)
+            .filter_map(|p| p.aioutput.as_ref()?.dominant_prob().map(|(_, label, _)| label.to_string()))
+            .collect();
+        labels.sort();
+        labels.dedup();
+
+        let mut close = false;
+        let mut run = false;
+        egui::Window::new(self.t(Key::triage))
+            .collapsible(false)
+            .resizable(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (mode, key) in [
+                        (TriageMode::Copy, Key::copy_files),
+                        (TriageMode::Move, Key::move_files),
+                        (TriageMode::Symlink, Key::link_files),
+                        (TriageMode::DryRun, Key::dry_run),
+                    ] {
+                        ui.radio_value(&mut self.triage_mode, mode, translate(key, &self.lang));
+                    }
+                });
+                ui.separator();
+
+                ui.label(translate(Key::confidence_level, &self.lang));
+                ui.add(egui::Slider::new(&mut self.triage_config.default_threshold, 0.0..=1.0));
+                egui::Grid::new("triage_thresholds").num_columns(2).show(ui, |ui| {
+                    for label in &labels {
+                        let mut threshold = self.triage_config.threshold(label);
+                        ui.label(label);
+                        if ui.add(egui::Slider::new(&mut threshold, 0.0..=1.0)).changed() {
+                            self.triage_config.thresholds.insert(label.clone(), threshold);
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                // Live preview of the folder sizes; the filesystem is only
+                // touched when running.
+                let mut counts: Vec<(String, usize)> = Vec::new();
+                for folder in self.selected_imgs.iter().filter_map(|p| self.triage_config.folder_for(p)) {
+                    match counts.iter_mut().find(|(f, _)| *f == folder) {
+                        Some((_, n)) => *n += 1,
+                        None => counts.push((folder, 1)),
+                    }
+                }
+                counts.sort();
+                for (folder, n) in &counts {
+                    ui.label(format!("{}/  {}", folder, n));
+                }
+
+                if let Some(preview) = &self.triage_preview {
+                    ui.separator();
+                    egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                        for m in preview {
+                            ui.label(
+                                egui::RichText::new(format!("{} → {}", m.from.display(), m.to.display()))
+                                    .small()
+                                    .monospace(),
+                            );
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(translate(Key::triage, &self.lang)).clicked() {
+                        run = true;
+                    }
+                    if ui.button(translate(Key::cancel, &self.lang)).clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if run {
+            let root = utils::common_root(self.selected_imgs.iter().map(|p| p.file_path.as_path()))
+                .unwrap_or_default();
+            let out_dir = root.join(triage::OUTPUT_FOLDER);
+            let to_sort: Vec<PredImg> = self
+                .selected_imgs
+                .iter()
+                .filter(|p| !triage::in_output(&p.file_path, &root, &out_dir))
+                .cloned()
+                .collect();
+            let moves = triage::plan(&to_sort, &out_dir, &self.triage_config);
+            if self.triage_mode == TriageMode::DryRun {
+                self.triage_preview = Some(moves);
+                return;
+            }
+            let mode = self.triage_mode;
+            let imgs = &mut self.selected_imgs;
+            // Keep the list pointing at the files' new homes, even if a later
+            // file fails.
+            let result = triage::apply(&moves, mode, |m| {
+                if mode != TriageMode::Move {
+                    return;
+                }
+                if let Some(p) = imgs.iter_mut().find(|p| p.file_path == m.from) {
+                    p.file_path = m.to.clone();
+                }
+            });
+            match result {
+                Ok(n) => {
+                    let msg = format!("{}: {}", self.t(Key::triaged_files), n);
+                    self.push_toast(super::Message::ok(msg));
+                }
+                Err(e) => self.push_toast(super::Message::Failed(e.to_string())),
+            }
+            close = true;
+        }
+        if close {
+            self.triage_preview = None;
+            self.dialog = OpenDialog::None;
+        }
+    }
+
     pub fn img_export_dialog(&mut self, ui: &mut egui::Ui) {
         egui::Window::new(self.t(Key::export))
             .collapsible(false)
@@ -188,6 +350,81 @@ impl Gui {
                     self.dialog = OpenDialog::None;
                 }
 
+                if ui.button(self.t(Key::export_csv)).clicked() {
+                    let path = export::prepare_export_file("predictions", "csv");
+                    let result = tabular::write_csv_list(&path, &self.selected_imgs);
+                    self.export_done(&path, result);
+                    self.dialog = OpenDialog::None;
+                }
+
+                if ui.button(self.t(Key::export_coco)).clicked() {
+                    let path = export::prepare_export_file("instances", "json");
+                    let root = utils::common_root(self.selected_imgs.iter().map(|p| p.file_path.as_path()));
+                    let result = coco::to_coco(&self.selected_imgs, root.as_deref())
+                        .and_then(|dataset| coco::write_coco(&path, &dataset));
+                    self.export_done(&path, result);
+                    self.dialog = OpenDialog::None;
+                }
+
+                if ui.button(self.t(Key::export_events_csv)).clicked() {
+                    let path = export::prepare_export_file("events", "csv");
+                    let result = events::write_events_csv(&path, &self.selected_imgs, &self.event_config);
+                    self.export_done(&path, result);
+                    self.dialog = OpenDialog::None;
+                }
+
+                if ui.button(self.t(Key::export_camtrap_dp)).clicked() {
+                    let dir = export::prepare_export_dir("camtrap_dp");
+                    let root = utils::common_root(self.selected_imgs.iter().map(|p| p.file_path.as_path()));
+                    let events = self.img_events.is_some().then_some(&self.event_config);
+                    let result = camtrap::write_camtrap_dp(&dir, &self.selected_imgs, &[], root.as_deref(), events);
+                    self.export_done(&dir, result);
+                    self.dialog = OpenDialog::None;
+                }
+
+                if ui.button(self.t(Key::export_geojson)).clicked() {
+                    let path = export::prepare_export_file("deployments", "geojson");
+                    let root = utils::common_root(self.selected_imgs.iter().map(|p| p.file_path.as_path()));
+                    let deployments = deployment::summarize(&self.selected_imgs, &[], root.as_deref());
+                    let result = geojson::write_geojson(&path, &deployment::to_geojson(&deployments));
+                    self.export_done(&path, result);
+                    self.dialog = OpenDialog::None;
+                }
+
+                if ui.button(self.t(Key::export_kml)).clicked() {
+                    let path = export::prepare_export_file("deployments", "kml");
+                    let root = utils::common_root(self.selected_imgs.iter().map(|p| p.file_path.as_path()));
+                    let deployments = deployment::summarize(&self.selected_imgs, &[], root.as_deref());
+                    let result = deployment::write_kml(&path, &deployments);
+                    self.export_done(&path, result);
+                    self.dialog = OpenDialog::None;
+                }
+
+                if ui.button(self.t(Key::import_coco)).clicked() {
+                    if let Some(file) = rfd::FileDialog::new().add_filter("COCO", &["json"]).pick_file() {
+                        let root = utils::common_root(self.selected_imgs.iter().map(|p| p.file_path.as_path()));
+                        let imported = coco::read_coco(&file)
+                            .and_then(|dataset| coco::apply_coco(&mut self.selected_imgs, &dataset, root.as_deref()))
+                            .and_then(|updated| {
+                                for &i in &updated {
+                                    self.selected_imgs[i].write_predictions()?;
+                                }
+                                Ok(updated)
+                            });
+                        match imported {
+                            Ok(updated) => {
+                                if !updated.is_empty() {
+                                    self.paint(ui, self.image_texture_n - 1);
+                                }
+                                let msg = format!("{}: {}", self.t(Key::imported_annotations), updated.len());
+                                self.push_toast(super::Message::ok(msg));
+                            }
+                            Err(e) => self.push_toast(super::Message::Failed(e.to_string())),
+                        }
+                    }
+                    self.dialog = OpenDialog::None;
+                }
+
                 if ui
                     .button(self.t(Key::export_imgs_with_predictions))
                     .clicked()
@@ -264,6 +501,10 @@ impl Gui {
         let mut new_index = self.image_texture_n;
         let can_analyze = self.can_run_image_ai();
         let mut analyze_this = false;
+        let mut grouped = self.img_events.is_some();
+        let mut gap_s = self.event_config.gap_s;
+        let mut vote = self.event_config.label == EventLabel::Vote;
+        let mut jump_to: Option<usize> = None;
 
         ui.horizontal_wrapped(|ui| {
             super::nav_prev_next(
@@ -281,6 +522,18 @@ impl Gui {
                 .unwrap_or(self.t(Key::unknown_file));
             super::nav_filename(ui, name, new_index, n);
 
+            if let Some(metadata) = &predimg.metadata {
+                let summary = metadata
+                    .captured_at
+                    .as_deref()
+                    .map(|s| s.replacen('T', " ", 1))
+                    .or_else(|| metadata.camera())
+                    .unwrap_or_default();
+                ui.separator();
+                ui.label(egui::RichText::new(format!("📷 {}", summary)).weak().small())
+                    .on_hover_ui(|ui| metadata_tooltip_ui(ui, metadata, &self.lang));
+            }
+
             if !predimg.wasprocessed {
                 ui.separator();
                 ui.label(
@@ -300,8 +553,52 @@ impl Gui {
                     analyze_this = true;
                 }
             }
+
+            ui.separator();
+            ui.checkbox(&mut grouped, self.t(Key::group_events));
+            if let Some(events) = &self.img_events {
+                ui.add(egui::DragValue::new(&mut gap_s).range(1..=86_400).suffix(" s"));
+                ui.checkbox(&mut vote, self.t(Key::vote));
+                if let Some(e) = events::event_of(events, new_index - 1) {
+                    let event = &events[e];
+                    let summary = event.summarize(&self.selected_imgs, self.event_config.label);
+                    ui.separator();
+                    if ui.small_button("⏮").on_hover_text(self.t(Key::prev)).clicked() && e > 0 {
+                        jump_to = Some(events[e - 1].members[0]);
+                    }
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} {} / {} · {} 🖼",
+                            self.t(Key::event),
+                            e + 1,
+                            events.len(),
+                            event.members.len(),
+                        ))
+                        .small(),
+                    );
+                    if let Some(label) = &summary.label {
+                        ui.label(egui::RichText::new(format!("{} ×{}", label, summary.max_count)).strong().small());
+                    }
+                    if ui.small_button("⏭").on_hover_text(self.t(Key::next)).clicked() && e + 1 < events.len() {
+                        jump_to = Some(events[e + 1].members[0]);
+                    }
+                }
+            }
         });
 
+        self.event_config.label = if vote { EventLabel::Vote } else { EventLabel::MaxConfidence };
+        let regroup = grouped && (self.img_events.is_none() || gap_s != self.event_config.gap_s);
+        self.event_config.gap_s = gap_s;
+        if !grouped {
+            self.img_events = None;
+        } else if regroup {
+            self.load_img_metadata();
+            self.img_events = Some(events::group_events(&self.selected_imgs, gap_s));
+        }
+        if let Some(idx) = jump_to {
+            new_index = idx + 1;
+        }
+
         super::nav_slider(ui, &mut new_index, n);
 
         if new_index != self.image_texture_n {
@@ -949,6 +1246,35 @@ fn bbox_tooltip_ui(ui: &mut egui::Ui, detection: &XYXYc, lang: &Lang) {
     refined_extras_ui(ui, detection.extra_cls.as_ref(), lang);
 }
 
+fn metadata_tooltip_ui(ui: &mut egui::Ui, metadata: &ImageMetadata, lang: &Lang) {
+    let location = metadata.latitude.zip(metadata.longitude).map(|(lat, lon)| match metadata.altitude {
+        Some(alt) => format!("{:.5}, {:.5} · {:.0} m", lat, lon, alt),
+        None => format!("{:.5}, {:.5}", lat, lon),
+    });
+    let burst = metadata.sequence.map(|(i, n)| format!("{} / {}", i, n));
+    let trigger = match (&metadata.trigger, metadata.temperature_c) {
+        (Some(t), Some(c)) => Some(format!("{} · {} °C", t, c)),
+        (Some(t), None) => Some(t.clone()),
+        _ => None,
+    };
+    egui::Grid::new("image_metadata").num_columns(2).show(ui, |ui| {
+        for (key, value) in [
+            (Key::captured, metadata.captured_at.clone()),
+            (Key::camera, metadata.camera()),
+            (Key::serial_number, metadata.serial_number.clone()),
+            (Key::location, location),
+            (Key::trigger, trigger),
+            (Key::burst, burst),
+        ] {
+            if let Some(value) = value {
+                ui.label(egui::RichText::new(translate(key, lang)).weak());
+                ui.label(value);
+                ui.end_row();
+            }
+        }
+    });
+}
+
 fn seg_tooltip_ui(ui: &mut egui::Ui, segment: &SEGc, lang: &Lang) {
     let color = super::class_color32(bbox_color_id(&segment.bbox));
     ui.horizontal(|ui| {
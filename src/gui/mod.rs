@@ -4,16 +4,21 @@ mod feed;
 mod image_view;
 mod video_file;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use super::{api::*, localization::*};
 use abstractions::*;
 use crate::api::audio::AudioData;
 use bq::*;
+use events::{Event, EventConfig};
+use batch::run_image_files;
 use models::Task;
+use triage::{TriageConfig, TriageMode, TriageMove};
 use processing::post::PostProcessing;
+use pipeline::{Pipeline, PipelineConfig};
+use registry::ModelRegistry;
 use render::*;
 use rest::{get_ipv4_address, Rest};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::{self};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -31,13 +36,28 @@ struct AiConfigSlot {
     show: bool,
     config: ModelConfig,
     temp: ModelConfig,
+    class_filter: String,
 }
 
 impl AiConfigSlot {
-    fn window(&mut self, ui: &mut egui::Ui, lang: &Lang, variant: GlobalBQ, current_ai: &AIMetadata) {
+    /// Starts from the settings saved for the model at `model_path`, else
+    /// keeps the general ones and drops the previous model's class settings.
+    fn load_saved(&mut self, model_path: &str) {
+        self.config = BQModel::load_config(model_path).unwrap_or_else(|| ModelConfig {
+            class_thresholds: BTreeMap::new(),
+            include_classes: Vec::new(),
+            exclude_classes: Vec::new(),
+            ..self.config.clone()
+        });
+        self.temp = self.config.clone();
+    }
+
+    /// Errors when the settings could not be saved next to the model.
+    fn window(&mut self, ui: &mut egui::Ui, lang: &Lang, variant: GlobalBQ, current_ai: &AIMetadata) -> Result<()> {
         if !self.show {
-            return;
+            return Ok(());
         }
+        let mut saved = Ok(());
         egui::Window::new(translate(Key::configure_ai, lang))
             .collapsible(false)
             .resizable(false)
@@ -46,7 +66,7 @@ impl AiConfigSlot {
                     egui::Slider::new(&mut self.temp.confidence_threshold, 0.10..=0.99)
                         .text(translate(Key::confidence_level, lang)),
                 );
-                if current_ai.post_processing.contains(&PostProcessing::NMS) {
+                if current_ai.post_processing.iter().any(PostProcessing::merges_boxes) {
                     ui.add(
                         egui::Slider::new(&mut self.temp.nms_threshold, 0.10..=0.99)
                             .text(translate(Key::overlap_filter, lang)),
@@ -62,10 +82,49 @@ impl AiConfigSlot {
                             }
                         });
                 }
+                if current_ai.modality == Modality::Image && matches!(current_ai.task, Task::Detect | Task::Segment) {
+                    let mut tiled = self.temp.tiling.is_some();
+                    if ui.checkbox(&mut tiled, translate(Key::tiled_inference, lang)).changed() {
+                        self.temp.tiling = tiled.then(TileConfig::default);
+                    }
+                    if let Some(tiling) = &mut self.temp.tiling {
+                        ui.add(egui::Slider::new(&mut tiling.size, 256..=2048).text(translate(Key::tile_size, lang)));
+                        ui.add(egui::Slider::new(&mut tiling.overlap, 0.0..=0.5).text(translate(Key::tile_overlap, lang)));
+                    }
+                }
+                if current_ai.modality == Modality::Image && current_ai.task != Task::Embed {
+                    let mut tta = self.temp.tta.is_some();
+                    if ui.checkbox(&mut tta, translate(Key::test_time_augmentation, lang)).changed() {
+                        self.temp.tta = tta.then(TtaConfig::default);
+                    }
+                }
+                if current_ai.task != Task::Embed && !current_ai.classes.is_empty() {
+                    egui::CollapsingHeader::new(translate(Key::per_class_settings, lang)).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("🔍");
+                            ui.text_edit_singleline(&mut self.class_filter);
+                        });
+                        let filter = self.class_filter.to_lowercase();
+                        let classes: Vec<&String> = current_ai
+                            .classes
+                            .iter()
+                            .filter(|class| class.to_lowercase().contains(&filter))
+                            .collect();
+                        let row_height = ui.spacing().interact_size.y;
+                        egui::ScrollArea::vertical()
+                            .max_height(240.0)
+                            .show_rows(ui, row_height, classes.len(), |ui, rows| {
+                                for class in &classes[rows] {
+                                    ui.horizontal(|ui| class_row(ui, &mut self.temp, class, lang));
+                                }
+                            });
+                    });
+                }
                 ui.horizontal(|ui| {
                     if ui.button(translate(Key::ok, lang)).clicked() {
                         self.config = self.temp.clone();
                         variant.update_config(self.config.clone());
+                        saved = BQModel::save_config(current_ai.get_path(), &self.config);
                         self.show = false;
                     }
                     ui.add_space(8.0);
@@ -75,6 +134,33 @@ impl AiConfigSlot {
                     }
                 });
             });
+        saved
+    }
+}
+
+/// Show/hide toggle and optional own threshold for one class.
+fn class_row(ui: &mut egui::Ui, config: &mut ModelConfig, class: &str, lang: &Lang) {
+    let mut shown = config.allows(class);
+    if ui.checkbox(&mut shown, class).changed() {
+        if shown {
+            config.exclude_classes.retain(|c| c != class);
+            if !config.include_classes.is_empty() && !config.include_classes.iter().any(|c| c == class) {
+                config.include_classes.push(class.to_owned());
+            }
+        } else {
+            config.exclude_classes.push(class.to_owned());
+        }
+    }
+    let mut own = config.class_thresholds.contains_key(class);
+    if ui.checkbox(&mut own, translate(Key::own_threshold, lang)).changed() {
+        if own {
+            config.class_thresholds.insert(class.to_owned(), config.confidence_threshold);
+        } else {
+            config.class_thresholds.remove(class);
+        }
+    }
+    if let Some(threshold) = config.class_thresholds.get_mut(class) {
+        ui.add(egui::Slider::new(threshold, 0.01..=0.99));
     }
 }
 
@@ -84,6 +170,14 @@ pub struct Gui {
     ais: Vec<AIMetadata>,
     ais_cls_only: Vec<AIMetadata>,
     selected_imgs: Vec<PredImg>,
+    // `Some` while the image view is grouped into events; membership only,
+    // labels are summarised on the fly for the event on screen.
+    img_events: Option<Vec<Event>>,
+    event_config: EventConfig,
+    triage_config: TriageConfig,
+    triage_mode: TriageMode,
+    // Planned moves shown after a dry run.
+    triage_preview: Option<Vec<TriageMove>>,
     selected_audios: Vec<PredAudio>,
     // AudioData is heavy (hours of float samples). We only keep it for the
     // currently displayed audio file — switching invalidates and reloads.
@@ -133,6 +227,9 @@ pub struct Gui {
     ai: AiConfigSlot,
     ai_cls: AiConfigSlot,
 
+    /// Pipeline file driving local image runs instead of the two AI slots.
+    pipeline_file: Option<PathBuf>,
+
     // usize and Option<usize> fields grouped together (8 bytes each on 64-bit)
     ai_selected: Option<usize>,
     ai_cls_selected: Option<usize>,
@@ -263,6 +360,8 @@ struct Temp {
 enum Message {
     Success(String),
     Error,
+    /// An error worth showing the details of.
+    Failed(String),
 }
 
 impl Message {
@@ -291,6 +390,7 @@ enum OpenDialog {
     None,
     ProcessAll,
     Export,
+    Triage,
     FeedUrl,
     ApiServer,
 }
@@ -377,6 +477,14 @@ impl Gui {
         self.push_toast(Message::ok(str));
     }
 
+    /// Toast for a finished whole-project export (CSV, COCO…).
+    fn export_done<T>(&mut self, path: &std::path::Path, result: Result<T>) {
+        match result {
+            Ok(_) => self.process_done_at(path.display().to_string()),
+            Err(e) => self.push_toast(Message::Failed(e.to_string())),
+        }
+    }
+
     fn t(&self, key: Key) -> &'static str {
         translate(key, &self.lang)
     }
@@ -385,6 +493,16 @@ impl Gui {
         return &self.ais[self.ai_selected.unwrap()];
     }
 
+    /// Provenance for results that arrive now. Unknown in remote mode: the
+    /// server's model isn't the one loaded here.
+    fn current_provenance(&self) -> Option<Provenance> {
+        if self.ep_selected.is_local() {
+            run_provenance()
+        } else {
+            None
+        }
+    }
+
     fn current_ai_cls(&self) -> &AIMetadata {
         return &self.ais_cls_only[self.ai_cls_selected.unwrap()];
     }
@@ -411,6 +529,7 @@ impl Gui {
                     match &toast.msg {
                         Message::Success(str) => {ui.label(str);},
                         Message::Error => {ui.label(self.t(Key::error_ocurred));}
+                        Message::Failed(detail) => {ui.label(format!("❌ {}: {}", self.t(Key::error_ocurred), detail));}
                     }
                 });
                 ui.request_repaint();
@@ -463,6 +582,7 @@ impl Gui {
             OpenDialog::ApiServer => {self.input_api_url_dialog(ui)},
             OpenDialog::FeedUrl => {self.feed_input_dialog(ui);},
             OpenDialog::ProcessAll => {},
+            OpenDialog::Triage => {self.img_triage_dialog(ui);},
             OpenDialog::Export => {
                 match self.mode {
                     Mode::Image => {self.img_export_dialog(ui);},
@@ -481,6 +601,8 @@ impl Gui {
         }
         
         let previous_ai = self.ai_selected;
+        let mut load_pipeline = false;
+        let mut unload_pipeline = false;
         ui.label(self.t(Key::select_ai));
 
         ui.horizontal(|ui| {
@@ -500,6 +622,24 @@ impl Gui {
                     });
             });
 
+            if let Some(file) = &self.pipeline_file {
+                let name = file.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                ui.label(format!("🔗 {}", name)).on_hover_ui(|ui| {
+                    ui.strong(self.t(Key::pipeline));
+                    for (i, stage) in Pipeline::active().iter().flat_map(|p| p.stages()).enumerate() {
+                        let mut line = format!("{}. {}", i + 1, stage.model.metadata().name);
+                        if !stage.crop.labels.is_empty() {
+                            line.push_str(&format!(" ({})", stage.crop.labels.join(", ")));
+                        }
+                        ui.label(line);
+                    }
+                });
+                if ui.button("✖").clicked() {
+                    unload_pipeline = true;
+                }
+                return;
+            }
+
             if self.ai_selected.is_some() {
                 if ui
                     .button("⚙")
@@ -524,9 +664,16 @@ impl Gui {
                     }
                 }
             }
+
+            if ui.button("🔗").on_hover_text(self.t(Key::load_pipeline)).clicked() {
+                load_pipeline = true;
+            }
         });
 
         if (self.ai_selected != previous_ai) && (self.ai_selected.is_some()) {
+            if self.pipeline_file.take().is_some() {
+                Pipeline::set_active(None);
+            }
             if self.is_audio_model() {
                 self.show_ai_cls = false;
                 self.ai_cls_selected = None;
@@ -537,6 +684,7 @@ impl Gui {
                 self.audio_state.texture = None;
             }
             let model_path = self.ais[self.ai_selected.unwrap()].get_path();
+            self.ai.load_saved(&model_path);
             if GlobalBQ::First.set_model(
                 &model_path,
                 self.ep_selected,
@@ -548,13 +696,48 @@ impl Gui {
 
         if self.ai.show {
             let current_ai = self.current_ai().clone();
-            self.ai.window(ui, &self.lang, GlobalBQ::First, &current_ai);
+            if self.ai.window(ui, &self.lang, GlobalBQ::First, &current_ai).is_err() {
+                self.push_toast(Message::Error);
+            }
         }
 
+        if load_pipeline {
+            if let Some(path) = rfd::FileDialog::new().add_filter("Pipeline", &["toml", "json"]).pick_file() {
+                if self.set_pipeline(&path, self.ep_selected).is_err() {
+                    self.push_toast(Message::Error);
+                }
+            }
+        }
+        if unload_pipeline {
+            self.pipeline_file = None;
+            Pipeline::set_active(None);
+            if self.set_ai(self.ep_selected).is_err() {
+                self.push_toast(Message::Error);
+            }
+        }
+
+        self.loaded_models_ui(ui);
+
         ui.add_space(8.0);
     
     }
 
+    /// Memory held by loaded models, listed per model on hover.
+    fn loaded_models_ui(&self, ui: &mut egui::Ui) {
+        let models = ModelRegistry::list();
+        if models.is_empty() {
+            return;
+        }
+        let mb = |bytes: usize| bytes as f64 / 1_048_576.0;
+        ui.weak(format!("🧠 {:.1} MB", mb(ModelRegistry::memory_bytes())))
+            .on_hover_ui(|ui| {
+                ui.strong(self.t(Key::loaded_models));
+                for model in &models {
+                    ui.label(format!("{} ({}): {:.1} MB", model.metadata().name, model.ep().name(), mb(model.memory_bytes())));
+                }
+            });
+    }
+
     fn ai_cls_widget(&mut self, ui: &mut egui::Ui) {
         if !(self.ep_selected.is_local() && self.show_ai_cls) {
             return;
@@ -598,6 +781,7 @@ impl Gui {
         });
         if (self.ai_cls_selected != previous_ai) && (self.ai_cls_selected.is_some()) {
             let model_path = self.ais_cls_only[self.ai_cls_selected.unwrap()].get_path();
+            self.ai_cls.load_saved(&model_path);
             if GlobalBQ::Second.set_model(
                 &model_path,
                 self.ep_selected,
@@ -609,14 +793,41 @@ impl Gui {
 
         if self.ai_cls.show {
             let current_ai_cls = self.current_ai_cls().clone();
-            self.ai_cls.window(ui, &self.lang, GlobalBQ::Second, &current_ai_cls);
+            if self.ai_cls.window(ui, &self.lang, GlobalBQ::Second, &current_ai_cls).is_err() {
+                self.push_toast(Message::Error);
+            }
         }
 
         ui.add_space(8.0);
         
     }
 
+    /// Loads the pipeline at `path` and selects its first stage's model, which
+    /// has to be one of `self.ais`. The two AI slots are emptied meanwhile.
+    fn set_pipeline(&mut self, path: &std::path::Path, ep: Ep) -> Result<()> {
+        let config = PipelineConfig::from_file(path)?;
+        let first = config.stages[0].model.trim_end_matches(".bq");
+        let first = first.rsplit(['/', '\\']).next().unwrap_or(first);
+        let index = self
+            .ais
+            .iter()
+            .position(|ai| ai.name == first)
+            .with_context(|| format!("'{}' is not in the models folder", first))?;
+        let pipeline = Pipeline::load(&config, path.parent().unwrap_or(std::path::Path::new(".")), ep)?;
+        Pipeline::set_active(Some(pipeline));
+        GlobalBQ::First.clear();
+        GlobalBQ::Second.clear();
+        self.show_ai_cls = false;
+        self.ai_cls_selected = None;
+        self.ai_selected = Some(index);
+        self.pipeline_file = Some(path.to_path_buf());
+        Ok(())
+    }
+
     fn set_ai(&mut self, ep: Ep) -> Result <()> {
+        if let Some(file) = self.pipeline_file.clone() {
+            return self.set_pipeline(&file, ep);
+        }
         if let Some(ai_index) = self.ai_selected {
             GlobalBQ::First.set_model(
                 &self.ais[ai_index].get_path(),
@@ -694,7 +905,7 @@ impl Gui {
                         match fs::read_dir(&folder_path) {
                             Ok(entries) => {
                                 let (image_files, audio_files, video_files) =
-                                    partition_media_in_dir(entries);
+                                    formats::partition_media_in_dir(entries);
 
                                 let had_any = !image_files.is_empty()
                                     || !audio_files.is_empty()
@@ -702,6 +913,7 @@ impl Gui {
 
                                 if !image_files.is_empty() {
                                     self.selected_imgs = image_files.into_preds(PredImg::new_simple);
+                                    self.img_events = None;
                                     self.image_texture_n = 1;
                                     self.paint(ui, 0);
                                     self.img_state.progress_bar =
@@ -753,6 +965,7 @@ impl Gui {
                         .pick_files()
                     {
                         self.selected_imgs = paths.into_preds(PredImg::new_simple);
+                        self.img_events = None;
                         self.image_texture_n = 1;
                         self.paint(ui, 0);
                         self.mode = Mode::Image;
@@ -1163,6 +1376,36 @@ fn imgbuf_to_texture(
     Some(ui.load_texture("current_frame", color_img, egui::TextureOptions::default()))
 }
 
+// ---------- Local inference ----------
+
+/// The models a local run works with: the loaded pipeline, or the primary
+/// model plus the optional crop classifier. Taken when the run starts, so
+/// picking another model mid-run only affects the next run.
+#[derive(Clone)]
+pub(super) struct RunModels(Option<Pipeline>);
+
+impl RunModels {
+    pub(super) fn current() -> Self {
+        Self(Pipeline::current())
+    }
+
+    pub(super) fn imgbuf(&self, img: &image::ImageBuffer<image::Rgb<u8>, Vec<u8>>) -> Result<AIOutputs> {
+        self.0.as_ref().context("no model loaded")?.run(img)
+    }
+
+    /// Decodes and runs `paths` as one batch; `None` for files that failed.
+    pub(super) fn image_files(&self, paths: &[PathBuf]) -> Vec<Option<AIOutputs>> {
+        match &self.0 {
+            Some(pipeline) => run_image_files(pipeline, paths).into_iter().map(Result::ok).collect(),
+            None => vec![None; paths.len()],
+        }
+    }
+
+    pub(super) fn audio(&self, audio: &AudioData) -> Result<AIOutputs> {
+        process_audio_with(self.0.as_ref().context("no model loaded")?.model(), audio)
+    }
+}
+
 // ---------- Cross-modality render helpers ----------
 
 /// Default max width for scaled-down preview thumbnails.
@@ -1396,32 +1639,3 @@ enum Mode {
     Feed,
     Audio,
 }
-
-fn partition_media_in_dir(
-    entries: fs::ReadDir,
-) -> (Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>) {
-    let mut images = Vec::new();
-    let mut audios = Vec::new();
-    let mut videos = Vec::new();
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
-        }
-        let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
-            continue;
-        };
-        if has_ext(ext, formats::IMAGE_FORMATS) {
-            images.push(path);
-        } else if has_ext(ext, formats::AUDIO_FORMATS) {
-            audios.push(path);
-        } else if has_ext(ext, formats::VIDEO_FORMATS) {
-            videos.push(path);
-        }
-    }
-    (images, audios, videos)
-}
-
-fn has_ext<const N: usize>(ext: &str, formats: [&str; N]) -> bool {
-    formats.iter().any(|f| ext.eq_ignore_ascii_case(f))
-}
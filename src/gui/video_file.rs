@@ -1,9 +1,11 @@
-use super::{imgbuf_to_texture, Gui};
+use super::{imgbuf_to_texture, Gui, RunModels};
 use crate::api::abstractions::*;
-use crate::api::bq::process_imgbuf;
 use crate::api::export;
+use crate::api::camtrap;
+use crate::api::tabular;
 use crate::api::render::*;
 use crate::api::rest::rgb_image_to_jpeg_buffer;
+use crate::api::utils;
 use crate::api::video_file;
 use crate::localization::*;
 use std::sync::Arc;
@@ -86,6 +88,7 @@ impl Gui {
         let processor = Arc::clone(&self.video_file_processor);
         let rest_client = self.rest_client.clone();
         let is_remote = !self.ep_selected.is_local();
+        let models = RunModels::current();
         let step = self.video_step_frame.max(1) as u64;
 
         tokio::spawn(async move {
@@ -110,8 +113,9 @@ impl Gui {
                         Err(_) => break,
                     }
                 } else {
+                    let models = models.clone();
                     match tokio::task::spawn_blocking(move || {
-                        process_imgbuf(&img).map(|result| (img, result))
+                        models.imgbuf(&img).map(|result| (img, result))
                     })
                     .await
                     {
@@ -256,8 +260,10 @@ impl Gui {
         if closed {
             if is_analysis {
                 self.video_state.progress_bar = 1.0;
+                let provenance = self.current_provenance();
                 if let Some(pv) = self.current_video_mut() {
                     pv.wasprocessed = true;
+                    pv.provenance = provenance;
                 }
             } else {
                 // Preview ran to EOF — drop the exhausted decoder so a later
@@ -483,6 +489,19 @@ impl Gui {
                     self.export_video_predictions_json();
                     close = true;
                 }
+                if ui.button(self.t(Key::export_csv)).clicked() {
+                    let path = export::prepare_export_file("predictions", "csv");
+                    let result = tabular::write_csv_list(&path, &self.selected_videos);
+                    self.export_done(&path, result);
+                    close = true;
+                }
+                if ui.button(self.t(Key::export_camtrap_dp)).clicked() {
+                    let dir = export::prepare_export_dir("camtrap_dp");
+                    let root = utils::common_root(self.selected_videos.iter().map(|p| p.file_path.as_path()));
+                    let result = camtrap::write_camtrap_dp(&dir, &[], &self.selected_videos, root.as_deref(), None);
+                    self.export_done(&dir, result);
+                    close = true;
+                }
                 if ui
                     .button(self.t(Key::export_video_with_predictions))
                     .clicked()
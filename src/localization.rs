@@ -84,6 +84,14 @@ pub enum Key {
     feed_processing,
     model_hub_url,
     export_predictions,
+    export_csv,
+    export_coco,
+    export_events_csv,
+    export_camtrap_dp,
+    export_geojson,
+    export_kml,
+    import_coco,
+    imported_annotations,
     export_imgs_with_predictions,
     input_url,
     example,
@@ -118,6 +126,30 @@ pub enum Key {
     saved_next_to_originals,
     unknown_file,
     not_analysed,
+    captured,
+    camera,
+    serial_number,
+    location,
+    trigger,
+    burst,
+    group_events,
+    event,
+    vote,
+    triage,
+    copy_files,
+    move_files,
+    link_files,
+    dry_run,
+    triaged_files,
+    loaded_models,
+    pipeline,
+    load_pipeline,
+    tiled_inference,
+    tile_size,
+    tile_overlap,
+    test_time_augmentation,
+    per_class_settings,
+    own_threshold,
     not_analysed_parens,
     predictions,
     classification,
@@ -573,6 +605,94 @@ pub fn translate(key: Key, lang: &Lang) -> &'static str {
             Lang::VI => "Xuất kết quả (.json)",
             Lang::NK => "Izvezi predviđanja (.json)",
         },
+        Key::export_csv => match lang {
+            Lang::EN => "Export table (.csv)",
+            Lang::ES => "Exportar tabla (.csv)",
+            Lang::FR => "Exporter le tableau (.csv)",
+            Lang::DE => "Tabelle exportieren (.csv)",
+            Lang::ZH => "导出表格（.csv）",
+            Lang::JA => "表をエクスポート（.csv）",
+            Lang::PT => "Exportar tabela (.csv)",
+            Lang::VI => "Xuất bảng (.csv)",
+            Lang::NK => "Izvezi tablicu (.csv)",
+        },
+        Key::export_coco => match lang {
+            Lang::EN => "Export COCO (.json)",
+            Lang::ES => "Exportar COCO (.json)",
+            Lang::FR => "Exporter en COCO (.json)",
+            Lang::DE => "COCO exportieren (.json)",
+            Lang::ZH => "导出 COCO（.json）",
+            Lang::JA => "COCO をエクスポート（.json）",
+            Lang::PT => "Exportar COCO (.json)",
+            Lang::VI => "Xuất COCO (.json)",
+            Lang::NK => "Izvezi COCO (.json)",
+        },
+        Key::export_events_csv => match lang {
+            Lang::EN => "Export events (.csv)",
+            Lang::ES => "Exportar eventos (.csv)",
+            Lang::FR => "Exporter les événements (.csv)",
+            Lang::DE => "Ereignisse exportieren (.csv)",
+            Lang::ZH => "导出事件（.csv）",
+            Lang::JA => "イベントをエクスポート（.csv）",
+            Lang::PT => "Exportar eventos (.csv)",
+            Lang::VI => "Xuất sự kiện (.csv)",
+            Lang::NK => "Izvezi događaje (.csv)",
+        },
+        Key::export_camtrap_dp => match lang {
+            Lang::EN => "Export Camtrap DP",
+            Lang::ES => "Exportar Camtrap DP",
+            Lang::FR => "Exporter en Camtrap DP",
+            Lang::DE => "Camtrap DP exportieren",
+            Lang::ZH => "导出 Camtrap DP",
+            Lang::JA => "Camtrap DP をエクスポート",
+            Lang::PT => "Exportar Camtrap DP",
+            Lang::VI => "Xuất Camtrap DP",
+            Lang::NK => "Izvezi Camtrap DP",
+        },
+        Key::export_geojson => match lang {
+            Lang::EN => "Export deployments as GeoJSON",
+            Lang::ES => "Exportar despliegues como GeoJSON",
+            Lang::FR => "Exporter les déploiements en GeoJSON",
+            Lang::DE => "Standorte als GeoJSON exportieren",
+            Lang::ZH => "将部署点导出为 GeoJSON",
+            Lang::JA => "設置地点を GeoJSON でエクスポート",
+            Lang::PT => "Exportar implantações como GeoJSON",
+            Lang::VI => "Xuất điểm đặt máy dạng GeoJSON",
+            Lang::NK => "Izvezi lokacije kao GeoJSON",
+        },
+        Key::export_kml => match lang {
+            Lang::EN => "Export deployments as KML",
+            Lang::ES => "Exportar despliegues como KML",
+            Lang::FR => "Exporter les déploiements en KML",
+            Lang::DE => "Standorte als KML exportieren",
+            Lang::ZH => "将部署点导出为 KML",
+            Lang::JA => "設置地点を KML でエクスポート",
+            Lang::PT => "Exportar implantações como KML",
+            Lang::VI => "Xuất điểm đặt máy dạng KML",
+            Lang::NK => "Izvezi lokacije kao KML",
+        },
+        Key::import_coco => match lang {
+            Lang::EN => "Import COCO annotations (.json)",
+            Lang::ES => "Importar anotaciones COCO (.json)",
+            Lang::FR => "Importer des annotations COCO (.json)",
+            Lang::DE => "COCO-Annotationen importieren (.json)",
+            Lang::ZH => "导入 COCO 标注（.json）",
+            Lang::JA => "COCO アノテーションをインポート（.json）",
+            Lang::PT => "Importar anotações COCO (.json)",
+            Lang::VI => "Nhập chú thích COCO (.json)",
+            Lang::NK => "Uvezi COCO anotacije (.json)",
+        },
+        Key::imported_annotations => match lang {
+            Lang::EN => "Images with imported annotations",
+            Lang::ES => "Imágenes con anotaciones importadas",
+            Lang::FR => "Images avec annotations importées",
+            Lang::DE => "Bilder mit importierten Annotationen",
+            Lang::ZH => "已导入标注的图像",
+            Lang::JA => "アノテーションをインポートした画像",
+            Lang::PT => "Imagens com anotações importadas",
+            Lang::VI => "Ảnh đã nhập chú thích",
+            Lang::NK => "Slike s uvezenim anotacijama",
+        },
         Key::export_imgs_with_predictions => match lang {
             Lang::EN => "Export images with predictions (.jpg)",
             Lang::ES => "Exportar imágenes con predicciones (.jpg)",
@@ -815,6 +935,270 @@ pub fn translate(key: Key, lang: &Lang) -> &'static str {
             Lang::VI => "chưa phân tích",
             Lang::NK => "neanalizirano",
         },
+        Key::captured => match lang {
+            Lang::EN => "Captured",
+            Lang::ES => "Capturada",
+            Lang::FR => "Prise le",
+            Lang::DE => "Aufgenommen",
+            Lang::ZH => "拍摄时间",
+            Lang::JA => "撮影日時",
+            Lang::PT => "Capturada",
+            Lang::VI => "Chụp lúc",
+            Lang::NK => "Snimljeno",
+        },
+        Key::camera => match lang {
+            Lang::EN => "Camera",
+            Lang::ES => "Cámara",
+            Lang::FR => "Appareil",
+            Lang::DE => "Kamera",
+            Lang::ZH => "相机",
+            Lang::JA => "カメラ",
+            Lang::PT => "Câmera",
+            Lang::VI => "Máy ảnh",
+            Lang::NK => "Kamera",
+        },
+        Key::serial_number => match lang {
+            Lang::EN => "Serial number",
+            Lang::ES => "Número de serie",
+            Lang::FR => "Numéro de série",
+            Lang::DE => "Seriennummer",
+            Lang::ZH => "序列号",
+            Lang::JA => "シリアル番号",
+            Lang::PT => "Número de série",
+            Lang::VI => "Số sê-ri",
+            Lang::NK => "Serijski broj",
+        },
+        Key::location => match lang {
+            Lang::EN => "Location",
+            Lang::ES => "Ubicación",
+            Lang::FR => "Emplacement",
+            Lang::DE => "Standort",
+            Lang::ZH => "位置",
+            Lang::JA => "位置",
+            Lang::PT => "Localização",
+            Lang::VI => "Vị trí",
+            Lang::NK => "Lokacija",
+        },
+        Key::trigger => match lang {
+            Lang::EN => "Trigger",
+            Lang::ES => "Disparo",
+            Lang::FR => "Déclenchement",
+            Lang::DE => "Auslöser",
+            Lang::ZH => "触发",
+            Lang::JA => "トリガー",
+            Lang::PT => "Disparo",
+            Lang::VI => "Kích hoạt",
+            Lang::NK => "Okidač",
+        },
+        Key::burst => match lang {
+            Lang::EN => "Burst",
+            Lang::ES => "Ráfaga",
+            Lang::FR => "Rafale",
+            Lang::DE => "Serie",
+            Lang::ZH => "连拍",
+            Lang::JA => "連写",
+            Lang::PT => "Sequência",
+            Lang::VI => "Chụp liên tiếp",
+            Lang::NK => "Serija",
+        },
+        Key::group_events => match lang {
+            Lang::EN => "Group into events",
+            Lang::ES => "Agrupar en eventos",
+            Lang::FR => "Grouper en événements",
+            Lang::DE => "In Ereignisse gruppieren",
+            Lang::ZH => "按事件分组",
+            Lang::JA => "イベントにまとめる",
+            Lang::PT => "Agrupar em eventos",
+            Lang::VI => "Nhóm thành sự kiện",
+            Lang::NK => "Grupiraj u događaje",
+        },
+        Key::event => match lang {
+            Lang::EN => "Event",
+            Lang::ES => "Evento",
+            Lang::FR => "Événement",
+            Lang::DE => "Ereignis",
+            Lang::ZH => "事件",
+            Lang::JA => "イベント",
+            Lang::PT => "Evento",
+            Lang::VI => "Sự kiện",
+            Lang::NK => "Događaj",
+        },
+        Key::vote => match lang {
+            Lang::EN => "Vote",
+            Lang::ES => "Votación",
+            Lang::FR => "Vote",
+            Lang::DE => "Abstimmung",
+            Lang::ZH => "投票",
+            Lang::JA => "投票",
+            Lang::PT => "Votação",
+            Lang::VI => "Bỏ phiếu",
+            Lang::NK => "Glasanje",
+        },
+        Key::triage => match lang {
+            Lang::EN => "Triage",
+            Lang::ES => "Clasificar",
+            Lang::FR => "Trier",
+            Lang::DE => "Sortieren",
+            Lang::ZH => "分拣",
+            Lang::JA => "仕分け",
+            Lang::PT => "Triagem",
+            Lang::VI => "Phân loại",
+            Lang::NK => "Sortiraj",
+        },
+        Key::copy_files => match lang {
+            Lang::EN => "Copy",
+            Lang::ES => "Copiar",
+            Lang::FR => "Copier",
+            Lang::DE => "Kopieren",
+            Lang::ZH => "复制",
+            Lang::JA => "コピー",
+            Lang::PT => "Copiar",
+            Lang::VI => "Sao chép",
+            Lang::NK => "Kopiraj",
+        },
+        Key::move_files => match lang {
+            Lang::EN => "Move",
+            Lang::ES => "Mover",
+            Lang::FR => "Déplacer",
+            Lang::DE => "Verschieben",
+            Lang::ZH => "移动",
+            Lang::JA => "移動",
+            Lang::PT => "Mover",
+            Lang::VI => "Di chuyển",
+            Lang::NK => "Premjesti",
+        },
+        Key::link_files => match lang {
+            Lang::EN => "Symlink",
+            Lang::ES => "Enlace simbólico",
+            Lang::FR => "Lien symbolique",
+            Lang::DE => "Symlink",
+            Lang::ZH => "符号链接",
+            Lang::JA => "シンボリックリンク",
+            Lang::PT => "Link simbólico",
+            Lang::VI => "Liên kết tượng trưng",
+            Lang::NK => "Simbolička veza",
+        },
+        Key::dry_run => match lang {
+            Lang::EN => "Dry run",
+            Lang::ES => "Simulación",
+            Lang::FR => "Simulation",
+            Lang::DE => "Probelauf",
+            Lang::ZH => "试运行",
+            Lang::JA => "ドライラン",
+            Lang::PT => "Simulação",
+            Lang::VI => "Chạy thử",
+            Lang::NK => "Probni rad",
+        },
+        Key::triaged_files => match lang {
+            Lang::EN => "Files sorted",
+            Lang::ES => "Archivos clasificados",
+            Lang::FR => "Fichiers triés",
+            Lang::DE => "Dateien sortiert",
+            Lang::ZH => "已分拣文件",
+            Lang::JA => "仕分けたファイル",
+            Lang::PT => "Arquivos triados",
+            Lang::VI => "Tệp đã phân loại",
+            Lang::NK => "Sortirane datoteke",
+        },
+        Key::pipeline => match lang {
+            Lang::EN => "Pipeline",
+            Lang::ES => "Flujo de modelos",
+            Lang::FR => "Chaîne de modèles",
+            Lang::DE => "Modellkette",
+            Lang::ZH => "模型流水线",
+            Lang::JA => "モデルパイプライン",
+            Lang::PT => "Fluxo de modelos",
+            Lang::VI => "Chuỗi mô hình",
+            Lang::NK => "Lanac modela",
+        },
+        Key::load_pipeline => match lang {
+            Lang::EN => "Load a pipeline file (TOML or JSON)",
+            Lang::ES => "Cargar un archivo de flujo (TOML o JSON)",
+            Lang::FR => "Charger un fichier de chaîne (TOML ou JSON)",
+            Lang::DE => "Modellkette aus Datei laden (TOML oder JSON)",
+            Lang::ZH => "加载流水线文件（TOML 或 JSON）",
+            Lang::JA => "パイプラインファイルを読み込む（TOML または JSON）",
+            Lang::PT => "Carregar um arquivo de fluxo (TOML ou JSON)",
+            Lang::VI => "Tải tệp chuỗi mô hình (TOML hoặc JSON)",
+            Lang::NK => "Učitaj datoteku lanca (TOML ili JSON)",
+        },
+        Key::tiled_inference => match lang {
+            Lang::EN => "Tiled inference (large images)",
+            Lang::ES => "Inferencia por mosaicos (imágenes grandes)",
+            Lang::FR => "Inférence par tuiles (grandes images)",
+            Lang::DE => "Kachelweise Inferenz (große Bilder)",
+            Lang::ZH => "分块推理（大图像）",
+            Lang::JA => "タイル推論（大きな画像）",
+            Lang::PT => "Inferência por blocos (imagens grandes)",
+            Lang::VI => "Suy luận theo ô (ảnh lớn)",
+            Lang::NK => "Inferencija po pločicama (velike slike)",
+        },
+        Key::tile_size => match lang {
+            Lang::EN => "Tile size (px)",
+            Lang::ES => "Tamaño del mosaico (px)",
+            Lang::FR => "Taille des tuiles (px)",
+            Lang::DE => "Kachelgröße (px)",
+            Lang::ZH => "分块大小（像素）",
+            Lang::JA => "タイルサイズ（px）",
+            Lang::PT => "Tamanho do bloco (px)",
+            Lang::VI => "Kích thước ô (px)",
+            Lang::NK => "Veličina pločice (px)",
+        },
+        Key::tile_overlap => match lang {
+            Lang::EN => "Tile overlap",
+            Lang::ES => "Solapamiento de mosaicos",
+            Lang::FR => "Chevauchement des tuiles",
+            Lang::DE => "Kachelüberlappung",
+            Lang::ZH => "分块重叠",
+            Lang::JA => "タイルの重なり",
+            Lang::PT => "Sobreposição de blocos",
+            Lang::VI => "Độ chồng lấn ô",
+            Lang::NK => "Preklapanje pločica",
+        },
+        Key::test_time_augmentation => match lang {
+            Lang::EN => "Test-time augmentation (slower, better recall)",
+            Lang::ES => "Aumentación en inferencia (más lento, mayor sensibilidad)",
+            Lang::FR => "Augmentation au test (plus lent, meilleur rappel)",
+            Lang::DE => "Testzeit-Augmentierung (langsamer, höhere Trefferquote)",
+            Lang::ZH => "测试时增强（更慢，召回率更高）",
+            Lang::JA => "テスト時拡張（低速、再現率向上）",
+            Lang::PT => "Aumento em inferência (mais lento, maior sensibilidade)",
+            Lang::VI => "Tăng cường khi suy luận (chậm hơn, độ nhạy cao hơn)",
+            Lang::NK => "Augmentacija pri testiranju (sporije, bolji odziv)",
+        },
+        Key::per_class_settings => match lang {
+            Lang::EN => "Per-class settings",
+            Lang::ES => "Ajustes por clase",
+            Lang::FR => "Réglages par classe",
+            Lang::DE => "Einstellungen pro Klasse",
+            Lang::ZH => "按类别设置",
+            Lang::JA => "クラスごとの設定",
+            Lang::PT => "Ajustes por classe",
+            Lang::VI => "Cài đặt theo lớp",
+            Lang::NK => "Postavke po klasi",
+        },
+        Key::own_threshold => match lang {
+            Lang::EN => "Own threshold",
+            Lang::ES => "Umbral propio",
+            Lang::FR => "Seuil propre",
+            Lang::DE => "Eigener Schwellenwert",
+            Lang::ZH => "单独阈值",
+            Lang::JA => "個別のしきい値",
+            Lang::PT => "Limiar próprio",
+            Lang::VI => "Ngưỡng riêng",
+            Lang::NK => "Vlastiti prag",
+        },
+        Key::loaded_models => match lang {
+            Lang::EN => "Models in memory",
+            Lang::ES => "Modelos en memoria",
+            Lang::FR => "Modèles en mémoire",
+            Lang::DE => "Modelle im Speicher",
+            Lang::ZH => "内存中的模型",
+            Lang::JA => "メモリ上のモデル",
+            Lang::PT => "Modelos na memória",
+            Lang::VI => "Mô hình trong bộ nhớ",
+            Lang::NK => "Modeli u memoriji",
+        },
         Key::not_analysed_parens => match lang {
             Lang::EN => "(not analysed)",
             Lang::ES => "(sin analizar)",
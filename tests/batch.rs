@@ -0,0 +1,123 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{
+    AIOutputs, ModelConfig, ModelStamp, Pred, PredImg, PredVideo, Prob, Provenance, SIDECAR_SCHEMA_VERSION,
+};
+use boquilahub::api::batch::collect_media;
+
+#[test]
+fn collect_media_splits_by_modality() -> Result<()> {
+    let flat = collect_media("tests/assets", false)?;
+    assert!(flat.images.iter().any(|p| p.ends_with("img.jpg")));
+    assert!(flat.audios.iter().any(|p| p.ends_with("bird.mp3")));
+    assert!(flat.audios.iter().all(|p| !p.to_string_lossy().contains("perch")));
+
+    let deep = collect_media("tests/assets", true)?;
+    assert!(deep.audios.iter().any(|p| p.ends_with("Parus major.wav")));
+    assert!(deep.len() > flat.len());
+    Ok(())
+}
+
+#[test]
+fn collect_media_accepts_single_file() -> Result<()> {
+    let single = collect_media("tests/assets/img.jpg", false)?;
+    assert_eq!(single.images.len(), 1);
+    assert_eq!(single.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn sidecar_envelope_round_trips_and_detects_stale() -> Result<()> {
+    let dir = std::env::temp_dir().join("boquilahub_sidecar_test");
+    std::fs::create_dir_all(&dir)?;
+    let img_path = dir.join("img.jpg");
+    std::fs::copy("tests/assets/img.jpg", &img_path)?;
+
+    let stamp = ModelStamp {
+        model: "test-model".to_owned(),
+        bq_sha256: "00ff".to_owned(),
+        config: ModelConfig::default(),
+        crop: None,
+        model_cls: None,
+    };
+    let pred = PredImg {
+        file_path: img_path.clone(),
+        aioutput: Some(AIOutputs::Classification(vec![Prob::new("cat".to_owned(), 0.9, 0)])),
+        wasprocessed: true,
+        provenance: Some(Provenance {
+            stamp: stamp.clone(),
+            architecture: "efficientnetv2".to_owned(),
+            classes: vec!["cat".to_owned()],
+            ep: "CPU".to_owned(),
+            created_at: "2026-01-01T00:00:00+00:00".to_owned(),
+            boquilahub_version: "0.0.0".to_owned(),
+        }),
+        metadata: None,
+    };
+    pred.write_predictions()?;
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(pred.predictions_file_path()?)?)?;
+    assert_eq!(json["schema_version"], SIDECAR_SCHEMA_VERSION);
+    assert_eq!(json["model"], "test-model");
+    assert!(json["outputs"]["Classification"].is_array());
+
+    let reloaded = PredImg::new_simple(img_path.clone());
+    assert!(reloaded.is_up_to_date(&stamp));
+    assert_eq!(reloaded.provenance.as_ref().map(|p| p.ep.as_str()), Some("CPU"));
+
+    let mut other = stamp.clone();
+    other.config.confidence_threshold = 0.5;
+    assert!(!reloaded.is_up_to_date(&other));
+
+    // Legacy bare sidecars still load, but never count as up to date.
+    std::fs::write(
+        pred.predictions_file_path()?,
+        serde_json::to_string(&pred.aioutput)?,
+    )?;
+    assert!(AIOutputs::from_file(pred.predictions_file_path()?).is_ok());
+    let reloaded = PredImg::new_simple(img_path.clone());
+    assert!(reloaded.wasprocessed);
+    assert!(!reloaded.is_up_to_date(&stamp));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn video_provenance_is_written_once() -> Result<()> {
+    let dir = std::env::temp_dir().join("boquilahub_video_sidecar_test");
+    std::fs::create_dir_all(&dir)?;
+    let video_path = dir.join("clip.mp4");
+
+    let mut video = PredVideo::new_simple(video_path.clone());
+    video.hydrate(640, 480, 30.0, 2);
+    video.record(0, AIOutputs::Classification(vec![Prob::new("cat".to_owned(), 0.9, 0)]));
+    video.wasprocessed = true;
+    video.provenance = Some(Provenance {
+        stamp: ModelStamp {
+            model: "test-model".to_owned(),
+            bq_sha256: "00ff".to_owned(),
+            config: ModelConfig::default(),
+            crop: None,
+            model_cls: None,
+        },
+        architecture: "efficientnetv2".to_owned(),
+        classes: vec!["cat".to_owned()],
+        ep: "CPU".to_owned(),
+        created_at: "2026-01-01T00:00:00+00:00".to_owned(),
+        boquilahub_version: "0.0.0".to_owned(),
+    });
+    video.write_predictions()?;
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(video.predictions_file_path()?)?)?;
+    assert_eq!(json["model"], "test-model");
+    assert!(json["outputs"].get("provenance").is_none(), "only in the envelope");
+
+    let reloaded = PredVideo::new_simple(video_path);
+    assert_eq!(reloaded.provenance.as_ref().map(|p| p.ep.as_str()), Some("CPU"));
+    assert_eq!(reloaded.processed_count(), 1);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
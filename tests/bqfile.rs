@@ -0,0 +1,179 @@
+use boquilahub::api::abstractions::{AIMetadataRaw, ModelInfo};
+use boquilahub::api::bqfile::{self, BqFile, Signer};
+use ed25519_dalek::SigningKey;
+
+// Not a real graph; the container format doesn't look inside it.
+const ONNX: &[u8] = &[0x08, 0x07, 0x12, 0x04, b't', b'e', b's', b't'];
+
+fn metadata() -> serde_json::Map<String, serde_json::Value> {
+    serde_json::from_str(
+        r#"{"task": "classify", "architecture": "efficientnetv2", "post_processing": [], "classes": ["deer", "fox"],
+            "model_version": "1.2.0", "authors": ["Jane Doe"], "license": "CC-BY-4.0"}"#,
+    )
+    .unwrap()
+}
+
+fn write(metadata: &mut serde_json::Map<String, serde_json::Value>, external: &[(&str, &[u8])], key: Option<&SigningKey>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bqfile::write(&mut bytes, metadata, ONNX, external, key).unwrap();
+    bytes
+}
+
+fn info(file: &BqFile) -> ModelInfo {
+    serde_json::from_slice::<AIMetadataRaw>(file.json).unwrap().info
+}
+
+#[test]
+fn v2_round_trips_and_detects_corruption() {
+    let bytes = write(&mut metadata(), &[], None);
+    let file = BqFile::parse(&bytes).unwrap();
+    let info = info(&file);
+    assert_eq!(info.version(), 2);
+    assert_eq!(info.model_version.as_deref(), Some("1.2.0"));
+    assert_eq!(info.onnx_sha256, Some(bqfile::sha256_hex(ONNX)));
+    assert_eq!((file.layout, file.onnx), (bqfile::LAYOUT_V1, ONNX));
+    assert!(file.signature.is_none());
+    file.verify(&info).unwrap();
+
+    let mut corrupted = bytes.clone();
+    *corrupted.last_mut().unwrap() ^= 0xff;
+    let file = BqFile::parse(&corrupted).unwrap();
+    assert!(file.verify(&info).is_err());
+
+    assert!(BqFile::parse(&bytes[..bytes.len() - 1]).is_err());
+    assert!(bqfile::write(&mut Vec::new(), &mut metadata().into_iter().chain([("model_version".into(), "v2".into())]).collect(), ONNX, &[], None).is_err());
+}
+
+#[test]
+fn signatures_cover_the_metadata_and_the_onnx() {
+    let key = bqfile::key_from_hex(&bqfile::key_to_hex(&bqfile::generate_key().unwrap())).unwrap();
+    let bytes = write(&mut metadata(), &[], Some(&key));
+    let file = BqFile::parse(&bytes).unwrap();
+    assert_eq!(file.signature.map(|s| s.signer()), Some(bqfile::public_key_hex(&key)));
+    file.verify(&info(&file)).unwrap();
+
+    // Changing the license in place breaks the signature.
+    let mut tampered = bytes.clone();
+    let at = tampered.windows(4).position(|w| w == b"CC-B").unwrap();
+    tampered[at] = b'X';
+    let file = BqFile::parse(&tampered).unwrap();
+    assert!(file.verify(&info(&file)).is_err());
+}
+
+#[test]
+fn only_listed_keys_are_trusted() {
+    let (ours, theirs) = (bqfile::generate_key().unwrap(), bqfile::generate_key().unwrap());
+    let list = format!("# lab keys\n\n{} Jane Doe <jane@example.org>\n", bqfile::public_key_hex(&ours));
+    let trusted = bqfile::parse_trusted_keys(&list).unwrap();
+    assert_eq!(trusted, [*ours.verifying_key().as_bytes()]);
+    assert!(bqfile::parse_trusted_keys("not-a-key").is_err());
+
+    let bytes = write(&mut metadata(), &[], Some(&ours));
+    let Signer::Trusted(sig) = BqFile::parse(&bytes).unwrap().signer(&trusted) else {
+        panic!("signed by a listed key");
+    };
+    assert_eq!(sig.fingerprint().len(), 19);
+    assert_eq!(sig.fingerprint(), bqfile::fingerprint(ours.verifying_key().as_bytes()));
+
+    // Re-signed by someone else: intact, but not by anyone we know.
+    let resigned = write(&mut metadata(), &[], Some(&theirs));
+    let file = BqFile::parse(&resigned).unwrap();
+    file.verify(&info(&file)).unwrap();
+    assert!(matches!(file.signer(&trusted), Signer::Unknown(_)));
+    let unsigned = write(&mut metadata(), &[], None);
+    assert_eq!(BqFile::parse(&unsigned).unwrap().signer(&trusted), Signer::Unsigned);
+
+    // With a trust list, only its keys load; without one, anything does.
+    assert!(file.check_signer(Some(&trusted)).is_err());
+    assert!(matches!(file.check_signer(None), Ok(Signer::Unknown(_))));
+    assert!(BqFile::parse(&bytes).unwrap().check_signer(Some(&trusted)).is_ok());
+
+    // Stripping the signature off a tampered model doesn't get it past the list.
+    let mut stripped = bytes[..bytes.len() - 101].to_vec();
+    let at = stripped.windows(4).position(|w| w == b"CC-B").unwrap();
+    stripped[at] = b'X';
+    let file = BqFile::parse(&stripped).unwrap();
+    assert_eq!(file.signer(&trusted), Signer::Unsigned);
+    assert!(file.check_signer(Some(&trusted)).is_err());
+    assert_eq!(file.check_signer(None).unwrap(), Signer::Unsigned);
+}
+
+#[test]
+fn v1_files_still_load() {
+    let json = serde_json::to_vec(&metadata()).unwrap();
+    let mut bytes = b"BQMODEL\x01".to_vec();
+    bytes.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&json);
+    bytes.extend_from_slice(&(ONNX.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(ONNX);
+
+    let file = BqFile::parse(&bytes).unwrap();
+    let info = info(&file);
+    assert_eq!((info.version(), info.onnx_sha256.as_deref()), (1, None));
+    file.verify(&info).unwrap();
+
+    // Older writers left bytes after the ONNX section; v1 readers ignored them.
+    bytes.extend_from_slice(b"padding");
+    let file = BqFile::parse(&bytes).unwrap();
+    assert_eq!((file.onnx, file.signature.is_none()), (ONNX, true));
+
+    // And a v2 file is a valid v1 file: same header, sections where v1 looks.
+    let v2 = write(&mut metadata(), &[], None);
+    assert_eq!(&v2[..8], b"BQMODEL\x01");
+    let (json, _) = bqfile::json_section(&v2).unwrap();
+    assert!(serde_json::from_slice::<AIMetadataRaw>(json).is_ok());
+}
+
+#[test]
+fn external_data_uses_the_sectioned_layout() {
+    let weights = vec![7u8; 1000];
+    let key = bqfile::generate_key().unwrap();
+    let bytes = write(&mut metadata(), &[("model.onnx.data", &weights)], Some(&key));
+    assert_eq!(&bytes[..8], b"BQMODEL\x02");
+
+    let file = BqFile::parse(&bytes).unwrap();
+    let info = info(&file);
+    assert_eq!((file.layout, file.onnx), (bqfile::LAYOUT_V2, ONNX));
+    assert_eq!(file.external, vec![("model.onnx.data", &weights[..])]);
+    assert_eq!(info.data_sha256.get("model.onnx.data"), Some(&bqfile::sha256_hex(&weights)));
+    assert!(file.signature.is_some());
+    file.verify(&info).unwrap();
+
+    // A flipped byte in the external data is caught like one in the graph.
+    let mut corrupted = bytes.clone();
+    let at = corrupted.len() - 200;
+    corrupted[at] ^= 0xff;
+    let file = BqFile::parse(&corrupted).unwrap();
+    assert!(file.verify(&info).is_err());
+    assert!(BqFile::parse(&bytes[..bytes.len() - 150]).is_err());
+}
+
+#[test]
+fn set_metadata_keeps_the_onnx_and_drops_stale_signatures() {
+    let key = bqfile::generate_key().unwrap();
+    let bytes = write(&mut metadata(), &[], Some(&key));
+    let fields = ["license=MIT", "model_version=1.3.0", r#"classes=["deer","fox","hare"]"#, "authors=null"]
+        .iter()
+        .map(|arg| bqfile::parse_field(arg).unwrap())
+        .collect();
+
+    let mut edited = Vec::new();
+    bqfile::set_metadata(&bytes, fields, &mut edited, None).unwrap();
+    let file = BqFile::parse(&edited).unwrap();
+    let raw: AIMetadataRaw = serde_json::from_slice(file.json).unwrap();
+    assert_eq!(raw.classes, ["deer", "fox", "hare"]);
+    assert_eq!((raw.info.license.as_deref(), raw.info.model_version.as_deref()), (Some("MIT"), Some("1.3.0")));
+    assert!(raw.info.authors.is_empty());
+    assert_eq!(file.onnx, ONNX);
+    assert!(file.signature.is_none());
+    file.verify(&raw.info).unwrap();
+
+    // Edits that break the metadata, and corrupted files, are refused.
+    let fields = [bqfile::parse_field("classes=3").unwrap()].into_iter().collect();
+    assert!(bqfile::set_metadata(&bytes, fields, &mut Vec::new(), None).is_err());
+    let mut corrupted = bytes.clone();
+    let at = corrupted.len() - 105;
+    corrupted[at] ^= 0xff;
+    assert!(bqfile::set_metadata(&corrupted, Default::default(), &mut Vec::new(), None).is_err());
+    assert!(bqfile::parse_field("license").is_err());
+}
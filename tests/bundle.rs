@@ -0,0 +1,53 @@
+use boquilahub::api::bq::BQModel;
+use boquilahub::api::bqfile;
+use boquilahub::api::bundle::{self, Installed};
+
+fn write_model(path: &std::path::Path, version: &str) {
+    let mut metadata = serde_json::from_str(&format!(
+        r#"{{"task": "classify", "architecture": "efficientnetv2", "post_processing": [], "classes": ["deer", "fox"], "model_version": "{}"}}"#,
+        version
+    ))
+    .unwrap();
+    let mut bytes = Vec::new();
+    bqfile::write(&mut bytes, &mut metadata, &[0x08, 0x07, 1, 2, 3], &[], None).unwrap();
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn bundles_install_offline_and_record_their_origin() {
+    let dir = std::env::temp_dir().join("boquilahub_bundle_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let model = dir.join("deerfox.bq");
+    write_model(&model, "1.1.0");
+
+    // No index entry: the bundle's comes from the file alone.
+    let archive = dir.join("station.zip");
+    let packed = bundle::create(&archive, std::slice::from_ref(&model), &[], br#"{"mammalia;cervidae": ["CO"]}"#).unwrap();
+    assert_eq!(packed[0].name, "deerfox");
+    assert_eq!(packed[0].version.as_deref(), Some("1.1.0"));
+    assert_eq!(packed[0].download_link, "models/deerfox.bq");
+
+    let models = dir.join("models");
+    let installed = bundle::install(&archive, &models).unwrap();
+    assert_eq!(installed[0].1, Installed::New);
+    let dest = models.join("deerfox.bq");
+    assert_eq!(std::fs::read(&dest).unwrap(), std::fs::read(&model).unwrap());
+    assert_eq!(BQModel::load_origin(&dest).unwrap().source, "bundle station.zip");
+    assert!(models.join(bundle::GEOFENCE_NAME).exists());
+
+    // Installing again changes nothing; a newer bundle replaces the model.
+    assert_eq!(bundle::install(&archive, &models).unwrap()[0].1, Installed::Unchanged);
+    write_model(&model, "1.2.0");
+    bundle::create(&archive, std::slice::from_ref(&model), &[], br#"{"mammalia;cervidae": ["CO", "EC"]}"#).unwrap();
+    assert_eq!(bundle::install(&archive, &models).unwrap()[0].1, Installed::Updated);
+    assert_eq!(std::fs::read(&dest).unwrap(), std::fs::read(&model).unwrap());
+    let geofence = std::fs::read(models.join(bundle::GEOFENCE_NAME)).unwrap();
+
+    // Bad geofence data is refused and the installed copy kept.
+    for bad in [&b"{}"[..], b"not json", br#"{"mammalia": "CO"}"#] {
+        bundle::create(&archive, std::slice::from_ref(&model), &[], bad).unwrap();
+        assert!(bundle::install(&archive, &models).is_err());
+        assert_eq!(std::fs::read(models.join(bundle::GEOFENCE_NAME)).unwrap(), geofence);
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{AIOutputs, PredImg, Prob, XYXY, XYXYc};
+use boquilahub::api::camtrap::{write_camtrap_dp, Taxon};
+use boquilahub::api::deployment::DeploymentLocation;
+use boquilahub::api::events::EventConfig;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn taxa_from_species_records_and_rolled_up_names() {
+    let ocelot = Taxon::from_label("uuid-1;mammalia;carnivora;felidae;leopardus;pardalis;ocelot");
+    assert_eq!(ocelot.observation_type, "animal");
+    assert_eq!(ocelot.scientific_name.as_deref(), Some("Leopardus pardalis"));
+    assert_eq!(ocelot.taxon_rank, Some("species"));
+    assert_eq!(ocelot.vernacular_name.as_deref(), Some("ocelot"));
+
+    let family = Taxon::from_label("Family felidae");
+    assert_eq!(family.scientific_name.as_deref(), Some("Felidae"));
+    assert_eq!(family.taxon_rank, Some("family"));
+
+    let genus = Taxon::from_label("Leopardus sp.");
+    assert_eq!(genus.taxon_rank, Some("genus"));
+
+    assert_eq!(Taxon::from_label("uuid-2;;;;;;blank").observation_type, "blank");
+    assert_eq!(Taxon::from_label("person").observation_type, "human");
+}
+
+#[test]
+fn writes_package_tables() -> Result<()> {
+    let mut animal = XYXYc::new(XYXY::new(0.0, 0.0, 10.0, 10.0, 0.9, 0), "animal".to_owned());
+    animal.extra_cls = Some(vec![Prob::new(
+        "uuid-1;mammalia;carnivora;felidae;leopardus;pardalis;ocelot".to_owned(),
+        0.8,
+        3,
+    )]);
+    let img = PredImg {
+        file_path: PathBuf::from("tests/assets/img.jpg"),
+        aioutput: Some(AIOutputs::ObjectDetection(vec![animal])),
+        wasprocessed: true,
+        provenance: None,
+        metadata: None,
+    };
+
+    let dir = std::env::temp_dir().join("boquilahub_camtrap_test");
+    write_camtrap_dp(&dir, &[img], &[], Some(Path::new("tests")), None)?;
+
+    let deployments = std::fs::read_to_string(dir.join("deployments.csv"))?;
+    assert!(deployments.lines().nth(1).unwrap().starts_with("assets,"));
+    let media = std::fs::read_to_string(dir.join("media.csv"))?;
+    assert!(media.contains("assets/img.jpg"));
+    assert!(media.contains("image/jpeg"));
+    let observations = std::fs::read_to_string(dir.join("observations.csv"))?;
+    assert!(observations.starts_with("observationID,deploymentID,mediaID"));
+    assert!(observations.contains("Leopardus pardalis"));
+
+    let datapackage: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("datapackage.json"))?)?;
+    assert_eq!(datapackage["taxonomic"][0]["scientificName"], "Leopardus pardalis");
+    assert_eq!(datapackage["resources"].as_array().unwrap().len(), 3);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn event_level_observations() -> Result<()> {
+    let img = |aioutput| PredImg {
+        file_path: PathBuf::from("tests/assets/img.jpg"),
+        aioutput: Some(aioutput),
+        wasprocessed: true,
+        provenance: None,
+        metadata: None,
+    };
+    let deer = |n| {
+        AIOutputs::ObjectDetection(
+            (0..n)
+                .map(|_| XYXYc::new(XYXY::new(0.0, 0.0, 10.0, 10.0, 0.9, 0), "Family cervidae".to_owned()))
+                .collect(),
+        )
+    };
+
+    let dir = std::env::temp_dir().join("boquilahub_camtrap_events_test");
+    write_camtrap_dp(&dir, &[img(deer(1)), img(deer(3))], &[], None, Some(&EventConfig::default()))?;
+
+    let media = std::fs::read_to_string(dir.join("media.csv"))?;
+    assert_eq!(media.lines().count(), 3, "header plus both images");
+    let observations = std::fs::read_to_string(dir.join("observations.csv"))?;
+    let rows: Vec<&str> = observations.lines().skip(1).collect();
+    assert_eq!(rows.len(), 1, "one observation for the burst");
+    assert!(rows[0].contains(",e000001,"));
+    assert!(rows[0].contains(",event,animal,"));
+    assert!(rows[0].contains(",Cervidae,3,"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn deployments_take_their_place_from_the_location_file() -> Result<()> {
+    let root = std::env::temp_dir().join("boquilahub_camtrap_location_test");
+    let north = root.join("north");
+    std::fs::create_dir_all(&north)?;
+    DeploymentLocation { name: Some("Ridge".to_owned()), latitude: 4.5, longitude: -74.1 }.write(&north)?;
+    std::fs::copy("tests/assets/img.jpg", north.join("img.jpg"))?;
+    let img = PredImg {
+        file_path: north.join("img.jpg"),
+        aioutput: Some(AIOutputs::ObjectDetection(vec![])),
+        wasprocessed: true,
+        provenance: None,
+        metadata: None,
+    };
+
+    let dir = root.join("package");
+    write_camtrap_dp(&dir, &[img], &[], Some(&root), None)?;
+
+    let deployments = std::fs::read_to_string(dir.join("deployments.csv"))?;
+    assert!(deployments.lines().nth(1).unwrap().starts_with("north,,Ridge,4.5,-74.1,"));
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{AIOutputs, AudioProb, ModelConfig, Prob, XY, XYXY, XYXYc, XYc};
+use std::collections::BTreeMap;
+
+fn megadetector_config() -> ModelConfig {
+    ModelConfig {
+        confidence_threshold: 0.2,
+        class_thresholds: BTreeMap::from([("vehicle".to_owned(), 0.6)]),
+        exclude_classes: vec!["person".to_owned()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn per_class_thresholds_and_exclusions() {
+    let config = megadetector_config();
+    assert_eq!((config.threshold("animal"), config.threshold("vehicle")), (0.2, 0.6));
+    assert_eq!(config.min_threshold(), 0.2);
+
+    let det = |label: &str, prob: f32| XYXYc::new(XYXY::new(0.0, 0.0, 1.0, 1.0, prob, 0), label.to_owned());
+    let mut output = AIOutputs::ObjectDetection(vec![
+        det("animal", 0.25),
+        det("vehicle", 0.5),
+        det("vehicle", 0.7),
+        det("person", 0.99),
+    ]);
+    config.filter_output(&mut output);
+    let AIOutputs::ObjectDetection(kept) = output else { unreachable!() };
+    let kept: Vec<(&str, f32)> = kept.iter().map(|d| (d.label.as_str(), d.xyxy.prob)).collect();
+    assert_eq!(kept, vec![("animal", 0.25), ("vehicle", 0.7)]);
+
+    let mut points = AIOutputs::PointDetection(vec![
+        XYc::new(XY::new(1.0, 1.0, 0.3, 0), "person".to_owned()),
+        XYc::new(XY::new(2.0, 2.0, 0.3, 1), "animal".to_owned()),
+    ]);
+    config.filter_output(&mut points);
+    assert!(matches!(&points, AIOutputs::PointDetection(p) if p.len() == 1 && p[0].label == "animal"));
+}
+
+#[test]
+fn include_list_and_audio_windows() {
+    let config = ModelConfig {
+        include_classes: vec!["ocelot".to_owned(), "margay".to_owned()],
+        exclude_classes: vec!["margay".to_owned()],
+        ..Default::default()
+    };
+    let mut probs = AIOutputs::Classification(vec![
+        Prob::new("ocelot".to_owned(), 0.9, 0),
+        Prob::new("margay".to_owned(), 0.8, 1),
+        Prob::new("puma".to_owned(), 0.7, 2),
+    ]);
+    config.filter_output(&mut probs);
+    assert!(matches!(&probs, AIOutputs::Classification(p) if p.len() == 1 && p[0].label == "ocelot"), "exclusion wins");
+
+    // Audio windows only drop under a threshold set for their own class.
+    let config = ModelConfig {
+        class_thresholds: BTreeMap::from([("tinamou".to_owned(), 0.5)]),
+        ..Default::default()
+    };
+    let window = |label: &str, prob: f32| AudioProb { start: 0.0, end: 3.0, prediction: Prob::new(label.to_owned(), prob, 0) };
+    let mut audio = AIOutputs::AudioClassification(vec![window("frog", 0.1), window("tinamou", 0.4), window("tinamou", 0.6)]);
+    config.filter_output(&mut audio);
+    let AIOutputs::AudioClassification(kept) = audio else { unreachable!() };
+    assert_eq!(kept.len(), 2);
+    assert_eq!(kept[1].prediction.prob, 0.6);
+}
+
+#[test]
+fn class_settings_round_trip_and_stay_optional() -> Result<()> {
+    let config = megadetector_config();
+    let json = serde_json::to_string(&config)?;
+    assert_eq!(serde_json::from_str::<ModelConfig>(&json)?, config);
+
+    let plain = serde_json::to_string(&ModelConfig::default())?;
+    assert!(!plain.contains("class_thresholds") && !plain.contains("exclude_classes"));
+    let old: ModelConfig = serde_json::from_str(r#"{"confidence_threshold":0.3,"nms_threshold":0.4,"geo_fence":""}"#)?;
+    assert!(old.class_thresholds.is_empty() && old.include_classes.is_empty());
+    Ok(())
+}
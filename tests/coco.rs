@@ -0,0 +1,142 @@
+use anyhow::Result;
+use bitvec::vec::BitVec;
+use boquilahub::api::abstractions::{AIOutputs, BitMatrix, PredImg, SEGc, XYXY, XYXYc};
+use boquilahub::api::coco::{apply_coco, read_coco, rle_from_string, rle_to_string, to_coco, CocoDataset};
+use std::path::{Path, PathBuf};
+
+fn img(aioutput: Option<AIOutputs>) -> PredImg {
+    PredImg {
+        file_path: PathBuf::from("tests/assets/img.jpg"),
+        wasprocessed: aioutput.is_some(),
+        aioutput,
+        provenance: None,
+        metadata: None,
+    }
+}
+
+#[test]
+fn rle_string_round_trips() {
+    assert_eq!(rle_to_string(&[4]), "4");
+    let counts = vec![0, 5, 3, 1000, 2, 70000, 1];
+    assert_eq!(rle_from_string(&rle_to_string(&counts)).unwrap(), counts);
+
+    // Hostile input fails instead of overflowing or wrapping around.
+    assert!(rle_from_string("4 ").is_err(), "outside the alphabet");
+    assert!(rle_from_string("P").is_err(), "ends mid-number");
+    assert!(rle_from_string("PPPPPPPP0").is_err(), "too many groups");
+    assert!(rle_from_string("@").is_err(), "negative count");
+}
+
+#[test]
+fn segmentation_round_trips_through_coco() -> Result<()> {
+    // 2x2 mask with the top-left and bottom-right quadrants set, stretched
+    // over a 10x10 box.
+    let mut data = BitVec::new();
+    for bit in [true, false, false, true] {
+        data.push(bit);
+    }
+    let mask = BitMatrix { data, width: 2, height: 2 };
+    let bbox = XYXYc::new(XYXY::new(10.0, 20.0, 20.0, 30.0, 0.8, 2), "deer".to_owned());
+    let exported = to_coco(&[img(Some(AIOutputs::Segmentation(vec![SEGc::new(mask, bbox)])))], Some(Path::new("tests/assets")))?;
+
+    assert_eq!(exported.images[0].file_name, "img.jpg");
+    assert_eq!(exported.annotations[0].category_id, 3);
+    assert_eq!(exported.annotations[0].area, 50.0);
+    assert_eq!(exported.categories[0].name, "deer");
+
+    let json = serde_json::to_string(&exported)?;
+    let dataset: CocoDataset = serde_json::from_str(&json)?;
+    let mut preds = vec![img(None)];
+    assert_eq!(apply_coco(&mut preds, &dataset, None)?, vec![0]);
+
+    let Some(AIOutputs::Segmentation(segs)) = &preds[0].aioutput else {
+        panic!("expected segmentation");
+    };
+    let seg = &segs[0];
+    assert_eq!(seg.bbox.label, "deer");
+    assert_eq!(seg.bbox.xyxy.class_id, 2);
+    assert_eq!((seg.mask.width, seg.mask.height), (10, 10));
+    assert!(seg.mask.data[0], "top-left quadrant set");
+    assert!(!seg.mask.data[9], "top-right quadrant clear");
+    assert!(seg.mask.data[99], "bottom-right quadrant set");
+    assert!(preds[0].wasprocessed);
+    Ok(())
+}
+
+#[test]
+fn polygon_annotations_import_as_masks() -> Result<()> {
+    let json = r#"{
+        "images": [{"id": 7, "file_name": "other/dir/img.jpg", "width": 100, "height": 100}],
+        "annotations": [
+            {"id": 1, "image_id": 7, "category_id": 1, "bbox": [0, 0, 10, 10],
+             "segmentation": [[0, 0, 10, 0, 0, 10]]},
+            {"id": 2, "image_id": 7, "category_id": 9, "bbox": [50, 50, 5, 5]}
+        ],
+        "categories": [{"id": 1, "name": "fox"}]
+    }"#;
+    let path = std::env::temp_dir().join("boquilahub_coco_polygon.json");
+    std::fs::write(&path, json)?;
+    let dataset = read_coco(&path)?;
+    std::fs::remove_file(&path)?;
+
+    // Falls back to matching on the bare file name.
+    let mut preds = vec![img(None)];
+    assert_eq!(apply_coco(&mut preds, &dataset, Some(Path::new("tests/assets")))?, vec![0]);
+
+    let Some(AIOutputs::Segmentation(segs)) = &preds[0].aioutput else {
+        panic!("expected segmentation");
+    };
+    assert_eq!(segs.len(), 2);
+    let triangle = &segs[0].mask;
+    assert!(triangle.data[0], "pixel near the right angle is inside");
+    assert!(!triangle.data[99], "opposite corner is outside");
+    assert_eq!(segs[1].bbox.label, "9", "unknown categories keep their id");
+    assert_eq!(segs[1].bbox.xyxy.prob, 1.0);
+    Ok(())
+}
+
+#[test]
+fn ambiguous_names_and_bad_masks_are_not_imported() -> Result<()> {
+    let json = r#"{
+        "images": [{"id": 1, "file_name": "card9/img.jpg", "width": 100, "height": 100}],
+        "annotations": [{"id": 1, "image_id": 1, "category_id": 1, "bbox": [0, 0, 10, 10]}],
+        "categories": [{"id": 1, "name": "fox"}]
+    }"#;
+    let dataset: CocoDataset = serde_json::from_str(json)?;
+
+    // Two cards, same file name: neither is the one the annotation meant.
+    let mut preds = vec![img(None), PredImg { file_path: PathBuf::from("other/card2/img.jpg"), ..img(None) }];
+    assert!(apply_coco(&mut preds, &dataset, Some(Path::new("tests")))?.is_empty());
+    assert!(preds.iter().all(|p| p.aioutput.is_none()));
+
+    // RLE that doesn't add up to the image size.
+    let json = json.replace(r#""bbox": [0, 0, 10, 10]"#, r#""bbox": [0, 0, 10, 10], "segmentation": {"size": [100, 100], "counts": [5, 4000000000]}"#);
+    let dataset: CocoDataset = serde_json::from_str(&json)?;
+    assert!(apply_coco(&mut [img(None)], &dataset, None).is_err());
+
+    // Boxes reaching past the image are clipped before their mask is made;
+    // NaN, negative and empty ones are dropped.
+    let json = r#"{
+        "images": [{"id": 1, "file_name": "img.jpg", "width": 100, "height": 100}],
+        "annotations": [
+            {"id": 1, "image_id": 1, "category_id": 1, "bbox": [90, -5, 1e9, 1e9],
+             "segmentation": [[90, 0, 100, 0, 90, 10]]},
+            {"id": 2, "image_id": 1, "category_id": 1, "bbox": [10, 10, -5, 5],
+             "segmentation": [[10, 10, 20, 10, 10, 20]]},
+            {"id": 3, "image_id": 1, "category_id": 1, "bbox": [200, 200, 5, 5],
+             "segmentation": [[200, 200, 205, 200, 200, 205]]}
+        ],
+        "categories": [{"id": 1, "name": "fox"}]
+    }"#;
+    let mut dataset: CocoDataset = serde_json::from_str(json)?;
+    dataset.annotations[1].bbox[0] = f32::NAN;
+    let mut preds = vec![img(None)];
+    assert_eq!(apply_coco(&mut preds, &dataset, None)?, vec![0]);
+    let Some(AIOutputs::Segmentation(segs)) = &preds[0].aioutput else {
+        panic!("expected segmentation");
+    };
+    assert_eq!(segs.len(), 1);
+    assert_eq!((segs[0].bbox.xyxy.x1, segs[0].bbox.xyxy.y1, segs[0].bbox.xyxy.x2, segs[0].bbox.xyxy.y2), (90.0, 0.0, 100.0, 100.0));
+    assert_eq!((segs[0].mask.width, segs[0].mask.height), (10, 100));
+    Ok(())
+}
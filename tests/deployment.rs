@@ -0,0 +1,83 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{AIOutputs, PredImg, XYXY, XYXYc};
+use boquilahub::api::deployment::{summarize, to_geojson, to_kml, DeploymentLocation};
+use boquilahub::api::geojson::Geometry;
+use boquilahub::api::metadata::ImageMetadata;
+use std::path::{Path, PathBuf};
+
+fn img(path: PathBuf, label: Option<&str>, gps: Option<(f64, f64)>) -> PredImg {
+    let boxes = label
+        .map(|label| vec![XYXYc::new(XYXY::new(0.0, 0.0, 1.0, 1.0, 0.9, 0), label.to_owned())])
+        .unwrap_or_default();
+    PredImg {
+        file_path: path,
+        aioutput: Some(AIOutputs::ObjectDetection(boxes)),
+        wasprocessed: true,
+        provenance: None,
+        metadata: gps.map(|(lat, lon)| ImageMetadata {
+            latitude: Some(lat),
+            longitude: Some(lon),
+            ..Default::default()
+        }),
+    }
+}
+
+#[test]
+fn deployments_from_location_files_and_exif() -> Result<()> {
+    let root = std::env::temp_dir().join("boquilahub_deployment_test");
+    let _ = std::fs::remove_dir_all(&root);
+    let (north, south) = (root.join("north"), root.join("south"));
+    std::fs::create_dir_all(north.join("card1"))?;
+    std::fs::create_dir_all(&south)?;
+    DeploymentLocation {
+        name: Some("North <ridge>".to_owned()),
+        latitude: 4.5,
+        longitude: -74.1,
+    }
+    .write(&north)?;
+
+    let imgs = vec![
+        img(north.join("card1/a.jpg"), Some("ocelot"), Some((1.0, 1.0))),
+        img(north.join("card1/b.jpg"), Some("ocelot"), None),
+        img(north.join("card1/c.jpg"), None, None),
+        img(south.join("d.jpg"), Some("tapir"), Some((3.0, -75.0))),
+        img(root.join("e.jpg"), Some("puma"), None),
+    ];
+    let deployments = summarize(&imgs, &[], Some(&root));
+    let ids: Vec<&str> = deployments.iter().map(|d| d.id.as_str()).collect();
+    assert_eq!(ids, vec!["boquilahub_deployment_test", "north/card1", "south"]);
+
+    let north = &deployments[1];
+    assert_eq!((north.latitude, north.longitude), (Some(4.5), Some(-74.1)), "the file wins over EXIF");
+    assert_eq!((north.files, north.counts["ocelot"], north.detections.len()), (3, 2, 2));
+    assert_eq!((deployments[2].latitude, deployments[2].longitude), (Some(3.0), Some(-75.0)));
+    assert_eq!(deployments[0].latitude, None, "no file and no GPS");
+
+    let geojson = to_geojson(&deployments);
+    assert_eq!(geojson.features.len(), 2, "unlocated deployments are left out");
+    assert_eq!(geojson.features[0].geometry, Geometry::Point([-74.1, 4.5]));
+    assert_eq!(geojson.features[0].properties["counts"]["ocelot"], 2);
+
+    let kml = to_kml(&deployments);
+    assert!(kml.contains("<name>North &lt;ridge&gt;</name>"));
+    assert!(kml.contains("<coordinates>-75,3</coordinates>"));
+    assert!(kml.contains(r#"<Data name="tapir"><value>1</value></Data>"#));
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[test]
+fn location_lookup_stops_at_root() -> Result<()> {
+    let root = std::env::temp_dir().join("boquilahub_deployment_root_test");
+    std::fs::create_dir_all(root.join("site"))?;
+    DeploymentLocation { name: None, latitude: 1.0, longitude: 2.0 }.write(&root)?;
+
+    assert!(DeploymentLocation::find(&root.join("site"), Some(&root)).is_some());
+    assert!(DeploymentLocation::find(&root.join("site"), Some(&root.join("site"))).is_none());
+    assert!(DeploymentLocation::find(&root.join("site"), None).is_none(), "without a root only the folder itself");
+    assert!(DeploymentLocation::find(Path::new("/nonexistent/site"), Some(Path::new("/nonexistent"))).is_none());
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use boquilahub::api::abstractions::AvailableModel;
+use boquilahub::api::bq::BQModel;
+use boquilahub::api::download;
+use std::path::PathBuf;
+
+fn model(link: &str) -> AvailableModel {
+    AvailableModel {
+        name: "boquilanet".to_owned(),
+        description: String::new(),
+        download_link: link.to_owned(),
+        sha256: None,
+        version: None,
+        size: None,
+    }
+}
+
+#[test]
+fn resolves_links_and_mirrors() {
+    assert_eq!(download::index_url(Some("file:///mnt/usb/models.json")), "file:///mnt/usb/models.json");
+    assert_eq!(download::local_path("file:///mnt/usb/models.json"), Some(PathBuf::from("/mnt/usb/models.json")));
+    assert_eq!(download::local_path("file:///C:/mirror/models.json"), Some(PathBuf::from("C:/mirror/models.json")));
+    assert_eq!(download::local_path("D:/mirror/models.json"), Some(PathBuf::from("D:/mirror/models.json")));
+    assert_eq!(download::local_path("https://boquila.org/api/models.json"), None);
+
+    // Relative links follow the index, so a copied mirror still works.
+    assert_eq!(download::resolve_link("file:///mnt/usb/models.json", "bq/mega.bq"), "file:///mnt/usb/bq/mega.bq");
+    assert_eq!(download::resolve_link("https://a.org/api/models.json", "https://b.org/mega.bq"), "https://b.org/mega.bq");
+
+    assert_eq!(download::file_name(&model("https://b.org/models/mega.bq?download=1")), Some("mega.bq"));
+    assert_eq!(download::file_name(&model("https://b.org/models/")), None);
+}
+
+#[tokio::test]
+async fn resumes_partial_downloads_and_checks_hashes() {
+    let dir = std::env::temp_dir().join("boquilahub_download_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("source.bq");
+    let content: Vec<u8> = (0..3_000_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&source, &content).unwrap();
+    let sha256 = BQModel::file_sha256(&source).unwrap();
+
+    // A first run that stopped a third of the way through.
+    let dest = dir.join("model.bq");
+    std::fs::write(download::part_path(&dest), &content[..1_000_000]).unwrap();
+    let mut first = None;
+    let url = format!("file://{}", source.display());
+    download::download(&url, &dest, Some(&sha256), |done, _| {
+        first.get_or_insert(done);
+    })
+    .await
+    .unwrap();
+    assert!(first.unwrap() > 1_000_000);
+    assert_eq!(std::fs::read(&dest).unwrap(), content);
+    assert!(!download::part_path(&dest).exists());
+
+    let entry = AvailableModel { sha256: Some(sha256), ..model(&url) };
+    assert!(!download::is_outdated(&dest, &entry).unwrap());
+
+    // A corrupted partial file is caught by the hash and downloaded again.
+    let other = dir.join("other.bq");
+    std::fs::write(download::part_path(&other), vec![0u8; 1_000_000]).unwrap();
+    let mut passes = Vec::new();
+    download::download(&url, &other, entry.sha256.as_deref(), |done, _| {
+        if passes.last().is_none_or(|&last| done < last) {
+            passes.push(done);
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(passes, [1_000_000 + (1 << 20), 1 << 20], "resumed, then started over");
+    assert_eq!(std::fs::read(&other).unwrap(), content);
+
+    // A file that never matches is thrown away.
+    let bad = dir.join("bad.bq");
+    std::fs::write(download::part_path(&bad), &content[..1_000_000]).unwrap();
+    assert!(download::download(&url, &bad, Some("00"), |_, _| {}).await.is_err());
+    assert!(!bad.exists() && !download::part_path(&bad).exists());
+    assert!(download::is_outdated(&source, &AvailableModel { sha256: Some("00".into()), ..model(&url) }).unwrap());
+}
+
+/// Serves `content` over HTTP with ETag `"v2"`, honouring `Range` only
+/// when `If-Range` matches; a range past the end gets 416.
+fn serve(content: Vec<u8>) -> String {
+    use std::io::{BufRead, BufReader, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/model.bq", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let (mut range, mut if_range) = (None, None);
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(": ").unwrap_or((&line, ""));
+                match name.to_ascii_lowercase().as_str() {
+                    "range" => range = value.trim_start_matches("bytes=").trim_end_matches('-').parse::<usize>().ok(),
+                    "if-range" => if_range = Some(value.to_owned()),
+                    _ => {}
+                }
+            }
+            let head = |status: &str, len: usize| {
+                format!("HTTP/1.1 {}\r\nETag: \"v2\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, len)
+            };
+            let response = match range.filter(|_| if_range.as_deref() == Some("\"v2\"")) {
+                Some(start) if start >= content.len() => head("416 Range Not Satisfiable", 0).into_bytes(),
+                Some(start) => [head("206 Partial Content", content.len() - start).as_bytes(), &content[start..]].concat(),
+                None => [head("200 OK", content.len()).as_bytes(), &content].concat(),
+            };
+            stream.write_all(&response).unwrap();
+        }
+    });
+    url
+}
+
+#[tokio::test]
+async fn stale_http_parts_are_not_trusted() {
+    let dir = std::env::temp_dir().join("boquilahub_download_http_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let url = serve(content.clone());
+
+    // A part left by an older version of the file: the server sends it all.
+    let old = dir.join("old.bq");
+    std::fs::write(download::part_path(&old), vec![0u8; 50_000]).unwrap();
+    std::fs::write(download::validator_path(&old), "\"v1\"").unwrap();
+    download::download(&url, &old, None, |_, _| {}).await.unwrap();
+    assert_eq!(std::fs::read(&old).unwrap(), content);
+    assert!(!download::validator_path(&old).exists());
+
+    // A part longer than the file: 416, then one fresh start.
+    let long = dir.join("long.bq");
+    std::fs::write(download::part_path(&long), vec![0u8; 200_000]).unwrap();
+    std::fs::write(download::validator_path(&long), "\"v2\"").unwrap();
+    download::download(&url, &long, None, |_, _| {}).await.unwrap();
+    assert_eq!(std::fs::read(&long).unwrap(), content);
+
+    // A part of the same version resumes.
+    let half = dir.join("half.bq");
+    std::fs::write(download::part_path(&half), &content[..60_000]).unwrap();
+    std::fs::write(download::validator_path(&half), "\"v2\"").unwrap();
+    let mut first = None;
+    download::download(&url, &half, None, |done, _| {
+        first.get_or_insert(done);
+    })
+    .await
+    .unwrap();
+    assert!(first.unwrap() > 60_000);
+    assert_eq!(std::fs::read(&half).unwrap(), content);
+}
@@ -0,0 +1,76 @@
+use boquilahub::api::abstractions::{AIOutputs, AudioProb, EnsembleConfig, Fusion, Prob, XYXY, XYXYc};
+use boquilahub::api::processing::ensemble::{fuse_audio, fuse_members, fuse_probs};
+
+fn probs(pairs: &[(&str, f32)]) -> Vec<Prob> {
+    pairs.iter().enumerate().map(|(i, (label, p))| Prob::new(label.to_string(), *p, i as u32)).collect()
+}
+
+fn window(start: f32, end: f32, label: &str, prob: f32) -> AudioProb {
+    AudioProb {
+        start,
+        end,
+        prediction: Prob::new(label.to_owned(), prob, 0),
+    }
+}
+
+#[test]
+fn classification_fusion_strategies() {
+    let members = vec![
+        probs(&[("ocelot", 0.6), ("margay", 0.4)]),
+        probs(&[("ocelot", 0.2), ("oncilla", 0.8)]),
+        probs(&[("margay", 0.7), ("ocelot", 0.3)]),
+    ];
+    let find = |fused: &[Prob], label: &str| fused.iter().find(|p| p.label == label).map(|p| p.prob);
+
+    let mean = fuse_probs(&members, Fusion::Mean);
+    assert!((find(&mean, "ocelot").unwrap() - 1.1 / 3.0).abs() < 1e-5);
+    assert!((find(&mean, "oncilla").unwrap() - 0.8 / 3.0).abs() < 1e-5, "missing counts as zero");
+    assert_eq!(mean[0].label, "ocelot", "highest first");
+
+    let max = fuse_probs(&members, Fusion::Max);
+    assert_eq!((max[0].label.as_str(), max[0].prob), ("oncilla", 0.8));
+
+    let vote = fuse_probs(&members, Fusion::Vote);
+    assert_eq!(vote.len(), 3, "one vote each");
+    assert!(vote.iter().all(|p| (p.prob - 1.0 / 3.0).abs() < 1e-5));
+}
+
+#[test]
+fn members_agree_on_class_ids_before_boxes_are_fused() {
+    let det = |x: f32, class_id: u32, label: &str| XYXYc::new(XYXY::new(x, 0.0, x + 100.0, 100.0, 0.8, class_id), label.to_owned());
+    // The two detectors list their classes in different orders.
+    let outputs = vec![
+        AIOutputs::ObjectDetection(vec![det(0.0, 0, "animal")]),
+        AIOutputs::ObjectDetection(vec![det(10.0, 1, "animal"), det(500.0, 0, "vehicle")]),
+    ];
+    let classes = vec!["animal".to_owned(), "vehicle".to_owned()];
+    let AIOutputs::ObjectDetection(fused) = fuse_members(outputs, &EnsembleConfig::default(), &classes, 0.5) else {
+        panic!("expected boxes");
+    };
+    assert_eq!(fused.len(), 2);
+    assert_eq!((fused[0].label.as_str(), fused[0].xyxy.x1, fused[0].xyxy.prob), ("animal", 5.0, 0.8));
+    assert_eq!((fused[1].xyxy.class_id, fused[1].xyxy.prob), (1, 0.4), "seen by one of two models");
+}
+
+#[test]
+fn audio_is_averaged_over_common_windows() {
+    // A 3 s model and a 5 s model over the same 6 s recording.
+    let short = vec![window(0.0, 3.0, "tinamou", 0.9), window(3.0, 6.0, "frog", 0.6)];
+    let long = vec![window(0.0, 5.0, "tinamou", 0.5), window(5.0, 6.0, "frog", 1.0)];
+    let fused = fuse_audio(&[short, long], Fusion::Mean, 3.0);
+    assert_eq!(fused.len(), 2);
+    assert_eq!((fused[0].start, fused[0].end), (0.0, 3.0));
+    assert_eq!(fused[0].prediction.label, "tinamou");
+    assert!((fused[0].prediction.prob - 0.7).abs() < 1e-5);
+
+    // 3–6 s: the long model says tinamou for 2 s and frog for 1 s.
+    assert_eq!(fused[1].prediction.label, "frog");
+    assert!((fused[1].prediction.prob - (0.6 + 1.0 / 3.0) / 2.0).abs() < 1e-5);
+
+    let classes = vec!["tinamou".to_owned(), "frog".to_owned()];
+    let outputs = vec![AIOutputs::AudioClassification(vec![window(0.0, 3.0, "frog", 0.4)])];
+    let AIOutputs::AudioClassification(single) = fuse_members(outputs, &EnsembleConfig::default(), &classes, 0.5) else {
+        panic!("expected audio");
+    };
+    assert_eq!(single[0].prediction.class_id, 1, "class id from the merged list");
+}
@@ -0,0 +1,84 @@
+use boquilahub::api::abstractions::{AIOutputs, PredImg, Prob, XYXY, XYXYc};
+use boquilahub::api::events::{group_events, EventLabel};
+use boquilahub::api::metadata::ImageMetadata;
+use std::path::PathBuf;
+
+fn shot(path: &str, time: &str, aioutput: AIOutputs) -> PredImg {
+    PredImg {
+        file_path: PathBuf::from(path),
+        aioutput: Some(aioutput),
+        wasprocessed: true,
+        provenance: None,
+        metadata: Some(ImageMetadata {
+            captured_at: Some(format!("2024-03-01T{}+00:00", time)),
+            ..Default::default()
+        }),
+    }
+}
+
+fn boxes(labels: &[(&str, f32)]) -> AIOutputs {
+    AIOutputs::ObjectDetection(
+        labels
+            .iter()
+            .map(|&(label, prob)| XYXYc::new(XYXY::new(0.0, 0.0, 1.0, 1.0, prob, 0), label.to_owned()))
+            .collect(),
+    )
+}
+
+fn class(label: &str, prob: f32) -> AIOutputs {
+    AIOutputs::Classification(vec![Prob::new(label.to_owned(), prob, 0)])
+}
+
+#[test]
+fn splits_on_gap_and_camera() {
+    let preds = vec![
+        shot("cam1/a.jpg", "10:00:00", class("deer", 0.9)),
+        shot("cam1/b.jpg", "10:00:40", class("deer", 0.9)),
+        shot("cam1/c.jpg", "10:01:30", class("deer", 0.9)), // 50 s after b
+        shot("cam1/d.jpg", "10:05:00", class("fox", 0.9)),
+        shot("cam2/e.jpg", "10:00:10", class("fox", 0.9)),
+    ];
+    let events = group_events(&preds, 60);
+    let members: Vec<Vec<usize>> = events.iter().map(|e| e.members.clone()).collect();
+    assert_eq!(members, vec![vec![0, 1, 2], vec![4], vec![3]]);
+
+    let events = group_events(&preds, 30);
+    assert_eq!(events.len(), 5);
+}
+
+#[test]
+fn summary_by_confidence_or_vote_with_max_count() {
+    let preds = vec![
+        shot("cam/a.jpg", "10:00:00", boxes(&[("deer", 0.6), ("deer", 0.5)])),
+        shot("cam/b.jpg", "10:00:01", boxes(&[("deer", 0.7)])),
+        shot("cam/c.jpg", "10:00:02", boxes(&[("puma", 0.95)])),
+        shot("cam/d.jpg", "10:00:03", boxes(&[])),
+    ];
+    let events = group_events(&preds, 60);
+    assert_eq!(events.len(), 1);
+
+    let best = events[0].summarize(&preds, EventLabel::MaxConfidence);
+    assert_eq!(best.label.as_deref(), Some("puma"));
+    assert_eq!(best.max_count, 1);
+
+    let voted = events[0].summarize(&preds, EventLabel::Vote);
+    assert_eq!(voted.label.as_deref(), Some("deer"));
+    assert_eq!(voted.prob, Some(0.7));
+    assert_eq!(voted.max_count, 2, "two deer in the first frame");
+}
+
+#[test]
+fn summary_names_species_from_the_crop_classifier() {
+    let animal = |species: &str, prob: f32| {
+        let mut det = XYXYc::new(XYXY::new(0.0, 0.0, 1.0, 1.0, 0.9, 0), "animal".to_owned());
+        det.extra_cls = Some(vec![Prob::new(species.to_owned(), prob, 3)]);
+        det
+    };
+    let preds = vec![
+        shot("cam/a.jpg", "10:00:00", AIOutputs::ObjectDetection(vec![animal("ocelot", 0.8), animal("ocelot", 0.7)])),
+        shot("cam/b.jpg", "10:00:01", AIOutputs::ObjectDetection(vec![animal("ocelot", 0.6)])),
+    ];
+    let summary = group_events(&preds, 60)[0].summarize(&preds, EventLabel::Vote);
+    assert_eq!(summary.label.as_deref(), Some("ocelot"), "not the detector's \"animal\"");
+    assert_eq!((summary.class_id, summary.prob, summary.max_count), (Some(3), Some(0.8), 2));
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{AIOutputs, XY, XYXY, XYXYc, XYc};
+use boquilahub::api::geojson::{FeatureCollection, Geometry};
+use boquilahub::api::geotiff::{epsg_from_geokeys, to_geojson, GeoTiff, GeoTransform};
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::tags::{PhotometricInterpretation, PlanarConfiguration, Tag};
+
+#[test]
+fn geotransform_from_tiepoint_and_scale() {
+    let transform = GeoTransform::from_tags(
+        Some(&[0.5, 0.25, 0.0]),
+        Some(&[0.0, 0.0, 0.0, 500000.0, 4000000.0, 0.0]),
+        None,
+    )
+    .unwrap();
+    assert_eq!(transform.apply(0.0, 0.0), [500000.0, 4000000.0]);
+    assert_eq!(transform.apply(10.0, 8.0), [500005.0, 3999998.0], "rows go south");
+    assert!(GeoTransform::from_tags(None, Some(&[0.0; 6]), None).is_none());
+
+    // Geokey directory: header, then GTModelType and ProjectedCSType entries.
+    let keys = [1, 1, 0, 2, 1024, 0, 1, 1, 3072, 0, 1, 32633];
+    assert_eq!(epsg_from_geokeys(&keys), Some(32633));
+    assert_eq!(epsg_from_geokeys(&[1, 1, 0, 1, 3072, 0, 1, 32767]), None, "user-defined");
+}
+
+#[test]
+fn reads_windows_across_strips() -> Result<()> {
+    let path = std::env::temp_dir().join("boquilahub_geotiff_test.tif");
+    let (width, height) = (20u32, 12u32);
+    let data: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 7]))
+        .collect();
+    {
+        let mut encoder = TiffEncoder::new(std::fs::File::create(&path)?)?;
+        let mut image = encoder.new_image::<colortype::RGB8>(width, height)?;
+        image.rows_per_strip(5)?;
+        image.encoder().write_tag(Tag::ModelPixelScaleTag, &[2.0f64, 2.0, 0.0][..])?;
+        image.encoder().write_tag(Tag::ModelTiepointTag, &[0.0f64, 0.0, 0.0, 100.0, 200.0, 0.0][..])?;
+        image.encoder().write_tag(Tag::GeoKeyDirectoryTag, &[1u16, 1, 0, 1, 3072, 0, 1, 32633][..])?;
+        image.write_data(&data)?;
+    }
+
+    let mut tif = GeoTiff::open(&path)?;
+    assert_eq!((tif.width, tif.height, tif.epsg), (20, 12, Some(32633)));
+    assert_eq!(tif.transform.apply(3.0, 4.0), [106.0, 192.0]);
+
+    let window = tif.read_window(&XYXY::new(15.0, 3.0, 30.0, 9.0, 1.0, 0))?;
+    assert_eq!(window.dimensions(), (5, 6), "clipped to the raster");
+    assert_eq!(window.get_pixel(0, 0).0, [15, 3, 7]);
+    assert_eq!(window.get_pixel(4, 5).0, [19, 8, 7], "second strip");
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn reads_band_interleaved_files() -> Result<()> {
+    // GDAL's INTERLEAVE=BAND: every band in its own strips, red first.
+    let path = std::env::temp_dir().join("boquilahub_geotiff_planar_test.tif");
+    let (width, height, rows_per_strip) = (20u32, 12u32, 5u32);
+    let bands: [Vec<u8>; 3] = [
+        (0..height).flat_map(|_| 0..width as u8).collect(),
+        (0..height).flat_map(|y| (0..width).map(move |_| y as u8)).collect(),
+        vec![7; (width * height) as usize],
+    ];
+    {
+        let mut encoder = TiffEncoder::new(std::fs::File::create(&path)?)?;
+        let mut dir = encoder.image_directory()?;
+        let (mut offsets, mut counts) = (Vec::new(), Vec::new());
+        for band in &bands {
+            for strip in band.chunks((width * rows_per_strip) as usize) {
+                offsets.push(dir.write_data(strip)? as u32);
+                counts.push(strip.len() as u32);
+            }
+        }
+        dir.write_tag(Tag::ImageWidth, width)?;
+        dir.write_tag(Tag::ImageLength, height)?;
+        dir.write_tag(Tag::BitsPerSample, &[8u16, 8, 8][..])?;
+        dir.write_tag(Tag::Compression, 1u16)?;
+        dir.write_tag(Tag::PhotometricInterpretation, PhotometricInterpretation::RGB)?;
+        dir.write_tag(Tag::StripOffsets, &offsets[..])?;
+        dir.write_tag(Tag::SamplesPerPixel, 3u16)?;
+        dir.write_tag(Tag::RowsPerStrip, rows_per_strip)?;
+        dir.write_tag(Tag::StripByteCounts, &counts[..])?;
+        dir.write_tag(Tag::PlanarConfiguration, PlanarConfiguration::Planar)?;
+        dir.write_tag(Tag::ModelPixelScaleTag, &[2.0f64, 2.0, 0.0][..])?;
+        dir.write_tag(Tag::ModelTiepointTag, &[0.0f64, 0.0, 0.0, 100.0, 200.0, 0.0][..])?;
+        dir.finish()?;
+    }
+
+    let mut tif = GeoTiff::open(&path)?;
+    let window = tif.read_window(&XYXY::new(15.0, 3.0, 30.0, 9.0, 1.0, 0))?;
+    assert_eq!(window.dimensions(), (5, 6));
+    assert_eq!(window.get_pixel(0, 0).0, [15, 3, 7]);
+    assert_eq!(window.get_pixel(4, 5).0, [19, 8, 7], "second strip of each band");
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn detections_become_map_features() -> Result<()> {
+    let transform = GeoTransform([100.0, 2.0, 0.0, 200.0, 0.0, -2.0]);
+    let boxes = AIOutputs::ObjectDetection(vec![XYXYc::new(XYXY::new(1.0, 1.0, 3.0, 2.0, 0.8, 0), "animal".to_owned())]);
+    let collection = to_geojson(&boxes, &transform, Some(32633));
+    let Geometry::Polygon(rings) = &collection.features[0].geometry else {
+        panic!("boxes are polygons");
+    };
+    assert_eq!(rings[0], vec![[102.0, 198.0], [106.0, 198.0], [106.0, 196.0], [102.0, 196.0], [102.0, 198.0]]);
+    assert_eq!(collection.features[0].properties["label"], "animal");
+
+    let points = AIOutputs::PointDetection(vec![XYc::new(XY::new(5.0, 5.0, 0.9, 1), "zebra".to_owned())]);
+    let json = serde_json::to_string(&to_geojson(&points, &transform, None))?;
+    assert!(json.contains(r#""type":"FeatureCollection""#));
+    assert!(json.contains(r#""geometry":{"type":"Point","coordinates":[110.0,190.0]}"#));
+    assert!(!json.contains("crs"));
+    let back: FeatureCollection = serde_json::from_str(&json)?;
+    assert_eq!(back.features.len(), 1);
+    Ok(())
+}
@@ -15,3 +15,61 @@ async fn image_inference() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn registry_models_side_by_side() -> Result<()> {
+    use boquilahub::api::abstractions::{AIOutputs, ModelConfig};
+    use boquilahub::api::registry::ModelRegistry;
+
+    let img = image::open("tests/assets/img.jpg")?.to_rgb8();
+    let model_path = "tests/assets/yolo11n-seg.bq";
+    let strict_config = ModelConfig {
+        confidence_threshold: 0.9,
+        ..Default::default()
+    };
+
+    let loose = ModelRegistry::load("seg_loose", model_path, Ep::Cpu, None)?;
+    let strict = ModelRegistry::load("seg_strict", model_path, Ep::Cpu, Some(strict_config))?;
+    assert!(ModelRegistry::memory_bytes() >= loose.memory_bytes() + strict.memory_bytes());
+
+    let count = |aio: AIOutputs| match aio {
+        AIOutputs::Segmentation(segs) => segs.len(),
+        _ => 0,
+    };
+    let n_loose = count(process_imgbuf_with(&loose, None, &img)?);
+    let n_strict = count(process_imgbuf_with(&strict, None, &img)?);
+    assert!(n_strict <= n_loose);
+
+    // Unloading drops the registry's entry; handles in use keep working.
+    assert!(ModelRegistry::unload("seg_loose"));
+    assert!(ModelRegistry::get("seg_loose").is_none());
+    assert!(ModelRegistry::list().iter().any(|m| m.name() == "seg_strict"));
+    process_imgbuf_with(&loose, None, &img)?;
+    assert!(!ModelRegistry::unload("seg_loose"));
+    ModelRegistry::unload("seg_strict");
+
+    Ok(())
+}
+
+#[test]
+fn batched_images_match_single_runs() -> Result<()> {
+    use boquilahub::api::abstractions::AIOutputs;
+    use boquilahub::api::registry::ModelRegistry;
+
+    let img = image::open("tests/assets/img.jpg")?.to_rgb8();
+    let imgs = vec![img.clone(), image::imageops::flip_horizontal(&img), img];
+    let model = ModelRegistry::load("seg_batch", "tests/assets/yolo11n-seg.bq", Ep::Cpu, None)?;
+
+    let count = |aio: &AIOutputs| match aio {
+        AIOutputs::Segmentation(segs) => segs.len(),
+        _ => 0,
+    };
+    let batched = model.run_images(&imgs)?;
+    assert_eq!(batched.len(), imgs.len());
+    for (img, aio) in imgs.iter().zip(&batched) {
+        assert_eq!(count(aio), count(&process_imgbuf_with(&model, None, img)?));
+    }
+    ModelRegistry::unload("seg_batch");
+
+    Ok(())
+}
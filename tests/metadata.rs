@@ -0,0 +1,112 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{AIOutputs, Pred, PredImg};
+use boquilahub::api::metadata::ImageMetadata;
+use boquilahub::api::tabular::ToRows;
+use exif::experimental::Writer;
+use exif::{Field, In, Rational, Tag, Value};
+
+fn field(tag: Tag, value: Value) -> Field {
+    Field { tag, ifd_num: In::PRIMARY, value }
+}
+
+fn ascii(s: &str) -> Value {
+    Value::Ascii(vec![s.as_bytes().to_vec()])
+}
+
+fn rational(num: u32, denom: u32) -> Rational {
+    Rational { num, denom }
+}
+
+/// A Reconyx HyperFire MakerNote: motion trigger, shot 2 of 3, event 7,
+/// 21 °C, serial "H500ABC".
+fn reconyx_note() -> Vec<u8> {
+    let mut note = vec![0u8; 80];
+    note[..2].copy_from_slice(&0xf101u16.to_le_bytes());
+    note[12] = b'M';
+    note[14..16].copy_from_slice(&2u16.to_le_bytes());
+    note[16..18].copy_from_slice(&3u16.to_le_bytes());
+    note[20..22].copy_from_slice(&7u16.to_le_bytes());
+    note[38..40].copy_from_slice(&21i16.to_le_bytes());
+    for (i, unit) in "H500ABC".encode_utf16().enumerate() {
+        note[40 + 2 * i..42 + 2 * i].copy_from_slice(&unit.to_le_bytes());
+    }
+    note
+}
+
+/// `tests/assets/img.jpg` with an APP1 Exif segment spliced in after SOI.
+fn jpeg_with_exif(path: &std::path::Path) -> Result<()> {
+    let fields = [
+        field(Tag::Make, ascii("RECONYX")),
+        field(Tag::Model, ascii("HC600 HYPERFIRE")),
+        field(Tag::DateTimeOriginal, ascii("2024:03:01 05:42:10")),
+        field(Tag::OffsetTimeOriginal, ascii("-03:00")),
+        field(Tag::GPSLatitudeRef, ascii("S")),
+        field(Tag::GPSLatitude, Value::Rational(vec![rational(33, 1), rational(30, 1), rational(0, 1)])),
+        field(Tag::GPSLongitudeRef, ascii("W")),
+        field(Tag::GPSLongitude, Value::Rational(vec![rational(70, 1), rational(45, 1), rational(0, 1)])),
+        field(Tag::MakerNote, Value::Undefined(reconyx_note(), 0)),
+    ];
+    let mut writer = Writer::new();
+    for f in &fields {
+        writer.push_field(f);
+    }
+    let mut tiff = std::io::Cursor::new(Vec::new());
+    writer.write(&mut tiff, true)?;
+    let tiff = tiff.into_inner();
+
+    let original = std::fs::read("tests/assets/img.jpg")?;
+    let mut jpeg = original[..2].to_vec();
+    jpeg.extend_from_slice(&[0xff, 0xe1]);
+    jpeg.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+    jpeg.extend_from_slice(b"Exif\0\0");
+    jpeg.extend_from_slice(&tiff);
+    jpeg.extend_from_slice(&original[2..]);
+    std::fs::write(path, jpeg)?;
+    Ok(())
+}
+
+#[test]
+fn reads_exif_and_reconyx_maker_note() -> Result<()> {
+    let dir = std::env::temp_dir().join("boquilahub_metadata_test");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("trap.jpg");
+    jpeg_with_exif(&path)?;
+
+    let metadata = ImageMetadata::read(&path).expect("EXIF present");
+    assert_eq!(metadata.captured_at.as_deref(), Some("2024-03-01T05:42:10-03:00"));
+    assert_eq!(metadata.camera().as_deref(), Some("RECONYX HC600 HYPERFIRE"));
+    assert_eq!(metadata.latitude, Some(-33.5));
+    assert_eq!(metadata.longitude, Some(-70.75));
+    assert_eq!(metadata.serial_number.as_deref(), Some("H500ABC"));
+    assert_eq!(metadata.trigger.as_deref(), Some("motion"));
+    assert_eq!(metadata.sequence, Some((2, 3)));
+    assert_eq!(metadata.event_number, Some(7));
+    assert_eq!(metadata.temperature_c, Some(21));
+    assert_eq!(
+        metadata.capture_time().map(|t| t.to_rfc3339()),
+        Some(chrono::DateTime::parse_from_rfc3339("2024-03-01T08:42:10Z")?.with_timezone(&chrono::Local).to_rfc3339())
+    );
+
+    // Read only when asked for, then kept in the sidecar and in CSV rows.
+    let mut pred = PredImg::new_simple(path.clone());
+    assert_eq!(pred.metadata, None);
+    pred.load_metadata();
+    assert_eq!(pred.metadata.as_ref(), Some(&metadata));
+    pred.aioutput = Some(AIOutputs::Classification(vec![]));
+    pred.wasprocessed = true;
+    pred.write_predictions()?;
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(pred.predictions_file_path()?)?)?;
+    assert_eq!(json["metadata"]["serial_number"], "H500ABC");
+    assert_eq!(pred.to_rows()[0].camera_serial.as_deref(), Some("H500ABC"));
+    let reloaded = PredImg::new_simple(path.clone());
+    assert_eq!(reloaded.metadata.as_ref(), Some(&metadata), "from the sidecar");
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn files_without_exif_have_no_metadata() {
+    assert_eq!(ImageMetadata::read(std::path::Path::new("tests/assets/img.jpg")), None);
+}
@@ -0,0 +1,120 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{
+    AIOutputs, EnsembleConfig, Fusion, ModelConfig, ModelStamp, Pred, PredImg, Provenance, XYXY, XYXYc,
+};
+use boquilahub::api::pipeline::{CropOptions, PipelineConfig};
+use std::path::PathBuf;
+
+#[test]
+fn parses_toml_and_json_pipelines() -> Result<()> {
+    let toml = PipelineConfig::from_toml(
+        r#"
+        [[stages]]
+        model = "MDV6-yolov9-c"
+        confidence_threshold = 0.3
+
+        [[stages]]
+        model = "speciesnet"
+        labels = ["animal"]
+        padding = 0.1
+        min_size = 32
+
+        [[stages]]
+        model = "dinov3"
+        "#,
+    )?;
+    assert_eq!(toml.stages.len(), 3);
+    assert_eq!(toml.stages[0].model_config().confidence_threshold, 0.3);
+    assert_eq!(toml.stages[0].model_config().nms_threshold, 0.4, "unset fields keep the default");
+    assert_eq!(toml.stages[1].crop.labels, vec!["animal".to_string()]);
+    assert_eq!(toml.stages[1].crop.min_size, 32);
+    assert_eq!(toml.stages[2].crop, CropOptions::default());
+
+    let json = PipelineConfig::from_json(&serde_json::to_string(&toml)?)?;
+    assert_eq!(json, toml, "round-trips through JSON");
+    Ok(())
+}
+
+#[test]
+fn rejects_empty_pipelines() {
+    assert!(PipelineConfig::from_json(r#"{"stages": []}"#).is_err());
+    assert!(PipelineConfig::from_json(r#"{"stages": [{"labels": ["animal"]}]}"#).is_err());
+    assert!(PipelineConfig::from_json(r#"{"stages": [{"model": "regional", "ensemble": {"models": []}}]}"#).is_err());
+}
+
+#[test]
+fn parses_ensemble_stages() -> Result<()> {
+    let config = PipelineConfig::from_json(
+        r#"{"stages": [{"model": "regional", "ensemble": {"models": ["amazonia", "andes.bq"], "fusion": "vote"}}]}"#,
+    )?;
+    let ensemble = config.stages[0].ensemble.as_ref().unwrap();
+    assert_eq!(ensemble.models, vec!["amazonia", "andes.bq"]);
+    assert_eq!(ensemble.fusion, Fusion::Vote);
+    assert_eq!(ensemble.window, EnsembleConfig::default().window);
+    assert!(PipelineConfig::from_json(r#"{"stages": [{"model": "x", "ensemble": {"models": ["a"], "fusion": "median"}}]}"#).is_err());
+    Ok(())
+}
+
+#[test]
+fn pipeline_paths_by_extension() {
+    assert!(PipelineConfig::is_pipeline_path("pipelines/traps.toml"));
+    assert!(PipelineConfig::is_pipeline_path("traps.JSON"));
+    assert!(!PipelineConfig::is_pipeline_path("MDV6-yolov9-c"));
+    assert!(!PipelineConfig::is_pipeline_path("models/speciesnet.bq"));
+}
+
+#[test]
+fn crop_filters_and_padding() {
+    let crop = CropOptions {
+        labels: vec!["animal".to_string()],
+        padding: 0.5,
+        min_size: 20,
+    };
+    let det = |label: &str, size: f32| XYXYc::new(XYXY::new(10.0, 10.0, 10.0 + size, 10.0 + size, 0.9, 0), label.to_string());
+
+    assert!(crop.accepts(&det("animal", 40.0)));
+    assert!(!crop.accepts(&det("person", 40.0)), "label not listed");
+    assert!(!crop.accepts(&det("animal", 10.0)), "smaller than min_size");
+    assert!(!CropOptions::default().accepts(&det("animal", 0.0)), "empty boxes never run");
+
+    let padded = crop.crop_box(&XYXY::new(10.0, 10.0, 50.0, 30.0, 0.9, 0), 60, 100);
+    assert_eq!((padded.x1, padded.y1, padded.x2, padded.y2), (0.0, 0.0, 60.0, 40.0));
+}
+
+#[test]
+fn changed_crop_options_make_results_stale() {
+    let stamp = |crop: &CropOptions| ModelStamp {
+        model: "detector".to_owned(),
+        bq_sha256: "00ff".to_owned(),
+        config: ModelConfig::default(),
+        crop: None,
+        model_cls: Some(Box::new(
+            ModelStamp {
+                model: "speciesnet".to_owned(),
+                bq_sha256: "ff00".to_owned(),
+                config: ModelConfig::default(),
+                crop: None,
+                model_cls: None,
+            }
+            .with_crop(crop),
+        )),
+    };
+    let padded = |padding: f32| CropOptions { padding, ..Default::default() };
+    let pred = PredImg {
+        file_path: PathBuf::from("a.jpg"),
+        aioutput: Some(AIOutputs::ObjectDetection(vec![])),
+        wasprocessed: true,
+        provenance: Some(Provenance {
+            stamp: stamp(&padded(0.1)),
+            architecture: "yolo".to_owned(),
+            classes: vec![],
+            ep: "CPU".to_owned(),
+            created_at: "2026-01-01T00:00:00+00:00".to_owned(),
+            boquilahub_version: "0.0.0".to_owned(),
+        }),
+        metadata: None,
+    };
+    assert!(pred.is_up_to_date(&stamp(&padded(0.1))));
+    assert!(!pred.is_up_to_date(&stamp(&padded(0.2))), "new padding runs again");
+    assert_eq!(stamp(&CropOptions::default()).model_cls.unwrap().crop, None, "defaults aren't recorded");
+}
@@ -25,4 +25,92 @@ fn nms_all_class_suppresses_across_classes() {
     assert!(kept.contains(&0), "highest prob box should be kept");
     assert!(!kept.contains(&1), "overlapping box of different class should be suppressed");
     assert!(kept.contains(&2), "non-overlapping box should be kept");
-}
\ No newline at end of file
+}
+#[test]
+fn point_suppression_keeps_strongest_nearby_point() {
+    use boquilahub::api::abstractions::XY;
+    use boquilahub::api::processing::post::point_suppression_indices;
+
+    let points = vec![
+        XY::new(10.0, 10.0, 0.6, 0),
+        XY::new(13.0, 14.0, 0.9, 0),
+        XY::new(12.0, 12.0, 0.8, 1),
+        XY::new(50.0, 50.0, 0.5, 0),
+    ];
+    let kept = point_suppression_indices(&points, 8.0, true);
+    assert_eq!(kept, vec![1, 2, 3], "weaker same-class neighbour is dropped");
+    let kept = point_suppression_indices(&points, 8.0, false);
+    assert_eq!(kept, vec![1, 3], "across classes only the strongest survives");
+}
+
+#[test]
+fn weighted_box_fusion_averages_overlapping_boxes() {
+    use boquilahub::api::processing::post::weighted_box_fusion;
+
+    let boxes = vec![
+        XYXY::new(0.0, 0.0, 100.0, 100.0, 0.9, 0),
+        XYXY::new(10.0, 10.0, 110.0, 110.0, 0.3, 0),
+        XYXY::new(5.0, 5.0, 105.0, 105.0, 0.6, 1),
+        XYXY::new(300.0, 300.0, 400.0, 400.0, 0.8, 0),
+    ];
+    let fused = weighted_box_fusion(&boxes, 0.5, 2);
+    assert_eq!(fused.len(), 3, "the class 1 box is not fused into class 0");
+
+    let (best, xyxy) = fused[0];
+    assert_eq!(best, 0);
+    assert!((xyxy.x1 - 2.5).abs() < 1e-4, "coordinates weighted by score");
+    assert!((xyxy.prob - 0.6).abs() < 1e-4, "mean score of both members");
+
+    let lone = fused.iter().find(|(i, _)| *i == 3).unwrap().1;
+    assert!((lone.prob - 0.4).abs() < 1e-4, "found by one of two sources");
+    assert_eq!((lone.x1, lone.x2), (300.0, 400.0));
+}
+
+#[test]
+fn soft_nms_decays_overlapping_boxes_instead_of_dropping_them() {
+    use boquilahub::api::processing::post::{soft_nms, SoftNmsMethod};
+
+    // Two zebras side by side (IoU ≈ 0.54) and a duplicate of the first (IoU ≈ 0.90).
+    let boxes = vec![
+        XYXY::new(0.0, 0.0, 100.0, 100.0, 0.9, 0),
+        XYXY::new(30.0, 0.0, 130.0, 100.0, 0.8, 0),
+        XYXY::new(2.0, 3.0, 100.0, 100.0, 0.3, 0),
+    ];
+    assert_eq!(nms_indices(&boxes, 0.5, true), vec![0], "hard NMS loses the second zebra");
+
+    let kept = soft_nms(&boxes, 0.5, 0.25, SoftNmsMethod::Linear, true);
+    let indices: Vec<usize> = kept.iter().map(|(i, _)| *i).collect();
+    assert_eq!(indices, vec![0, 1], "the duplicate decays below the score threshold");
+    let iou = boxes[0].iou(&boxes[1]);
+    assert!((kept[1].1.prob - 0.8 * (1.0 - iou)).abs() < 1e-5);
+
+    let kept = soft_nms(&boxes, 0.5, 0.1, SoftNmsMethod::Gaussian, true);
+    assert_eq!(kept.len(), 2);
+    assert!((kept[1].1.prob - 0.8 * (-iou * iou / 0.5).exp()).abs() < 1e-5);
+    assert!(kept.windows(2).all(|w| w[0].1.prob >= w[1].1.prob), "strongest first");
+
+    let other_class = [boxes[0], XYXY::new(1.0, 1.0, 100.0, 100.0, 0.7, 1)];
+    let kept = soft_nms(&other_class, 0.5, 0.25, SoftNmsMethod::Linear, true);
+    assert_eq!(kept[1].1.prob, 0.7, "other classes are left alone per class");
+}
+
+#[test]
+fn box_merging_techniques_from_bq_names() {
+    use boquilahub::api::processing::post::{merge_boxes, PostProcessing, SoftNmsMethod};
+
+    assert_eq!(PostProcessing::from("soft_nms"), PostProcessing::SoftNMS(SoftNmsMethod::Linear));
+    assert_eq!(PostProcessing::from("soft_nms_gaussian"), PostProcessing::SoftNMS(SoftNmsMethod::Gaussian));
+    assert_eq!(PostProcessing::from("WBF"), PostProcessing::WBF);
+    assert!(PostProcessing::WBF.merges_boxes() && !PostProcessing::GeoFence.merges_boxes());
+
+    let boxes = vec![
+        XYXY::new(0.0, 0.0, 100.0, 100.0, 0.8, 0),
+        XYXY::new(10.0, 0.0, 110.0, 100.0, 0.8, 0),
+    ];
+    let fused = merge_boxes(&boxes, PostProcessing::WBF, 0.5, 0.25, true).unwrap();
+    assert_eq!(fused.len(), 1);
+    assert_eq!((fused[0].1.x1, fused[0].1.x2, fused[0].1.prob), (5.0, 105.0, 0.8), "one model: no score penalty");
+    let kept = merge_boxes(&boxes, PostProcessing::NMS, 0.5, 0.25, true).unwrap();
+    assert_eq!(kept.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![0]);
+    assert!(merge_boxes(&boxes, PostProcessing::Rollup, 0.5, 0.25, true).is_none());
+}
@@ -0,0 +1,57 @@
+use boquilahub::api::abstractions::{AIOutputs, PredImg, PredVideo, Prob, XYXY, XYXYc};
+use boquilahub::api::tabular::ToRows;
+use std::path::PathBuf;
+
+fn img(aioutput: AIOutputs) -> PredImg {
+    PredImg {
+        file_path: PathBuf::from("a.jpg"),
+        aioutput: Some(aioutput),
+        wasprocessed: true,
+        provenance: None,
+        metadata: None,
+    }
+}
+
+#[test]
+fn detections_become_one_row_each_with_extra_cls_top1() {
+    let mut fox = XYXYc::new(XYXY::new(0.0, 0.0, 10.0, 10.0, 0.9, 1), "animal".to_owned());
+    fox.extra_cls = Some(vec![
+        Prob::new("fox".to_owned(), 0.8, 3),
+        Prob::new("dog".to_owned(), 0.2, 4),
+    ]);
+    let person = XYXYc::new(XYXY::new(5.0, 5.0, 20.0, 20.0, 0.6, 0), "person".to_owned());
+
+    let rows = img(AIOutputs::ObjectDetection(vec![fox, person])).to_rows();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].file, "a.jpg");
+    assert_eq!(rows[0].x2, Some(10.0));
+    assert_eq!(rows[0].cls_label.as_deref(), Some("fox"));
+    assert_eq!(rows[1].label, "person");
+    assert_eq!(rows[1].cls_label, None);
+}
+
+#[test]
+fn empty_and_unprocessed_files() {
+    let rows = img(AIOutputs::ObjectDetection(vec![])).to_rows();
+    assert_eq!(rows.len(), 1, "processed-but-empty files keep one row");
+    assert!(rows[0].label.is_empty());
+
+    let mut unprocessed = img(AIOutputs::Classification(vec![]));
+    unprocessed.wasprocessed = false;
+    assert!(unprocessed.to_rows().is_empty());
+}
+
+#[test]
+fn video_rows_carry_frame_and_time() {
+    let mut video = PredVideo::new_simple(PathBuf::from("missing.mp4"));
+    video.hydrate(640, 480, 10.0, 30);
+    video.set_step(5);
+    video.record(20, AIOutputs::Classification(vec![Prob::new("deer".to_owned(), 0.7, 2)]));
+
+    let rows = video.to_rows();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].frame, Some(20));
+    assert_eq!(rows[0].start_s, Some(2.0));
+    assert_eq!(rows[0].end_s, Some(2.5), "the frame stands for the next `step` frames");
+    assert_eq!(rows[0].label, "deer");
+}
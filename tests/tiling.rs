@@ -0,0 +1,44 @@
+use boquilahub::api::abstractions::{AIOutputs, TileConfig, XYXY, XYXYc};
+use boquilahub::api::processing::tiling::{merge_tiles, tile_grid};
+
+#[test]
+fn tiles_cover_the_image() {
+    let config = TileConfig {
+        size: 100,
+        overlap: 0.2,
+        full_image: false,
+        ..Default::default()
+    };
+    let tiles = tile_grid(250, 100, &config);
+    let xs: Vec<(f32, f32)> = tiles.iter().map(|t| (t.x1, t.x2)).collect();
+    assert_eq!(xs, vec![(0.0, 100.0), (80.0, 180.0), (150.0, 250.0)], "last tile is shifted inwards");
+    assert!(tiles.iter().all(|t| t.y1 == 0.0 && t.y2 == 100.0));
+
+    assert_eq!(tile_grid(80, 60, &config).len(), 1, "small images are a single tile");
+    let with_full = tile_grid(250, 100, &TileConfig { size: 100, ..Default::default() });
+    assert_eq!((with_full[0].x2, with_full[0].y2), (250.0, 100.0), "full image first");
+    assert_eq!(with_full.len(), 4);
+}
+
+#[test]
+fn merged_tiles_use_image_coordinates() {
+    let det = |x1: f32, x2: f32, prob: f32| XYXYc::new(XYXY::new(x1, 0.0, x2, 10.0, prob, 0), "animal".to_string());
+    let tiles = vec![
+        (XYXY::new(0.0, 0.0, 100.0, 100.0, 1.0, 0), AIOutputs::ObjectDetection(vec![det(85.0, 95.0, 0.9)])),
+        (XYXY::new(80.0, 0.0, 180.0, 100.0, 1.0, 0), AIOutputs::ObjectDetection(vec![det(5.0, 15.0, 0.7), det(50.0, 60.0, 0.8)])),
+    ];
+    let AIOutputs::ObjectDetection(merged) = merge_tiles(tiles, 0.4, 8.0) else {
+        panic!("expected detections");
+    };
+    let xs: Vec<(f32, f32)> = merged.iter().map(|d| (d.xyxy.x1, d.xyxy.x2)).collect();
+    assert_eq!(xs, vec![(85.0, 95.0), (130.0, 140.0)], "duplicate from the overlap is suppressed");
+}
+
+#[test]
+fn empty_tiles_keep_the_output_kind() {
+    let tiles = tile_grid(250, 100, &TileConfig { size: 100, overlap: 0.2, full_image: false, ..Default::default() });
+    let empty = |output: fn() -> AIOutputs| tiles.iter().map(move |&tile| (tile, output()));
+    assert!(matches!(merge_tiles(empty(|| AIOutputs::Segmentation(vec![])), 0.4, 8.0), AIOutputs::Segmentation(s) if s.is_empty()));
+    assert!(matches!(merge_tiles(empty(|| AIOutputs::PointDetection(vec![])), 0.4, 8.0), AIOutputs::PointDetection(p) if p.is_empty()));
+    assert!(matches!(merge_tiles(empty(|| AIOutputs::ObjectDetection(vec![])), 0.4, 8.0), AIOutputs::ObjectDetection(d) if d.is_empty()));
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use boquilahub::api::abstractions::{AIOutputs, Pred, PredImg, XYXY, XYXYc};
+use boquilahub::api::triage::{self, TriageConfig, TriageMode};
+use std::path::{Path, PathBuf};
+
+fn img(path: PathBuf, labels: &[(&str, f32)]) -> PredImg {
+    let boxes = labels
+        .iter()
+        .map(|&(label, prob)| XYXYc::new(XYXY::new(0.0, 0.0, 1.0, 1.0, prob, 0), label.to_owned()))
+        .collect();
+    PredImg {
+        file_path: path,
+        aioutput: Some(AIOutputs::ObjectDetection(boxes)),
+        wasprocessed: true,
+        provenance: None,
+        metadata: None,
+    }
+}
+
+#[test]
+fn folders_follow_label_thresholds() -> Result<()> {
+    let mut config = TriageConfig::default();
+    let (label, threshold) = TriageConfig::parse_threshold("person=0.2")?;
+    config.thresholds.insert(label, threshold);
+
+    let folder = |labels: &[(&str, f32)]| config.folder_for(&img(PathBuf::from("a.jpg"), labels));
+    assert_eq!(folder(&[]).as_deref(), Some("empty"));
+    assert_eq!(folder(&[("animal", 0.9)]).as_deref(), Some("animal"));
+    assert_eq!(folder(&[("animal", 0.3)]).as_deref(), Some("empty"));
+    assert_eq!(folder(&[("person", 0.3)]).as_deref(), Some("person"));
+    assert_eq!(folder(&[("a/b", 0.9)]).as_deref(), Some("a_b"));
+
+    let mut unprocessed = img(PathBuf::from("a.jpg"), &[]);
+    unprocessed.wasprocessed = false;
+    assert_eq!(config.folder_for(&unprocessed), None);
+    Ok(())
+}
+
+#[test]
+fn copies_images_with_sidecars_and_dry_run_touches_nothing() -> Result<()> {
+    let dir = std::env::temp_dir().join("boquilahub_triage_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("a"))?;
+    std::fs::create_dir_all(dir.join("b"))?;
+    let preds = vec![
+        img(dir.join("a/img.jpg"), &[("animal", 0.9)]),
+        img(dir.join("b/img.jpg"), &[("animal", 0.8)]),
+        img(dir.join("b/blank.jpg"), &[]),
+    ];
+    for p in &preds {
+        std::fs::copy("tests/assets/img.jpg", &p.file_path)?;
+        p.write_predictions()?;
+    }
+
+    let out = dir.join("sorted");
+    let moves = triage::plan(&preds, &out, &TriageConfig::default());
+    assert_eq!(moves[1].to, out.join("animal/img_1.jpg"), "name clash gets a suffix");
+    assert_eq!(triage::counts(&moves), vec![("animal".to_owned(), 2), ("empty".to_owned(), 1)]);
+
+    assert_eq!(triage::apply(&moves, TriageMode::DryRun, |_| {})?, 0);
+    assert!(!out.exists());
+
+    assert_eq!(triage::apply(&moves, TriageMode::Copy, |_| {})?, 3);
+    assert!(out.join("empty/blank.jpg").exists());
+    assert!(out.join("animal/img_1_predictions.json").exists());
+    assert!(Path::new(&preds[0].file_path).exists(), "copy keeps the original");
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn sidecar_names_never_clash_and_partial_moves_are_reported() -> Result<()> {
+    let dir = std::env::temp_dir().join("boquilahub_triage_move_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let out = dir.join(triage::OUTPUT_FOLDER);
+    std::fs::create_dir_all(out.join("animal"))?;
+    let preds = [
+        img(dir.join("img.jpg"), &[("animal", 0.9)]),
+        img(dir.join("img.png"), &[("animal", 0.9)]),
+        img(dir.join("missing.jpg"), &[("animal", 0.9)]),
+        img(out.join("animal/old.jpg"), &[("animal", 0.9)]),
+    ];
+    for p in &preds[..2] {
+        std::fs::copy("tests/assets/img.jpg", &p.file_path)?;
+        p.write_predictions()?;
+    }
+
+    assert!(triage::in_output(&preds[3].file_path, &dir, &out), "earlier output is left alone");
+    assert!(!triage::in_output(&preds[0].file_path, &dir, &out));
+    assert!(!triage::in_output(&preds[0].file_path, &dir, &dir), "sorting in place");
+
+    let moves = triage::plan(&preds[..3], &out, &TriageConfig::default());
+    assert_eq!(moves[1].to, out.join("animal/img_1.png"), "img.jpg's sidecar already takes the name");
+
+    let mut moved = Vec::new();
+    let result = triage::apply(&moves, TriageMode::Move, |m| moved.push(m.to.clone()));
+    assert!(result.is_err(), "missing.jpg can't be moved");
+    assert_eq!(moved, [moves[0].to.clone(), moves[1].to.clone()]);
+    assert!(out.join("animal/img_predictions.json").exists());
+    assert!(out.join("animal/img_1.png").exists());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use boquilahub::api::abstractions::{AIOutputs, Prob, TtaConfig, XYXY, XYXYc};
+use boquilahub::api::processing::tta::{augment, fuse, restore};
+use image::{ImageBuffer, Rgb};
+
+fn boxes(output: &AIOutputs) -> Vec<XYXY> {
+    match output {
+        AIOutputs::ObjectDetection(dets) => dets.iter().map(|det| det.xyxy).collect(),
+        _ => panic!("expected boxes"),
+    }
+}
+
+#[test]
+fn augmented_boxes_map_back_to_the_original() {
+    let img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::from_fn(120, 60, |x, _| Rgb([x as u8, 0, 0]));
+    let config = TtaConfig { flip: true, scales: vec![1.2, 0.8] };
+    let variants = augment(&img, &config);
+    assert_eq!(variants.len(), 6, "three framings, each also flipped");
+    assert_eq!(variants[0].1, img, "original first");
+    assert_eq!(variants[1].1.get_pixel(0, 0).0[0], 119, "mirrored");
+    assert_eq!(variants[2].1.dimensions(), (100, 50), "zoomed in");
+    assert_eq!(variants[4].1.dimensions(), (150, 75), "zoomed out");
+
+    // The same animal, as each copy would report it.
+    let animal = XYXY::new(10.0, 20.0, 40.0, 50.0, 0.9, 0);
+    for (aug, copy) in &variants {
+        let (dx, dy) = aug.offset;
+        let (mut x1, mut x2) = (animal.x1 - dx, animal.x2 - dx);
+        if aug.flip {
+            (x1, x2) = (copy.width() as f32 - x2, copy.width() as f32 - x1);
+        }
+        let seen = XYXY::new(x1, animal.y1 - dy, x2, animal.y2 - dy, 0.9, 0);
+        let output = restore(AIOutputs::ObjectDetection(vec![XYXYc::new(seen, "deer".to_owned())]), aug);
+        let back = boxes(&output)[0];
+        assert!((back.x1 - animal.x1).abs() < 1e-3 && (back.x2 - animal.x2).abs() < 1e-3);
+        assert!((back.y1 - animal.y1).abs() < 1e-3 && (back.y2 - animal.y2).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn fused_outputs_average_probs_and_boxes() {
+    let probs = |p: f32| AIOutputs::Classification(vec![Prob::new("ocelot".to_owned(), p, 3)]);
+    let AIOutputs::Classification(averaged) = fuse(vec![probs(0.9), probs(0.5), AIOutputs::Classification(Vec::new())], 0.5) else {
+        panic!("expected probabilities");
+    };
+    assert_eq!(averaged.len(), 1);
+    assert!((averaged[0].prob - 0.466_666).abs() < 1e-4, "missing counts as zero");
+
+    let det = |x: f32, prob: f32| AIOutputs::ObjectDetection(vec![XYXYc::new(XYXY::new(x, 0.0, x + 100.0, 100.0, prob, 0), "deer".to_owned())]);
+    let fused = boxes(&fuse(vec![det(0.0, 0.8), det(10.0, 0.8)], 0.5));
+    assert_eq!(fused.len(), 1, "one animal seen twice");
+    assert!((fused[0].x1 - 5.0).abs() < 1e-4);
+}